@@ -0,0 +1,11 @@
+//! # Binaire Boot
+//!
+//! Ce binaire est exécuté au démarrage de la machine (script de démarrage GPO
+//! sous Windows, service systemd sous Linux), avant toute ouverture de session.
+
+use winlog_client::{data_structures::Action, utils};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Action "B" = Démarrage
+    utils::process_session_event(Action::Boot, "boot")
+}
\ No newline at end of file
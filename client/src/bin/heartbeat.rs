@@ -0,0 +1,13 @@
+//! # Binaire Heartbeat
+//!
+//! Ce binaire est exécuté périodiquement pendant la session (tâche planifiée
+//! Windows répétée toutes les 5 minutes, timer systemd utilisateur sous Linux).
+//! Sans signal de présence pendant `[sessions] heartbeat_timeout_minutes`, le
+//! serveur ferme la session (coupure de courant, plantage).
+
+use winlog_client::{data_structures::Action, utils};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Action "H" = Signal de présence
+    utils::process_session_event(Action::Heartbeat, "heartbeat")
+}
\ No newline at end of file
@@ -0,0 +1,12 @@
+//! # Binaire Lock
+//!
+//! Ce binaire est exécuté au verrouillage de l'écran (tâche planifiée Windows sur
+//! l'événement de verrouillage, hook du gestionnaire de session sous Linux).
+//! Le serveur en déduit le temps actif de la session.
+
+use winlog_client::{data_structures::Action, utils};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Action "L" = Verrouillage
+    utils::process_session_event(Action::Lock, "lock")
+}
\ No newline at end of file
@@ -3,9 +3,9 @@
 //! Ce binaire est exécuté lors de l'ouverture d'une session (Windows/Linux).
 //! Il collecte les informations de base et les envoie au serveur de monitoring.
 
-use winlog_client::utils;
+use winlog_client::{data_structures::Action, utils};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Action "C" = Connexion
-    utils::process_session_event("C", "logon")
+    utils::process_session_event(Action::Connect, "logon")
 }
\ No newline at end of file
@@ -0,0 +1,11 @@
+//! # Binaire Shutdown
+//!
+//! Ce binaire est exécuté à l'arrêt de la machine (script d'arrêt GPO sous
+//! Windows, service systemd sous Linux). Budget d'envoi réduit, comme logout.
+
+use winlog_client::{data_structures::Action, utils};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Action "S" = Arrêt
+    utils::process_session_event(Action::Shutdown, "shutdown")
+}
\ No newline at end of file
@@ -0,0 +1,12 @@
+//! # Binaire Unlock
+//!
+//! Ce binaire est exécuté au déverrouillage de l'écran (tâche planifiée Windows sur
+//! l'événement de déverrouillage, hook du gestionnaire de session sous Linux).
+//! Le serveur en déduit le temps actif de la session.
+
+use winlog_client::{data_structures::Action, utils};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Action "U" = Déverrouillage
+    utils::process_session_event(Action::Unlock, "unlock")
+}
\ No newline at end of file
@@ -14,8 +14,16 @@
 //! | `WINLOG_SERVER_URL` | String | `http://127.0.0.1:3000/api/v1/events` | URL du serveur de monitoring |
 //! | `WINLOG_TIMEOUT` | u64 | `30` | Timeout HTTP en secondes |
 //! | `WINLOG_MAX_RETRIES` | u32 | `3` | Nombre de tentatives max |
-//! | `WINLOG_RETRY_DELAY_MS` | u64 | `1000` | Délai entre retries (ms) |
+//! | `WINLOG_RETRY_DELAY_MS` | u64 | `1000` | Délai de base du backoff exponentiel (ms) |
+//! | `WINLOG_TIME_BUDGET` | u64 | `60` | Durée totale max d'un envoi, tentatives comprises (s) |
+//! | `WINLOG_LOGOUT_TIME_BUDGET` | u64 | `10` | Idem pour `logout` (arrêt de la machine) (s) |
 //! | `WINLOG_USER_AGENT` | String | `Winlog/0.1.0` | User-Agent HTTP |
+//! | `WINLOG_HMAC_SECRET` | String | *(aucun)* | Secret partagé pour signer les requêtes |
+//! | `WINLOG_CA_FILE` | String | *(aucun)* | Autorités PEM ajoutées au magasin système (HTTPS) |
+//! | `WINLOG_PIN_SHA256` | String | *(aucun)* | Empreintes SPKI SHA-256 (base64, séparées par des virgules) |
+//! | `WINLOG_TOKEN_FILE` | String | voir [`token_file()`] | Fichier contenant le jeton d'enrôlement de la machine |
+//! | `WINLOG_SPOOL_DIR` | String | voir [`spool_dir()`] | File d'attente des événements non envoyés |
+//! | `WINLOG_SPOOL_MAX_EVENTS` | usize | `1000` | Nombre maximum d'événements en file d'attente |
 //! 
 //! ## Déploiement en production
 //! 
@@ -66,6 +74,8 @@
 //! - ✅ **Maintenance simplifiée** : Un seul binaire pour tous les environnements
 
 use std::env;
+use std::fs;
+use std::path::PathBuf;
 
 // ============================================================================
 // CONSTANTES PAR DÉFAUT (utilisées si variables d'environnement absentes)
@@ -80,12 +90,37 @@ const DEFAULT_TIMEOUT: u64 = 30;
 /// Nombre maximum de tentatives de retry
 const DEFAULT_MAX_RETRIES: u32 = 3;
 
-/// Délai entre les tentatives de retry (en millisecondes)
+/// Délai de base du backoff exponentiel (en millisecondes)
 const DEFAULT_RETRY_DELAY_MS: u64 = 1000;
 
+/// Durée totale maximale d'un envoi, attentes comprises (en secondes)
+const DEFAULT_TIME_BUDGET: u64 = 60;
+
+/// Durée totale maximale d'un envoi par `logout` (en secondes)
+const DEFAULT_LOGOUT_TIME_BUDGET: u64 = 10;
+
 /// User-Agent par défaut utilisé pour les requêtes HTTP
 const DEFAULT_USER_AGENT: &str = "Winlog/0.1.0";
 
+/// Emplacement par défaut du jeton d'enrôlement (Windows)
+#[cfg(windows)]
+const DEFAULT_TOKEN_FILE: &str = r"C:\ProgramData\Winlog\host.token";
+
+/// Emplacement par défaut du jeton d'enrôlement (Linux/Unix)
+#[cfg(not(windows))]
+const DEFAULT_TOKEN_FILE: &str = "/etc/winlog/host.token";
+
+/// Répertoire par défaut de la file d'attente (Windows)
+#[cfg(windows)]
+const DEFAULT_SPOOL_DIR: &str = r"C:\ProgramData\Winlog\spool";
+
+/// Répertoire par défaut de la file d'attente (Linux/Unix)
+#[cfg(not(windows))]
+const DEFAULT_SPOOL_DIR: &str = "/var/spool/winlog";
+
+/// Nombre maximum d'événements en file d'attente par défaut
+const DEFAULT_SPOOL_MAX_EVENTS: usize = 1000;
+
 // ============================================================================
 // FONCTIONS D'ACCÈS À LA CONFIGURATION (lecture avec fallback)
 // ============================================================================
@@ -152,7 +187,11 @@ pub fn max_retries() -> u32 {
         .unwrap_or(DEFAULT_MAX_RETRIES)
 }
 
-/// Récupère le délai entre les retries (en millisecondes)
+/// Récupère le délai de base du backoff entre les retries (en millisecondes)
+///
+/// Le délai effectif double à chaque tentative, avec une gigue aléatoire
+/// (voir [`crate::retry::RetryPolicy::backoff`]). Un en-tête `Retry-After`
+/// du serveur est prioritaire.
 ///
 /// **Priorité** :
 /// 1. Variable d'environnement `WINLOG_RETRY_DELAY_MS` (doit être un entier valide)
@@ -171,6 +210,43 @@ pub fn retry_delay_ms() -> u64 {
         .unwrap_or(DEFAULT_RETRY_DELAY_MS)
 }
 
+/// Récupère la durée totale maximale d'un envoi (en secondes)
+///
+/// **Priorité** :
+/// 1. Variable d'environnement `WINLOG_TIME_BUDGET` (doit être un entier valide)
+/// 2. Constante par défaut (`60`)
+///
+/// Le timeout de chaque tentative est réduit au temps restant, et aucune
+/// attente n'est entamée si elle dépasse le budget.
+pub fn time_budget() -> u64 {
+    env::var("WINLOG_TIME_BUDGET")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_TIME_BUDGET)
+}
+
+/// Récupère la durée totale maximale d'un envoi par `logout` (en secondes)
+///
+/// **Priorité** :
+/// 1. Variable d'environnement `WINLOG_LOGOUT_TIME_BUDGET` (doit être un entier valide)
+/// 2. Constante par défaut (`10`)
+///
+/// `logout` s'exécute pendant la fermeture de session ou l'arrêt de la machine :
+/// il ne doit pas retarder l'arrêt.
+///
+/// # Exemples
+///
+/// ```powershell
+/// $env:WINLOG_LOGOUT_TIME_BUDGET = "5"
+/// .\logout.exe
+/// ```
+pub fn logout_time_budget() -> u64 {
+    env::var("WINLOG_LOGOUT_TIME_BUDGET")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_LOGOUT_TIME_BUDGET)
+}
+
 /// Récupère le User-Agent HTTP
 ///
 /// **Priorité** :
@@ -186,4 +262,146 @@ pub fn retry_delay_ms() -> u64 {
 pub fn user_agent() -> String {
     env::var("WINLOG_USER_AGENT")
         .unwrap_or_else(|_| DEFAULT_USER_AGENT.to_string())
-}
\ No newline at end of file
+}
+
+/// Récupère le secret partagé pour la signature HMAC des requêtes
+///
+/// **Priorité** :
+/// 1. Variable d'environnement `WINLOG_HMAC_SECRET` (ignorée si vide)
+/// 2. Aucun secret : les requêtes ne sont pas signées
+///
+/// Le secret doit figurer dans `security.hmac_secrets` côté serveur.
+///
+/// # Exemples
+///
+/// ```bash
+/// export WINLOG_HMAC_SECRET="change-me"
+/// ./logon
+/// ```
+pub fn hmac_secret() -> Option<String> {
+    env::var("WINLOG_HMAC_SECRET")
+        .ok()
+        .filter(|v| !v.is_empty())
+}
+
+/// Récupère le chemin du fichier contenant le jeton d'enrôlement
+///
+/// **Priorité** :
+/// 1. Variable d'environnement `WINLOG_TOKEN_FILE`
+/// 2. Constante par défaut (`C:\ProgramData\Winlog\host.token` sous Windows,
+///    `/etc/winlog/host.token` sous Linux)
+///
+/// Le fichier doit être lisible par le compte qui exécute les binaires mais
+/// pas modifiable par les utilisateurs (ACL SYSTEM/Administrateurs sous
+/// Windows, `root:root 0644` ou groupe dédié sous Linux).
+pub fn token_file() -> PathBuf {
+    env::var("WINLOG_TOKEN_FILE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(DEFAULT_TOKEN_FILE))
+}
+
+/// Lit le jeton d'enrôlement de la machine
+///
+/// **Retourne** `None` si le fichier est absent ou vide : la requête part alors
+/// sans en-tête `Authorization` (accepté tant que le serveur n'exige pas de jeton).
+///
+/// # Exemples
+///
+/// ```bash
+/// # Linux : enregistrement du jeton délivré par POST /api/v1/enroll
+/// echo -n "<jeton>" | sudo tee /etc/winlog/host.token
+/// sudo chmod 0644 /etc/winlog/host.token
+/// ```
+pub fn host_token() -> Option<String> {
+    let path = token_file();
+    match fs::read_to_string(&path) {
+        Ok(content) => Some(content.trim().to_string()).filter(|t| !t.is_empty()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            eprintln!("[WARN] Impossible de lire le jeton {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Récupère le répertoire de la file d'attente des événements non envoyés
+///
+/// **Priorité** :
+/// 1. Variable d'environnement `WINLOG_SPOOL_DIR` (ignorée si vide)
+/// 2. Constante par défaut (`C:\ProgramData\Winlog\spool` sous Windows,
+///    `/var/spool/winlog` sous Linux)
+///
+/// Le répertoire est propre à la machine et partagé par les comptes qui
+/// exécutent `logon`/`logout` (scripts de session utilisateur) : il doit
+/// appartenir à un groupe dédié regroupant ces comptes, en mode `2770` sous
+/// Linux (`root:winlog`, créé ainsi s'il n'existe pas), ou n'accorder
+/// « Modifier » qu'à ce groupe sous Windows. Un répertoire accessible en
+/// écriture à tous (`1777`) est refusé : n'importe quel compte pourrait y
+/// déposer de faux événements, rejoués avec les droits de la machine.
+pub fn spool_dir() -> PathBuf {
+    env::var("WINLOG_SPOOL_DIR")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_SPOOL_DIR))
+}
+
+/// Récupère le nombre maximum d'événements conservés en file d'attente
+///
+/// **Priorité** :
+/// 1. Variable d'environnement `WINLOG_SPOOL_MAX_EVENTS` (entier strictement positif)
+/// 2. Constante par défaut (`1000`)
+///
+/// Au-delà, un nouvel événement non envoyé est abandonné (erreur) plutôt que
+/// d'écarter les plus anciens, dont dépend l'appariement des sessions.
+pub fn spool_max_events() -> usize {
+    env::var("WINLOG_SPOOL_MAX_EVENTS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&max| max > 0)
+        .unwrap_or(DEFAULT_SPOOL_MAX_EVENTS)
+}
+
+/// Récupère le fichier d'autorités de certification supplémentaires (HTTPS)
+///
+/// **Priorité** :
+/// 1. Variable d'environnement `WINLOG_CA_FILE` (fichier PEM, ignorée si vide)
+/// 2. Aucun fichier : seul le magasin du système est utilisé
+///
+/// # Exemples
+///
+/// ```bash
+/// export WINLOG_SERVER_URL=https://winlog.lab.local:3000/api/v1/events
+/// export WINLOG_CA_FILE=/etc/winlog/ca.pem
+/// ./logon
+/// ```
+pub fn ca_file() -> Option<PathBuf> {
+    env::var("WINLOG_CA_FILE")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Récupère les empreintes de clés publiques épinglées (HTTPS)
+///
+/// **Format** : empreintes SHA-256 du SPKI encodées en base64, séparées par des
+/// virgules (prévoir l'empreinte de la clé suivante avant un renouvellement).
+///
+/// **Retourne** une liste vide si `WINLOG_PIN_SHA256` est absente : pas d'épinglage.
+///
+/// # Exemples
+///
+/// ```powershell
+/// $env:WINLOG_PIN_SHA256 = "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+/// .\logon.exe
+/// ```
+pub fn pin_sha256() -> Vec<String> {
+    env::var("WINLOG_PIN_SHA256")
+        .map(|v| {
+            v.split(',')
+                .map(|pin| pin.trim().to_string())
+                .filter(|pin| !pin.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
@@ -6,62 +6,298 @@
 // Déclaration du module de configuration externe
 pub mod config;
 
+// Configuration TLS (autorités personnalisées, épinglage)
+pub mod tls;
+
+// Politique de nouvel essai (backoff, Retry-After, budget de temps)
+pub mod retry;
+
+// File d'attente persistante des événements non envoyés
+pub mod spool;
+
 /// Module de gestion des requêtes HTTP vers le serveur de monitoring
 pub mod http_client {
-    use minreq;
-    use crate::config;
+    use hmac::{Hmac, Mac};
+    use serde::Deserialize;
+    use sha2::Sha256;
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use crate::{config, retry::{self, Deadline, RetryPolicy}, tls};
+    
+    /// Codes d'erreur du serveur pour lesquels un nouvel essai peut aboutir
+    const RETRYABLE_CODES: &[&str] = &["RATE_LIMITED", "DB_UNAVAILABLE"];
+    
+    /// Corps d'erreur retourné par le serveur (`code` stable, `error` lisible)
+    #[derive(Debug, Deserialize)]
+    struct ErrorBody {
+        code: String,
+        #[serde(default)]
+        error: String,
+        #[serde(default)]
+        details: Option<serde_json::Value>,
+    }
+    
+    /// Événement refusé définitivement par le serveur (données invalides,
+    /// authentification refusée...) : un nouvel essai donnerait le même résultat
+    #[derive(Debug, Clone)]
+    pub struct RejectedError {
+        /// Statut HTTP
+        pub status: u16,
+        /// Code d'erreur stable du serveur (ex : `INVALID_ACTION`), absent si
+        /// la réponse ne provient pas du serveur Winlog (proxy...)
+        pub code: Option<String>,
+        /// Message du serveur
+        pub message: String,
+        /// Détails structurés (champ en cause...)
+        pub details: Option<serde_json::Value>,
+    }
+    
+    impl fmt::Display for RejectedError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "Événement refusé par le serveur (HTTP {}", self.status)?;
+            if let Some(code) = &self.code {
+                write!(f, ", {}", code)?;
+            }
+            write!(f, ") : {}", self.message)?;
+            if let Some(details) = &self.details {
+                write!(f, " {}", details)?;
+            }
+            Ok(())
+        }
+    }
+    
+    impl std::error::Error for RejectedError {}
+    
+    /// Réponse de POST /api/v1/events/batch
+    #[derive(Debug, Deserialize)]
+    struct BatchResponse {
+        results: Vec<BatchItemResult>,
+    }
+    
+    /// Résultat d'un élément d'un lot rejoué
+    #[derive(Debug, Deserialize)]
+    pub struct BatchItemResult {
+        /// Position de l'événement dans le lot
+        pub index: usize,
+        /// "success" ou "error"
+        pub status: String,
+        /// Code d'erreur stable (si échec)
+        #[serde(default)]
+        pub code: Option<String>,
+        /// Message d'erreur (si échec)
+        #[serde(default)]
+        pub error: Option<String>,
+    }
+    
+    impl BatchItemResult {
+        /// Indique si l'élément a été enregistré (ou l'était déjà)
+        pub fn is_success(&self) -> bool {
+            self.status == "success"
+        }
+        
+        /// Indique si l'échec de l'élément est transitoire (à rejouer plus tard)
+        pub fn is_retryable(&self) -> bool {
+            self.code.as_deref().is_some_and(|code| RETRYABLE_CODES.contains(&code))
+        }
+        
+        /// Indique si l'élément a été refusé par la limitation de débit du serveur
+        pub fn is_rate_limited(&self) -> bool {
+            self.code.as_deref() == Some("RATE_LIMITED")
+        }
+    }
+    
+    /// Calcule les en-têtes de signature (horodatage, signature hexadécimale)
+    ///
+    /// Signature = HMAC-SHA256(secret, "<timestamp>.<corps JSON>"), recalculée
+    /// à chaque tentative pour que l'horodatage reste dans la fenêtre du serveur.
+    pub fn sign_body(secret: &str, body: &str) -> (String, String) {
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC accepte des clés de toute longueur");
+        mac.update(timestamp.as_bytes());
+        mac.update(b".");
+        mac.update(body.as_bytes());
+        let signature = hex::encode(mac.finalize().into_bytes());
+        (timestamp, signature)
+    }
     
     /// Client HTTP pour envoyer les données au serveur de monitoring
     pub struct WinlogClient {
         server_url: String,
-        #[allow(dead_code)]  // Timeout conservé pour évolutions futures
-        timeout: std::time::Duration,
+        agent: ureq::Agent,
+        /// Détail du dernier échec d'épinglage TLS (renseigné par le vérificateur)
+        pin_failure: tls::PinFailure,
+        /// Politique de nouvel essai des envois
+        retry_policy: RetryPolicy,
     }
     
     impl WinlogClient {
         /// Crée une nouvelle instance du client HTTP
-        pub fn new(server_url: Option<String>) -> Self {
-            Self {
-                server_url: server_url.unwrap_or_else(|| config::server_url()),
-                timeout: std::time::Duration::from_secs(config::timeout()),
+        ///
+        /// Pour une URL `https://`, la configuration TLS est construite à partir de
+        /// `WINLOG_CA_FILE` et `WINLOG_PIN_SHA256`.
+        ///
+        /// # Erreurs
+        /// Fichier CA illisible ou empreinte d'épinglage mal formée
+        pub fn new(server_url: Option<String>) -> Result<Self, Box<dyn std::error::Error>> {
+            let server_url = server_url.unwrap_or_else(config::server_url);
+            let pin_failure: tls::PinFailure = Arc::new(Mutex::new(None));
+            
+            let mut builder = ureq::AgentBuilder::new()
+                .timeout(std::time::Duration::from_secs(config::timeout()));
+            
+            if server_url.starts_with("https://") {
+                let tls_config = tls::build_client_config(
+                    config::ca_file().as_deref(),
+                    &config::pin_sha256(),
+                    pin_failure.clone(),
+                )?;
+                builder = builder.tls_config(tls_config);
             }
+            
+            Ok(Self {
+                server_url,
+                agent: builder.build(),
+                pin_failure,
+                retry_policy: RetryPolicy::from_config(),
+            })
+        }
+        
+        /// Limite la durée totale d'un envoi (tentatives et attentes comprises)
+        pub fn with_time_budget(mut self, time_budget: Duration) -> Self {
+            self.retry_policy = self.retry_policy.with_time_budget(time_budget);
+            self
         }
         
         /// Envoie les données au serveur via HTTP POST synchrone avec retry
+        ///
+        /// Seuls les échecs transitoires sont retentés (erreur réseau, HTTP 408,
+        /// 429 et 5xx), après l'attente indiquée par `Retry-After` ou un backoff
+        /// exponentiel avec gigue. L'ensemble ne dépasse pas le budget de temps
+        /// de la politique : le timeout de chaque tentative est réduit au temps
+        /// restant.
+        ///
+        /// Un échec d'épinglage TLS interrompt immédiatement les tentatives et
+        /// retourne une `tls::PinningError`. Un refus définitif (autre 4xx, ou
+        /// `code` serveur autre que `RATE_LIMITED`/`DB_UNAVAILABLE`) retourne
+        /// aussitôt une `RejectedError`.
         pub fn send_data(&self, data: &crate::data_structures::WinlogData) -> Result<(), Box<dyn std::error::Error>> {
             let json_data = serde_json::to_string(data)?;
             
             // Debug: Affichage du JSON envoyé
             println!("JSON envoyé: {}", json_data);
             
-            for attempt in 1..=config::max_retries() {
-                println!("Tentative {}/{} d'envoi vers {}", attempt, config::max_retries(), self.server_url);
+            self.post_json(&self.server_url, &json_data)?;
+            Ok(())
+        }
+        
+        /// Rejoue des événements en attente via POST /api/v1/events/batch
+        ///
+        /// Même politique de nouvel essai que `send_data` pour la requête globale.
+        ///
+        /// # Retourne
+        /// Le résultat de chaque élément, dans l'ordre de `events`
+        pub fn send_batch(&self, events: &[crate::data_structures::WinlogData]) -> Result<Vec<BatchItemResult>, Box<dyn std::error::Error>> {
+            let json_data = serde_json::to_string(events)?;
+            let batch_url = format!("{}/batch", self.server_url.trim_end_matches('/'));
+            
+            let body = self.post_json(&batch_url, &json_data)?;
+            let response: BatchResponse = serde_json::from_str(&body)?;
+            Ok(response.results)
+        }
+        
+        /// POST d'un corps JSON avec la politique de nouvel essai
+        ///
+        /// # Retourne
+        /// Le corps de la réponse en cas de succès (2xx)
+        fn post_json(&self, url: &str, json_data: &str) -> Result<String, Box<dyn std::error::Error>> {
+            let hmac_secret = config::hmac_secret();
+            let host_token = config::host_token();
+            let policy = &self.retry_policy;
+            let deadline = Deadline::start(policy.time_budget);
+            
+            for attempt in 1..=policy.max_attempts {
+                println!("Tentative {}/{} d'envoi vers {}", attempt, policy.max_attempts, url);
+                
+                // Heure d'envoi (à chaque tentative) : le serveur en déduit l'écart
+                // d'horloge de la machine, indépendamment du retard des événements
+                let mut request = self.agent.post(url)
+                    .timeout(policy.attempt_timeout.min(deadline.remaining()))
+                    .set("Content-Type", "application/json")
+                    .set("User-Agent", &config::user_agent())
+                    .set("X-Winlog-Sent-At", &chrono::Utc::now().timestamp().to_string());
+                
+                if let Some(token) = &host_token {
+                    request = request.set("Authorization", &format!("Bearer {}", token));
+                }
+                
+                if let Some(secret) = &hmac_secret {
+                    let (timestamp, signature) = sign_body(secret, json_data);
+                    request = request
+                        .set("X-Winlog-Timestamp", &timestamp)
+                        .set("X-Winlog-Signature", &signature);
+                }
                 
-                match minreq::post(&self.server_url)
-                    .with_header("Content-Type", "application/json")
-                    .with_header("User-Agent", &config::user_agent())
-                    .with_timeout(config::timeout())
-                    .with_body(json_data.clone())
-                    .send()
-                {
+                // Délai imposé par le serveur (Retry-After), sinon backoff
+                let mut retry_after = None;
+                
+                match request.send_string(json_data) {
                     Ok(response) => {
-                        if response.status_code >= 200 && response.status_code < 300 {
-                            println!("Données envoyées avec succès (HTTP {})", response.status_code);
-                            return Ok(());
-                        } else {
-                            eprintln!("Erreur HTTP {}: {}", response.status_code, response.reason_phrase);
-                            if let Ok(body) = response.as_str() {
-                                eprintln!("Réponse du serveur: {}", body);
-                            }
+                        println!("Données envoyées avec succès (HTTP {})", response.status());
+                        return Ok(response.into_string()?);
+                    }
+                    Err(ureq::Error::Status(status, response)) => {
+                        eprintln!("Erreur HTTP {}: {}", status, response.status_text());
+                        retry_after = retry::parse_retry_after(response.header("Retry-After"));
+                        let body = response.into_string().unwrap_or_default();
+                        eprintln!("Réponse du serveur: {}", body);
+                        
+                        // Le code serveur, s'il est présent, précise le statut HTTP
+                        let error = serde_json::from_str::<ErrorBody>(&body).ok();
+                        let retryable = match &error {
+                            Some(error) => RETRYABLE_CODES.contains(&error.code.as_str()),
+                            None => RetryPolicy::is_retryable_status(status),
+                        };
+                        if !retryable {
+                            let rejected = match error {
+                                Some(error) => RejectedError {
+                                    status,
+                                    code: Some(error.code),
+                                    message: error.error,
+                                    details: error.details,
+                                },
+                                None => RejectedError {
+                                    status,
+                                    code: None,
+                                    message: body,
+                                    details: None,
+                                },
+                            };
+                            eprintln!("{}", rejected);
+                            return Err(Box::new(rejected));
                         }
                     }
-                    Err(e) => {
+                    Err(ureq::Error::Transport(e)) => {
+                        if let Some(pin_error) = self.pin_failure.lock().ok().and_then(|mut slot| slot.take()) {
+                            eprintln!("{}", pin_error);
+                            return Err(Box::new(pin_error));
+                        }
                         eprintln!("Erreur réseau (tentative {}): {}", attempt, e);
                     }
                 }
                 
-                if attempt < config::max_retries() {
-                    std::thread::sleep(std::time::Duration::from_millis(config::retry_delay_ms()));
+                if attempt < policy.max_attempts {
+                    let delay = retry_after.unwrap_or_else(|| policy.backoff(attempt));
+                    if !deadline.allows_wait(delay) {
+                        eprintln!(
+                            "Budget de temps épuisé ({}s) : abandon après {} tentative(s)",
+                            policy.time_budget.as_secs(),
+                            attempt
+                        );
+                        break;
+                    }
+                    std::thread::sleep(delay);
                 }
             }
             
@@ -135,28 +371,88 @@ pub mod system_info {
 pub mod data_structures {
     use serde::{Deserialize, Serialize};
     use std::collections::HashMap;
+    use std::fmt;
+    
+    /// Type d'événement, transmis au serveur sous forme d'un code d'une lettre
+    ///
+    /// Vocabulaire identique à `models::Action` côté serveur : une nouvelle
+    /// action s'ajoute aux deux.
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Action {
+        /// Ouverture de session (logon)
+        #[serde(rename = "C")]
+        Connect,
+        /// Fermeture de session (logout)
+        #[serde(rename = "D")]
+        Disconnect,
+        /// Inventaire matériel (matos)
+        #[serde(rename = "M")]
+        Hardware,
+        /// Verrouillage de l'écran (lock)
+        #[serde(rename = "L")]
+        Lock,
+        /// Déverrouillage de l'écran (unlock)
+        #[serde(rename = "U")]
+        Unlock,
+        /// Démarrage de la machine (boot)
+        #[serde(rename = "B")]
+        Boot,
+        /// Arrêt de la machine (shutdown)
+        #[serde(rename = "S")]
+        Shutdown,
+        /// Signal de présence d'une session ouverte
+        #[serde(rename = "H")]
+        Heartbeat,
+    }
+    
+    impl Action {
+        /// Code d'une lettre envoyé au serveur
+        pub fn code(self) -> &'static str {
+            match self {
+                Action::Connect => "C",
+                Action::Disconnect => "D",
+                Action::Hardware => "M",
+                Action::Lock => "L",
+                Action::Unlock => "U",
+                Action::Boot => "B",
+                Action::Shutdown => "S",
+                Action::Heartbeat => "H",
+            }
+        }
+    }
+    
+    impl fmt::Display for Action {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(self.code())
+        }
+    }
     
     /// Structure principale des données à envoyer au serveur
     #[derive(Serialize, Deserialize, Debug)]
     pub struct WinlogData {
         pub username: String,
-        pub action: String,
+        pub action: Action,
         pub timestamp: String,
         pub hostname: String,
         pub os_info: HashMap<String, String>,
         pub hardware_info: Option<HashMap<String, serde_json::Value>>,
+        /// Identifiant unique de l'événement, conservé entre les tentatives
+        /// pour que le serveur ignore un renvoi déjà enregistré
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub event_id: Option<String>,
     }
     
     impl WinlogData {
         /// Crée une nouvelle instance avec les informations de base
-        pub fn new(username: String, action_code: String) -> Self {
+        pub fn new(username: String, action: Action) -> Self {
             Self {
                 username,
-                action: action_code, // "C" = Connexion, "D" = Déconnexion, "M" = Matériel
+                action,
                 timestamp: chrono::Utc::now().to_rfc3339(),
                 hostname: String::new(),
                 os_info: HashMap::new(),
                 hardware_info: None,
+                event_id: Some(uuid::Uuid::new_v4().to_string()),
             }
         }
     }
@@ -164,7 +460,14 @@ pub mod data_structures {
 
 /// Module des utilitaires communs
 pub mod utils {
-    use crate::{data_structures::WinlogData, http_client::WinlogClient, system_info};
+    use std::time::Duration;
+    use crate::{
+        config,
+        data_structures::{Action, WinlogData},
+        http_client::{RejectedError, WinlogClient},
+        spool::Spool,
+        system_info,
+    };
     
     /// Génère un timestamp au format ISO 8601 UTC
     pub fn get_current_timestamp() -> String {
@@ -173,11 +476,89 @@ pub mod utils {
     
     /// Valide les données avant envoi
     pub fn validate_data(data: &WinlogData) -> bool {
-        !data.username.is_empty() && !data.action.is_empty()
+        !data.username.is_empty()
     }
     
-    /// Fonction commune pour traiter les événements de session (connexion/déconnexion)
-    pub fn process_session_event(action_code: &str, event_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    /// Issue de la transmission d'un événement
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Delivery {
+        /// Enregistré par le serveur
+        Sent,
+        /// Conservé dans la file d'attente locale (envoyé lors d'une exécution suivante)
+        Spooled,
+        /// Abandonné : signal de présence derrière des événements en attente
+        Skipped,
+    }
+    
+    /// Transmet un événement en préservant l'ordre d'émission
+    ///
+    /// 1. Rejoue d'abord la file d'attente locale
+    /// 2. Si des événements plus anciens restent en attente, l'événement est
+    ///    ajouté à la file sans envoi (pour ne pas dépasser les précédents)
+    /// 3. Sinon il est envoyé ; en cas d'échec non définitif, il est mis en file
+    ///
+    /// Un signal de présence (`Action::Heartbeat`) n'est jamais mis en file :
+    /// rejoué en retard, il ne prouverait plus que la machine est en marche.
+    ///
+    /// # Retourne
+    /// `Ok(Delivery)` si l'événement est envoyé ou conservé en file d'attente
+    pub fn deliver(client: &WinlogClient, data: &WinlogData, event_name: &str) -> Result<Delivery, Box<dyn std::error::Error>> {
+        let tag = event_name.to_uppercase();
+        
+        // File inutilisable (droits, disque) : envoi direct, sans filet
+        let spool = match Spool::from_config() {
+            Ok(spool) => spool,
+            Err(e) => {
+                eprintln!("[{}] File d'attente indisponible ({}) : envoi direct", tag, e);
+                return client.send_data(data).map(|()| Delivery::Sent);
+            }
+        };
+        
+        // Une autre exécution manipule la file : envoi direct
+        let lock = match spool.try_lock() {
+            Ok(Some(lock)) => lock,
+            Ok(None) => {
+                println!("[{}] File d'attente occupée par une autre exécution : envoi direct", tag);
+                return client.send_data(data).map(|()| Delivery::Sent);
+            }
+            Err(e) => {
+                eprintln!("[{}] Verrou de file d'attente impossible ({}) : envoi direct", tag, e);
+                return client.send_data(data).map(|()| Delivery::Sent);
+            }
+        };
+        
+        let remaining = spool.flush(client, &lock).unwrap_or_else(|e| {
+            eprintln!("[{}] File d'attente inutilisable : {}", tag, e);
+            0
+        });
+        
+        let spoolable = data.action != Action::Heartbeat;
+        
+        if remaining > 0 && !spoolable {
+            println!("[{}] {} événement(s) plus ancien(s) en attente : signal ignoré", tag, remaining);
+            return Ok(Delivery::Skipped);
+        }
+        
+        if remaining > 0 {
+            spool.enqueue(data, &lock)?;
+            println!("[{}] {} événement(s) plus ancien(s) en attente : événement mis en file", tag, remaining);
+            return Ok(Delivery::Spooled);
+        }
+        
+        match client.send_data(data) {
+            Ok(()) => Ok(Delivery::Sent),
+            Err(e) if e.is::<RejectedError>() || !spoolable => Err(e),
+            Err(e) => {
+                let path = spool.enqueue(data, &lock)?;
+                eprintln!("[{}] Envoi impossible ({}) : événement conservé dans {}", tag, e, path.display());
+                Ok(Delivery::Spooled)
+            }
+        }
+    }
+    
+    /// Fonction commune pour traiter les événements de session et de machine
+    /// (connexion/déconnexion, verrouillage, démarrage/arrêt)
+    pub fn process_session_event(action: Action, event_name: &str) -> Result<(), Box<dyn std::error::Error>> {
         println!("[{}] Démarrage du processus {}", event_name.to_uppercase(), event_name);
         
         // Collecte des informations système de base
@@ -190,7 +571,7 @@ pub mod utils {
             .to_string();
         
         // Création de la structure de données
-        let mut data = WinlogData::new(username, action_code.to_string());
+        let mut data = WinlogData::new(username, action);
         
         // Extraction hostname - évite allocation temporaire
         data.hostname = system_info.get("hostname")
@@ -206,13 +587,26 @@ pub mod utils {
             return Err("Données invalides".into());
         }
         
-        // Envoi des données au serveur
-        let client = WinlogClient::new(None);
-        match client.send_data(&data) {
-            Ok(()) => {
-                let success_msg = match action_code {
-                    "C" => "Session ouverte avec succès",
-                    "D" => "Session fermée avec succès",
+        // Envoi des données au serveur (budget réduit pour logout et shutdown : arrêt de
+        // la machine ; heartbeat : le signal suivant prendra le relais)
+        let mut client = WinlogClient::new(None)?;
+        if matches!(action, Action::Disconnect | Action::Shutdown | Action::Heartbeat) {
+            client = client.with_time_budget(Duration::from_secs(config::logout_time_budget()));
+        }
+        match deliver(&client, &data, event_name) {
+            Ok(Delivery::Spooled) => {
+                println!("[{}] Événement en attente d'envoi", event_name.to_uppercase());
+            }
+            Ok(Delivery::Skipped) => {}
+            Ok(Delivery::Sent) => {
+                let success_msg = match action {
+                    Action::Connect => "Session ouverte avec succès",
+                    Action::Disconnect => "Session fermée avec succès",
+                    Action::Lock => "Verrouillage enregistré",
+                    Action::Unlock => "Déverrouillage enregistré",
+                    Action::Boot => "Démarrage enregistré",
+                    Action::Shutdown => "Arrêt enregistré",
+                    Action::Heartbeat => "Signal de présence enregistré",
                     _ => "Opération terminée avec succès"
                 };
                 println!("[{}] {}", event_name.to_uppercase(), success_msg);
@@ -243,7 +637,7 @@ pub mod utils {
             .to_string();
         
         // Création de la structure de données
-        let mut data = WinlogData::new(username, "M".to_string());
+        let mut data = WinlogData::new(username, Action::Hardware);
         
         // Extraction hostname - évite allocation temporaire
         data.hostname = basic_info.get("hostname")
@@ -261,9 +655,11 @@ pub mod utils {
         }
         
         // Envoi des données au serveur
-        let client = WinlogClient::new(None);
-        match client.send_data(&data) {
-            Ok(()) => println!("[MATOS] Collecte matérielle terminée avec succès"),
+        let client = WinlogClient::new(None)?;
+        match deliver(&client, &data, "matos") {
+            Ok(Delivery::Sent) => println!("[MATOS] Collecte matérielle terminée avec succès"),
+            Ok(Delivery::Spooled) => println!("[MATOS] Événement en attente d'envoi"),
+            Ok(Delivery::Skipped) => {}
             Err(e) => {
                 eprintln!("[MATOS] Échec de l'envoi: {}", e);
                 return Err(e);
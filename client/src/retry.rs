@@ -0,0 +1,183 @@
+//! # Politique de nouvel essai des envois
+//!
+//! Distingue les échecs transitoires (réseau, 408, 429, 5xx) des refus
+//! définitifs (autres 4xx), espace les tentatives par un backoff exponentiel
+//! avec gigue et borne la durée totale de l'envoi.
+//!
+//! Le budget de temps est essentiel pour `logout` : exécuté pendant l'arrêt de
+//! la machine, il ne peut pas bloquer `WINLOG_MAX_RETRIES × WINLOG_TIMEOUT`.
+
+use std::time::{Duration, Instant};
+
+use crate::config;
+
+/// Délai maximal entre deux tentatives, quel que soit le rang de la tentative
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Paramètres de nouvel essai d'un envoi
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Nombre maximal de tentatives (première incluse)
+    pub max_attempts: u32,
+    /// Délai de base du backoff (doublé à chaque tentative)
+    pub base_delay: Duration,
+    /// Timeout d'une tentative
+    pub attempt_timeout: Duration,
+    /// Durée totale maximale de l'envoi, attentes comprises
+    pub time_budget: Duration,
+}
+
+impl RetryPolicy {
+    /// Politique issue de la configuration (`WINLOG_MAX_RETRIES`,
+    /// `WINLOG_RETRY_DELAY_MS`, `WINLOG_TIMEOUT`, `WINLOG_TIME_BUDGET`)
+    pub fn from_config() -> Self {
+        Self {
+            max_attempts: config::max_retries().max(1),
+            base_delay: Duration::from_millis(config::retry_delay_ms()),
+            attempt_timeout: Duration::from_secs(config::timeout()),
+            time_budget: Duration::from_secs(config::time_budget()),
+        }
+    }
+
+    /// Remplace le budget de temps total (ex : `WINLOG_LOGOUT_TIME_BUDGET`)
+    pub fn with_time_budget(mut self, time_budget: Duration) -> Self {
+        self.time_budget = time_budget;
+        self
+    }
+
+    /// Délai avant la tentative suivant la tentative `attempt` (à partir de 1)
+    ///
+    /// Gigue « pleine » : valeur aléatoire entre 0 et `base_delay × 2^(attempt-1)`
+    /// (plafonné à `MAX_BACKOFF`), pour que les postes d'une salle redémarrée
+    /// en même temps ne réessaient pas tous au même instant.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(16);
+        let ceiling = self.base_delay.saturating_mul(1 << exponent).min(MAX_BACKOFF);
+        ceiling.mul_f64(random_fraction())
+    }
+
+    /// Indique si un statut HTTP d'erreur peut disparaître lors d'un nouvel essai
+    ///
+    /// Seuls 408 (Request Timeout), 429 (Too Many Requests) et les 5xx sont
+    /// transitoires ; les autres 4xx donneraient le même résultat.
+    pub fn is_retryable_status(status: u16) -> bool {
+        matches!(status, 408 | 429 | 500..=599)
+    }
+}
+
+/// Suivi du budget de temps d'un envoi
+pub struct Deadline {
+    started_at: Instant,
+    budget: Duration,
+}
+
+impl Deadline {
+    /// Démarre le décompte du budget
+    pub fn start(budget: Duration) -> Self {
+        Self {
+            started_at: Instant::now(),
+            budget,
+        }
+    }
+
+    /// Temps restant (zéro si le budget est épuisé)
+    pub fn remaining(&self) -> Duration {
+        self.budget.saturating_sub(self.started_at.elapsed())
+    }
+
+    /// Indique si une attente de `delay` laisserait encore du temps pour une tentative
+    pub fn allows_wait(&self, delay: Duration) -> bool {
+        delay < self.remaining()
+    }
+}
+
+/// Lit l'en-tête `Retry-After` exprimé en secondes
+///
+/// La forme date HTTP n'est pas utilisée par le serveur Winlog : elle est
+/// ignorée (le backoff s'applique alors).
+pub fn parse_retry_after(value: Option<&str>) -> Option<Duration> {
+    value
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+/// Fraction aléatoire dans [0, 1) (aléa du générateur UUID v4, déjà en dépendance)
+fn random_fraction() -> f64 {
+    // 53 bits de poids faible : hors des bits de version et de variante
+    let bits = uuid::Uuid::new_v4().as_u128() as u64 & ((1u64 << 53) - 1);
+    bits as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::http_client::BatchItemResult;
+
+    fn policy(base_delay_ms: u64) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(base_delay_ms),
+            attempt_timeout: Duration::from_secs(30),
+            time_budget: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_only() {
+        assert_eq!(parse_retry_after(Some("7")), Some(Duration::from_secs(7)));
+        assert_eq!(parse_retry_after(Some(" 12 ")), Some(Duration::from_secs(12)));
+        assert_eq!(parse_retry_after(Some("Wed, 21 Oct 2026 07:28:00 GMT")), None);
+        assert_eq!(parse_retry_after(Some("-1")), None);
+        assert_eq!(parse_retry_after(Some("")), None);
+        assert_eq!(parse_retry_after(None), None);
+    }
+
+    #[test]
+    fn only_timeout_rate_limit_and_server_errors_are_retried() {
+        for status in [408, 429, 500, 502, 503, 504, 599] {
+            assert!(RetryPolicy::is_retryable_status(status), "{}", status);
+        }
+        for status in [400, 401, 403, 404, 405, 409, 413, 422] {
+            assert!(!RetryPolicy::is_retryable_status(status), "{}", status);
+        }
+    }
+
+    #[test]
+    fn only_transient_server_codes_are_retried() {
+        let result = |code: &str| BatchItemResult {
+            index: 0,
+            status: "error".to_string(),
+            code: Some(code.to_string()),
+            error: None,
+        };
+        assert!(result("RATE_LIMITED").is_retryable());
+        assert!(result("DB_UNAVAILABLE").is_retryable());
+        for code in ["DATABASE_ERROR", "INVALID_ACTION", "CLOCK_SKEW", "UNAUTHORIZED", "HOSTNAME_MISMATCH"] {
+            assert!(!result(code).is_retryable(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn backoff_is_jittered_below_a_doubling_ceiling() {
+        let policy = policy(100);
+        for _ in 0..100 {
+            assert!(policy.backoff(1) < Duration::from_millis(100));
+            assert!(policy.backoff(3) < Duration::from_millis(400));
+            assert!(policy.backoff(40) < MAX_BACKOFF);
+        }
+    }
+
+    #[test]
+    fn deadline_refuses_waits_beyond_the_budget() {
+        let deadline = Deadline::start(Duration::from_secs(5));
+        assert!(deadline.allows_wait(Duration::from_secs(1)));
+        assert!(!deadline.allows_wait(Duration::from_secs(5)));
+        assert!(!deadline.allows_wait(Duration::from_secs(30)));
+
+        // Budget épuisé : plus aucune tentative, même sans attente
+        let exhausted = Deadline::start(Duration::ZERO);
+        assert_eq!(exhausted.remaining(), Duration::ZERO);
+        assert!(!exhausted.allows_wait(Duration::ZERO));
+    }
+}
@@ -0,0 +1,558 @@
+//! # File d'attente persistante des événements non envoyés
+//!
+//! Un événement dont l'envoi échoue (serveur injoignable, portable hors du
+//! réseau du campus...) est écrit dans le répertoire `WINLOG_SPOOL_DIR`, un
+//! fichier JSON par événement. Chaque exécution suivante de `logon`, `logout`
+//! ou `matos` rejoue d'abord la file, dans l'ordre, via
+//! `POST /api/v1/events/batch` : les événements conservent leur horodatage et
+//! leur `event_id` d'origine (un renvoi déjà enregistré est ignoré par le serveur).
+//!
+//! ## Fichiers
+//!
+//! - `<nanosecondes>-<event_id>.json` : événement en attente (l'ordre des noms
+//!   est l'ordre d'arrivée, strictement croissant même si l'horloge recule)
+//! - `*.rejected` : événement refusé définitivement par le serveur, conservé
+//!   pour diagnostic mais plus rejoué
+//! - `.lock` : verrou exclusif, une seule exécution manipule la file à la fois
+//!
+//! L'écriture passe par un fichier temporaire (`.tmp`) renommé : un arrêt
+//! brutal ne laisse jamais de JSON tronqué dans la file, et les fichiers
+//! temporaires abandonnés sont supprimés au rejeu suivant.
+//!
+//! ## Droits
+//!
+//! Le répertoire est partagé par les comptes de la machine : sous Linux, il
+//! est créé en `2770` (groupe dédié, voir [`config::spool_dir`]) et refusé
+//! s'il est accessible en écriture à tous. La file est bornée à
+//! `WINLOG_SPOOL_MAX_EVENTS` événements.
+
+use std::fs::{self, File};
+use std::io;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config;
+use crate::data_structures::WinlogData;
+use crate::http_client::{BatchItemResult, WinlogClient};
+
+/// Nombre maximum d'événements rejoués par requête (limite serveur : 500)
+const FLUSH_BATCH_SIZE: usize = 100;
+
+/// Extension des événements en attente
+const PENDING_EXTENSION: &str = "json";
+
+/// Extension des événements refusés définitivement
+const REJECTED_EXTENSION: &str = "rejected";
+
+/// Extension des fichiers en cours d'écriture
+const TMP_EXTENSION: &str = "tmp";
+
+/// Droits du répertoire créé par le client : groupe dédié, setgid
+#[cfg(unix)]
+const DIR_MODE: u32 = 0o2770;
+
+/// Droits du verrou, ouvert en écriture par tous les comptes du groupe
+#[cfg(unix)]
+const LOCK_MODE: u32 = 0o660;
+
+/// Répertoire de file d'attente des événements
+pub struct Spool {
+    dir: PathBuf,
+    max_events: usize,
+}
+
+/// Verrou exclusif sur la file (libéré à la destruction)
+pub struct SpoolLock {
+    _file: File,
+}
+
+impl Spool {
+    /// Ouvre (et crée si besoin) la file du répertoire `dir`, bornée à
+    /// `max_events` événements
+    ///
+    /// # Erreurs
+    /// `PermissionDenied` si le répertoire est accessible en écriture à tous (Unix)
+    pub fn open(dir: impl Into<PathBuf>, max_events: usize) -> io::Result<Self> {
+        let dir = dir.into();
+        if !dir.is_dir() {
+            fs::create_dir_all(&dir)?;
+            // Droits explicites : le masque (umask) retirerait l'écriture du groupe
+            #[cfg(unix)]
+            fs::set_permissions(&dir, fs::Permissions::from_mode(DIR_MODE))?;
+        }
+
+        #[cfg(unix)]
+        {
+            let mode = fs::metadata(&dir)?.permissions().mode();
+            if mode & 0o002 != 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!(
+                        "{} accessible en écriture à tous (mode {:o}) : utiliser un groupe dédié en 2770",
+                        dir.display(),
+                        mode & 0o7777
+                    ),
+                ));
+            }
+        }
+
+        Ok(Self { dir, max_events })
+    }
+
+    /// Ouvre la file configurée (`WINLOG_SPOOL_DIR`, `WINLOG_SPOOL_MAX_EVENTS`)
+    pub fn from_config() -> io::Result<Self> {
+        Self::open(config::spool_dir(), config::spool_max_events())
+    }
+
+    /// Répertoire de la file
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Prend le verrou exclusif de la file
+    ///
+    /// **Retourne** `None` si une autre exécution le détient déjà.
+    pub fn try_lock(&self) -> io::Result<Option<SpoolLock>> {
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.dir.join(".lock"))?;
+        // Sans effet (et sans erreur à signaler) si le verrou appartient à un autre compte
+        #[cfg(unix)]
+        let _ = file.set_permissions(fs::Permissions::from_mode(LOCK_MODE));
+        match file.try_lock() {
+            Ok(()) => Ok(Some(SpoolLock { _file: file })),
+            Err(fs::TryLockError::WouldBlock) => Ok(None),
+            Err(fs::TryLockError::Error(e)) => Err(e),
+        }
+    }
+
+    /// Ajoute un événement en fin de file
+    ///
+    /// Le verrou doit être détenu par l'appelant.
+    ///
+    /// # Erreurs
+    /// Si la file contient déjà `max_events` événements : l'événement n'est pas
+    /// conservé (les plus anciens restent prioritaires)
+    pub fn enqueue(&self, data: &WinlogData, _lock: &SpoolLock) -> io::Result<PathBuf> {
+        let pending = self.pending()?;
+        if pending.len() >= self.max_events {
+            return Err(io::Error::other(format!(
+                "file d'attente pleine ({} événements, WINLOG_SPOOL_MAX_EVENTS)",
+                pending.len()
+            )));
+        }
+
+        // Toujours après le dernier événement en attente, même si l'horloge a reculé
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let nanos = pending
+            .last()
+            .and_then(|path| sequence(path))
+            .map_or(now, |last| now.max(last + 1));
+        let id = data.event_id.as_deref().unwrap_or("sans-id");
+        let path = self.dir.join(format!("{:020}-{}.{}", nanos, id, PENDING_EXTENSION));
+
+        let tmp = path.with_extension(TMP_EXTENSION);
+        fs::write(&tmp, serde_json::to_vec(data)?)?;
+        fs::rename(&tmp, &path)?;
+        Ok(path)
+    }
+
+    /// Liste les événements en attente, du plus ancien au plus récent
+    pub fn pending(&self) -> io::Result<Vec<PathBuf>> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == PENDING_EXTENSION))
+            .collect();
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Rejoue les événements en attente, dans l'ordre
+    ///
+    /// S'arrête au premier échec de la requête (serveur toujours injoignable) ;
+    /// les événements non envoyés restent en file. Un élément refusé
+    /// définitivement est renommé en `.rejected`, un élément en échec
+    /// transitoire reste en file.
+    ///
+    /// Un élément en `RATE_LIMITED` interrompt aussi le rejeu après son lot :
+    /// envoyer les lots suivants ne ferait que prolonger la limitation.
+    ///
+    /// Le verrou doit être détenu par l'appelant.
+    ///
+    /// # Retourne
+    /// Le nombre d'événements encore en attente
+    ///
+    /// # Erreurs
+    /// Si un événement rejoué ne peut être supprimé (ou un événement refusé
+    /// renommé) : il serait renvoyé à chaque exécution. Le lot en cours est
+    /// traité jusqu'au bout, les suivants ne sont pas envoyés.
+    pub fn flush(&self, client: &WinlogClient, lock: &SpoolLock) -> io::Result<usize> {
+        self.replay(lock, |events| client.send_batch(events))
+    }
+
+    /// Rejoue la file avec `send_batch` pour l'envoi de chaque lot (voir `flush`)
+    fn replay<F>(&self, _lock: &SpoolLock, mut send_batch: F) -> io::Result<usize>
+    where
+        F: FnMut(&[WinlogData]) -> Result<Vec<BatchItemResult>, Box<dyn std::error::Error>>,
+    {
+        self.remove_stale_tmp();
+
+        let pending = self.pending()?;
+        if pending.is_empty() {
+            return Ok(0);
+        }
+        println!("[SPOOL] {} événement(s) en attente dans {}", pending.len(), self.dir.display());
+
+        let mut remaining = 0;
+        let mut chunks = pending.chunks(FLUSH_BATCH_SIZE);
+        for chunk in chunks.by_ref() {
+            let mut paths = Vec::with_capacity(chunk.len());
+            let mut events = Vec::with_capacity(chunk.len());
+            for path in chunk {
+                match read_event(path) {
+                    Ok(event) => {
+                        paths.push(path);
+                        events.push(event);
+                    }
+                    Err(e) => {
+                        eprintln!("[SPOOL] Fichier illisible {} : {}", path.display(), e);
+                        mark_rejected(path)?;
+                    }
+                }
+            }
+            if events.is_empty() {
+                continue;
+            }
+
+            let results = match send_batch(&events) {
+                Ok(results) => results,
+                Err(e) => {
+                    eprintln!("[SPOOL] Rejeu interrompu : {}", e);
+                    remaining += paths.len();
+                    break;
+                }
+            };
+
+            let mut replayed = 0;
+            let mut rate_limited = false;
+            let mut failure = None;
+            for (index, path) in paths.iter().enumerate() {
+                let result = results.iter().find(|r| r.index == index);
+                match result {
+                    Some(result) if result.is_success() => {
+                        replayed += 1;
+                        if let Err(e) = fs::remove_file(path) {
+                            eprintln!("[SPOOL] Impossible de supprimer {} : {}", path.display(), e);
+                            failure.get_or_insert(e);
+                        }
+                    }
+                    Some(result) if !result.is_retryable() => {
+                        eprintln!(
+                            "[SPOOL] Événement refusé ({}) : {}",
+                            result.code.as_deref().unwrap_or("?"),
+                            result.error.as_deref().unwrap_or("")
+                        );
+                        if let Err(e) = mark_rejected(path) {
+                            failure.get_or_insert(e);
+                        }
+                    }
+                    Some(result) => {
+                        rate_limited |= result.is_rate_limited();
+                        remaining += 1;
+                    }
+                    None => remaining += 1,
+                }
+            }
+            println!("[SPOOL] {} événement(s) rejoué(s)", replayed);
+
+            if let Some(e) = failure {
+                return Err(e);
+            }
+            if rate_limited {
+                eprintln!("[SPOOL] Limite de débit du serveur atteinte, rejeu reporté");
+                break;
+            }
+        }
+
+        // Lots non tentés après une interruption
+        remaining += chunks.map(<[PathBuf]>::len).sum::<usize>();
+        Ok(remaining)
+    }
+
+    /// Supprime les fichiers temporaires laissés par une écriture interrompue
+    ///
+    /// Le verrou étant détenu, aucune écriture n'est en cours : tout `.tmp` est
+    /// abandonné. Un échec est sans conséquence (le fichier n'est jamais rejoué).
+    fn remove_stale_tmp(&self) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        for path in entries.filter_map(|entry| entry.ok().map(|e| e.path())) {
+            if path.extension().is_some_and(|ext| ext == TMP_EXTENSION) {
+                match fs::remove_file(&path) {
+                    Ok(()) => println!("[SPOOL] Fichier temporaire abandonné supprimé : {}", path.display()),
+                    Err(e) => eprintln!("[SPOOL] Impossible de supprimer {} : {}", path.display(), e),
+                }
+            }
+        }
+    }
+}
+
+/// Numéro d'ordre (nanosecondes) d'un événement en attente, tiré de son nom
+fn sequence(path: &Path) -> Option<u128> {
+    path.file_name()?.to_str()?.split('-').next()?.parse().ok()
+}
+
+/// Lit un événement en attente
+fn read_event(path: &Path) -> io::Result<WinlogData> {
+    let content = fs::read(path)?;
+    Ok(serde_json::from_slice(&content)?)
+}
+
+/// Écarte un événement de la file sans le supprimer
+fn mark_rejected(path: &Path) -> io::Result<()> {
+    fs::rename(path, path.with_extension(REJECTED_EXTENSION)).inspect_err(|e| {
+        eprintln!("[SPOOL] Impossible d'écarter {} : {}", path.display(), e);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::data_structures::Action;
+
+    /// File verrouillée dans un répertoire temporaire, supprimé à la destruction
+    struct TestSpool {
+        spool: Spool,
+        lock: SpoolLock,
+    }
+
+    impl TestSpool {
+        fn new(max_events: usize) -> Self {
+            let dir = std::env::temp_dir().join(format!("winlog-spool-{}", uuid::Uuid::new_v4()));
+            let spool = Spool::open(dir, max_events).expect("file de test");
+            let lock = spool.try_lock().expect("verrou").expect("verrou libre");
+            Self { spool, lock }
+        }
+
+        /// Met en file un événement par utilisateur, dans l'ordre
+        fn enqueue(&self, usernames: impl IntoIterator<Item = String>) {
+            for username in usernames {
+                let data = WinlogData::new(username, Action::Connect);
+                self.spool.enqueue(&data, &self.lock).expect("mise en file");
+            }
+        }
+
+        fn replay<F>(&self, send_batch: F) -> io::Result<usize>
+        where
+            F: FnMut(&[WinlogData]) -> Result<Vec<BatchItemResult>, Box<dyn std::error::Error>>,
+        {
+            self.spool.replay(&self.lock, send_batch)
+        }
+
+        /// Utilisateurs des événements encore en attente, dans l'ordre de la file
+        fn pending_usernames(&self) -> Vec<String> {
+            let pending = self.spool.pending().expect("file");
+            pending.iter().map(|path| read_event(path).expect("événement").username).collect()
+        }
+
+        fn files_with_extension(&self, extension: &str) -> usize {
+            fs::read_dir(self.spool.dir())
+                .expect("répertoire")
+                .filter(|entry| {
+                    entry.as_ref().is_ok_and(|e| e.path().extension().is_some_and(|ext| ext == extension))
+                })
+                .count()
+        }
+    }
+
+    impl Drop for TestSpool {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(self.spool.dir());
+        }
+    }
+
+    fn users(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("user-{:03}", i)).collect()
+    }
+
+    fn item(index: usize, code: Option<&str>) -> BatchItemResult {
+        BatchItemResult {
+            index,
+            status: if code.is_some() { "error" } else { "success" }.to_string(),
+            code: code.map(str::to_string),
+            error: None,
+        }
+    }
+
+    /// Réponse du serveur : code d'erreur éventuel de chaque élément
+    fn respond(events: &[WinlogData], code: impl Fn(usize) -> Option<&'static str>) -> Vec<BatchItemResult> {
+        (0..events.len()).map(|index| item(index, code(index))).collect()
+    }
+
+    #[test]
+    fn replay_sends_events_in_arrival_order_by_chunks() {
+        let test = TestSpool::new(1000);
+        test.enqueue(users(2 * FLUSH_BATCH_SIZE + 5));
+
+        let mut sent = Vec::new();
+        let mut sizes = Vec::new();
+        let remaining = test
+            .replay(|events| {
+                sizes.push(events.len());
+                sent.extend(events.iter().map(|e| e.username.clone()));
+                Ok(respond(events, |_| None))
+            })
+            .expect("rejeu");
+
+        assert_eq!(remaining, 0);
+        assert_eq!(sizes, [FLUSH_BATCH_SIZE, FLUSH_BATCH_SIZE, 5]);
+        assert_eq!(sent, users(2 * FLUSH_BATCH_SIZE + 5));
+        assert!(test.pending_usernames().is_empty());
+    }
+
+    #[test]
+    fn permanently_rejected_event_is_renamed_and_not_replayed() {
+        let test = TestSpool::new(1000);
+        test.enqueue(users(3));
+
+        let remaining = test
+            .replay(|events| Ok(respond(events, |index| (index == 1).then_some("INVALID_ACTION"))))
+            .expect("rejeu");
+        assert_eq!(remaining, 0);
+        assert_eq!(test.files_with_extension(REJECTED_EXTENSION), 1);
+
+        let mut batches = 0;
+        test.replay(|_| {
+            batches += 1;
+            Ok(Vec::new())
+        })
+        .expect("rejeu");
+        assert_eq!(batches, 0);
+    }
+
+    #[test]
+    fn transient_item_failure_keeps_event_pending() {
+        let test = TestSpool::new(1000);
+        test.enqueue(users(3));
+
+        let remaining = test
+            .replay(|events| Ok(respond(events, |index| (index == 1).then_some("DB_UNAVAILABLE"))))
+            .expect("rejeu");
+        assert_eq!(remaining, 1);
+        assert_eq!(test.pending_usernames(), ["user-001"]);
+        assert_eq!(test.files_with_extension(REJECTED_EXTENSION), 0);
+    }
+
+    #[test]
+    fn request_failure_keeps_unsent_chunks_pending() {
+        let test = TestSpool::new(1000);
+        test.enqueue(users(2 * FLUSH_BATCH_SIZE + 5));
+
+        // Premier lot enregistré, serveur injoignable pour le second
+        let mut batches = 0;
+        let remaining = test
+            .replay(|events| {
+                batches += 1;
+                if batches == 1 {
+                    Ok(respond(events, |_| None))
+                } else {
+                    Err("connexion refusée".into())
+                }
+            })
+            .expect("rejeu");
+
+        assert_eq!(batches, 2);
+        assert_eq!(remaining, FLUSH_BATCH_SIZE + 5);
+        assert_eq!(test.pending_usernames(), users(2 * FLUSH_BATCH_SIZE + 5)[FLUSH_BATCH_SIZE..]);
+    }
+
+    #[test]
+    fn rate_limited_item_stops_replay_after_its_batch() {
+        let test = TestSpool::new(1000);
+        test.enqueue(users(FLUSH_BATCH_SIZE + 10));
+
+        let mut batches = 0;
+        let remaining = test
+            .replay(|events| {
+                batches += 1;
+                Ok(respond(events, |index| (index > 0).then_some("RATE_LIMITED")))
+            })
+            .expect("rejeu");
+
+        // Un seul lot envoyé : le premier élément est rejoué, le reste attend
+        assert_eq!(batches, 1);
+        assert_eq!(remaining, FLUSH_BATCH_SIZE + 9);
+        assert_eq!(test.pending_usernames().len(), FLUSH_BATCH_SIZE + 9);
+    }
+
+    #[test]
+    fn undeletable_replayed_event_is_reported() {
+        let test = TestSpool::new(1000);
+        test.enqueue(users(FLUSH_BATCH_SIZE + 1));
+        let first = test.spool.pending().expect("file").remove(0);
+
+        // Un répertoire à la place du fichier rejoué : sa suppression échoue
+        let mut batches = 0;
+        let result = test.replay(|events| {
+            batches += 1;
+            fs::remove_file(&first).expect("suppression");
+            fs::create_dir(&first).expect("répertoire");
+            Ok(respond(events, |_| None))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(batches, 1);
+    }
+
+    #[test]
+    fn enqueue_refuses_events_beyond_cap() {
+        let test = TestSpool::new(2);
+        test.enqueue(users(2));
+
+        let data = WinlogData::new("user-002".to_string(), Action::Connect);
+        assert!(test.spool.enqueue(&data, &test.lock).is_err());
+        assert_eq!(test.pending_usernames(), users(2));
+    }
+
+    #[test]
+    fn enqueue_stays_last_when_clock_goes_back() {
+        let test = TestSpool::new(1000);
+        let future = test.spool.dir().join(format!("{:020}-futur.{}", u64::MAX, PENDING_EXTENSION));
+        fs::write(&future, b"{}").expect("événement");
+
+        let data = WinlogData::new("alice".to_string(), Action::Connect);
+        let path = test.spool.enqueue(&data, &test.lock).expect("mise en file");
+        assert_eq!(sequence(&path), Some(u128::from(u64::MAX) + 1));
+        assert_eq!(test.spool.pending().expect("file").last(), Some(&path));
+    }
+
+    #[test]
+    fn replay_removes_abandoned_tmp_files() {
+        let test = TestSpool::new(1000);
+        fs::write(test.spool.dir().join("00000000000000000001-x.tmp"), b"{\"user").expect("fichier");
+
+        test.replay(|_| Ok(Vec::new())).expect("rejeu");
+        assert_eq!(test.files_with_extension(TMP_EXTENSION), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spool_dir_is_group_only() {
+        let test = TestSpool::new(1000);
+        let mode = fs::metadata(test.spool.dir()).expect("répertoire").permissions().mode();
+        assert_eq!(mode & 0o7777, DIR_MODE);
+
+        fs::set_permissions(test.spool.dir(), fs::Permissions::from_mode(0o1777)).expect("droits");
+        let error = Spool::open(test.spool.dir(), 1000).err().expect("répertoire refusé");
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+    }
+}
@@ -0,0 +1,310 @@
+//! # Configuration TLS du client (HTTPS)
+//!
+//! Construit la configuration rustls utilisée pour les URLs `https://` :
+//!
+//! - **Autorités de confiance** : magasin du système d'exploitation, complété
+//!   par le fichier PEM `WINLOG_CA_FILE` (PKI interne absente de certaines images)
+//! - **Épinglage SPKI** (optionnel) : `WINLOG_PIN_SHA256` liste les empreintes
+//!   SHA-256 (base64) des clés publiques acceptées. La chaîne est toujours
+//!   vérifiée normalement ; l'épinglage est une contrainte supplémentaire.
+//!
+//! Calcul d'une empreinte à partir d'un certificat :
+//!
+//! ```bash
+//! openssl x509 -in server.crt -pubkey -noout \
+//!   | openssl pkey -pubin -outform der \
+//!   | openssl dgst -sha256 -binary | base64
+//! ```
+
+use base64::Engine;
+use rustls::client::WebPkiServerVerifier;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::ring;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Dernier échec d'épinglage constaté (partagé entre le vérificateur et le client HTTP)
+///
+/// rustls ne transmet qu'une erreur générique à la couche HTTP : le vérificateur
+/// y dépose le détail pour que `send_data` puisse retourner une `PinningError`.
+pub type PinFailure = Arc<Mutex<Option<PinningError>>>;
+
+/// Construit la configuration TLS du client
+///
+/// # Arguments
+/// * `ca_file` - Fichier PEM d'autorités supplémentaires (`WINLOG_CA_FILE`)
+/// * `pins` - Empreintes SHA-256 base64 des clés publiques acceptées (`WINLOG_PIN_SHA256`)
+/// * `pin_failure` - Emplacement où consigner un échec d'épinglage
+///
+/// # Erreurs
+/// Fichier CA illisible ou vide, empreinte mal formée, aucune autorité disponible
+pub fn build_client_config(
+    ca_file: Option<&Path>,
+    pins: &[String],
+    pin_failure: PinFailure,
+) -> Result<Arc<ClientConfig>, Box<dyn std::error::Error>> {
+    let provider = Arc::new(ring::default_provider());
+    let roots = Arc::new(load_roots(ca_file)?);
+
+    let inner = WebPkiServerVerifier::builder_with_provider(roots, provider.clone()).build()?;
+    let builder = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinnedVerifier {
+            inner,
+            pins: decode_pins(pins)?,
+            failure: pin_failure,
+        }));
+
+    Ok(Arc::new(builder.with_no_client_auth()))
+}
+
+/// Charge le magasin système puis le fichier CA optionnel
+fn load_roots(ca_file: Option<&Path>) -> Result<RootCertStore, Box<dyn std::error::Error>> {
+    let mut roots = RootCertStore::empty();
+
+    let native = rustls_native_certs::load_native_certs();
+    for error in &native.errors {
+        eprintln!("[WARN] Magasin de certificats système partiellement illisible: {}", error);
+    }
+    let (added, _ignored) = roots.add_parsable_certificates(native.certs);
+
+    if let Some(path) = ca_file {
+        let file = File::open(path)
+            .map_err(|e| format!("Lecture de WINLOG_CA_FILE ({}) impossible: {}", path.display(), e))?;
+        let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("WINLOG_CA_FILE ({}) invalide: {}", path.display(), e))?;
+        if certs.is_empty() {
+            return Err(format!("Aucun certificat PEM dans WINLOG_CA_FILE ({})", path.display()).into());
+        }
+        for cert in certs {
+            roots.add(cert)?;
+        }
+    } else if added == 0 {
+        return Err("Aucune autorité de certification disponible (définir WINLOG_CA_FILE)".into());
+    }
+
+    Ok(roots)
+}
+
+/// Décode les empreintes base64 (32 octets chacune)
+fn decode_pins(pins: &[String]) -> Result<Vec<[u8; 32]>, Box<dyn std::error::Error>> {
+    pins.iter()
+        .map(|pin| {
+            base64::engine::general_purpose::STANDARD
+                .decode(pin.trim())
+                .ok()
+                .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+                .ok_or_else(|| format!("Empreinte WINLOG_PIN_SHA256 invalide: {}", pin).into())
+        })
+        .collect()
+}
+
+/// Empreinte SHA-256 (base64) de la clé publique (SPKI) d'un certificat
+fn spki_sha256(cert: &CertificateDer<'_>) -> Option<[u8; 32]> {
+    let cert = webpki::EndEntityCert::try_from(cert).ok()?;
+    Some(Sha256::digest(cert.subject_public_key_info().as_ref()).into())
+}
+
+/// Vérificateur standard (chaîne, nom, validité) + épinglage des clés publiques
+///
+/// L'épinglage est satisfait si l'une des clés de la chaîne présentée (feuille
+/// ou intermédiaire) figure dans la liste : on peut épingler la clé du serveur
+/// ou celle de l'autorité interne.
+#[derive(Debug)]
+struct PinnedVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    pins: Vec<[u8; 32]>,
+    failure: PinFailure,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+
+        if self.pins.is_empty() {
+            return Ok(verified);
+        }
+
+        let presented: Vec<[u8; 32]> = std::iter::once(end_entity)
+            .chain(intermediates)
+            .filter_map(spki_sha256)
+            .collect();
+
+        if presented.iter().any(|digest| self.pins.contains(digest)) {
+            return Ok(verified);
+        }
+
+        let error = PinningError {
+            server_name: server_name.to_str().into_owned(),
+            presented: presented
+                .iter()
+                .map(|d| base64::engine::general_purpose::STANDARD.encode(d))
+                .collect(),
+        };
+        if let Ok(mut slot) = self.failure.lock() {
+            *slot = Some(error);
+        }
+        Err(rustls::Error::General("certificate pin mismatch".into()))
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// Le certificat présenté ne correspond à aucune empreinte de `WINLOG_PIN_SHA256`
+///
+/// Erreur définitive : l'envoi n'est pas retenté (interception possible ou
+/// certificat renouvelé sans mise à jour des empreintes).
+#[derive(Debug, Clone)]
+pub struct PinningError {
+    /// Serveur contacté
+    pub server_name: String,
+    /// Empreintes SPKI (base64) des certificats reçus
+    pub presented: Vec<String>,
+}
+
+impl fmt::Display for PinningError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Échec de l'épinglage TLS pour {} : aucune clé présentée ne figure dans WINLOG_PIN_SHA256 (reçu : {})",
+            self.server_name,
+            self.presented.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for PinningError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rustls::pki_types::pem::PemObject;
+
+    /// Autorité de test, certificat `winlog.test` qu'elle a signé (valables jusqu'en 2126)
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    /// Empreinte SPKI de `server.pem` (calculée avec la commande openssl du module)
+    const SERVER_PIN: &str = "jVS5IvlgCTuuABpKXEvzWB3WZYtTnrO2lzHWPokgkTg=";
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        Path::new(FIXTURES).join(name)
+    }
+
+    fn server_cert() -> CertificateDer<'static> {
+        CertificateDer::from_pem_file(fixture("server.pem")).expect("certificat de test")
+    }
+
+    /// Vérificateur de confiance `ca_file`, avec les empreintes `pins`
+    fn verifier(ca_file: &str, pins: &[&str]) -> PinnedVerifier {
+        let provider = Arc::new(ring::default_provider());
+        let roots = Arc::new(load_roots(Some(&fixture(ca_file))).expect("autorités"));
+        let pins: Vec<String> = pins.iter().map(|pin| pin.to_string()).collect();
+        PinnedVerifier {
+            inner: WebPkiServerVerifier::builder_with_provider(roots, provider).build().expect("vérificateur"),
+            pins: decode_pins(&pins).expect("empreintes"),
+            failure: PinFailure::default(),
+        }
+    }
+
+    fn verify(verifier: &PinnedVerifier) -> Result<ServerCertVerified, rustls::Error> {
+        let server_name = ServerName::try_from("winlog.test").expect("nom");
+        verifier.verify_server_cert(&server_cert(), &[], &server_name, &[], UnixTime::now())
+    }
+
+    #[test]
+    fn pinned_server_key_is_accepted() {
+        let pins = decode_pins(&[SERVER_PIN.to_string()]).expect("empreinte");
+        assert_eq!(spki_sha256(&server_cert()), Some(pins[0]));
+        assert!(verify(&verifier("ca.pem", &[SERVER_PIN])).is_ok());
+        assert!(verify(&verifier("ca.pem", &[])).is_ok());
+    }
+
+    #[test]
+    fn pin_mismatch_is_reported_with_presented_keys() {
+        let other_pin = base64::engine::general_purpose::STANDARD.encode([0u8; 32]);
+        let verifier = verifier("ca.pem", &[&other_pin]);
+
+        assert!(verify(&verifier).is_err());
+        let failure = verifier.failure.lock().expect("échec").clone().expect("échec consigné");
+        assert_eq!(failure.server_name, "winlog.test");
+        assert_eq!(failure.presented, [SERVER_PIN]);
+    }
+
+    #[test]
+    fn pin_does_not_bypass_chain_verification() {
+        // Clé épinglée, mais autorité inconnue : refus sans échec d'épinglage
+        let verifier = verifier("other-ca.pem", &[SERVER_PIN]);
+        assert!(verify(&verifier).is_err());
+        assert!(verifier.failure.lock().expect("échec").is_none());
+    }
+
+    #[test]
+    fn malformed_pins_are_refused() {
+        assert!(decode_pins(&["pas du base64 !".to_string()]).is_err());
+        // Base64 valide mais pas 32 octets
+        assert!(decode_pins(&["c2hvcnQ=".to_string()]).is_err());
+    }
+
+    #[test]
+    fn ca_bundle_must_exist_and_hold_certificates() {
+        let roots = load_roots(Some(&fixture("ca.pem"))).expect("autorités");
+        assert!(!roots.is_empty());
+
+        assert!(load_roots(Some(&fixture("absent.pem"))).is_err());
+
+        let empty = std::env::temp_dir().join(format!("winlog-ca-{}.pem", uuid::Uuid::new_v4()));
+        std::fs::write(&empty, "pas de certificat\n").expect("fichier");
+        let error = load_roots(Some(&empty)).expect_err("fichier sans certificat").to_string();
+        let _ = std::fs::remove_file(&empty);
+        assert!(error.contains("Aucun certificat PEM"), "{}", error);
+    }
+
+    #[test]
+    fn client_config_builds_with_ca_bundle_and_pins() {
+        let pins = [SERVER_PIN.to_string()];
+        assert!(build_client_config(Some(&fixture("ca.pem")), &pins, PinFailure::default()).is_ok());
+        assert!(build_client_config(Some(&fixture("ca.pem")), &["court".to_string()], PinFailure::default()).is_err());
+    }
+}
@@ -0,0 +1,4 @@
+// Recompile le serveur quand une migration change (sqlx::migrate! les embarque)
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
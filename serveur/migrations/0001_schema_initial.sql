@@ -0,0 +1,150 @@
+-- ============================================================================
+-- Migration 0001 : schéma initial (structure partitionnée)
+--
+-- Tables events_today / events_history, jetons d'enrôlement, sessions et vue
+-- events_all. Appliquée par le serveur au démarrage (sqlx::migrate!).
+--
+-- IF NOT EXISTS : une base créée auparavant par scripts/create_base.sh est
+-- reprise telle quelle et enregistrée comme étant à la version 1.
+-- Les PRAGMA (journal_mode, synchronous...) viennent de config.toml [database].
+-- ============================================================================
+
+-- ============================================================================
+-- Table : events_today (données du jour - haute performance)
+-- ============================================================================
+CREATE TABLE IF NOT EXISTS events_today (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username VARCHAR(50) NOT NULL,
+    action CHAR(1) NOT NULL CHECK (action IN ('C', 'D', 'M')),
+    timestamp DATETIME NOT NULL,
+    hostname VARCHAR(100),
+    source_ip VARCHAR(45),
+    -- Adresse de la connexion TCP (proxy de confiance le cas échéant)
+    peer_ip VARCHAR(45),
+    server_timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+    
+    -- Informations OS
+    os_name VARCHAR(50),
+    os_version VARCHAR(100),
+    kernel_version VARCHAR(50),
+    
+    -- Informations matérielles (JSON pour action='M')
+    hardware_info TEXT,
+    
+    -- Identifiant unique de session
+    session_uuid VARCHAR(100),
+    
+    -- Identifiant généré par le client (UUID, idempotence des renvois)
+    event_id VARCHAR(36),
+    
+    -- Metadata
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Index optimisés pour recherche rapide (sessions actives)
+CREATE INDEX IF NOT EXISTS idx_today_username_action ON events_today(username, action);
+CREATE INDEX IF NOT EXISTS idx_today_timestamp ON events_today(timestamp);
+CREATE INDEX IF NOT EXISTS idx_today_hostname ON events_today(hostname);
+CREATE INDEX IF NOT EXISTS idx_today_session ON events_today(session_uuid);
+CREATE INDEX IF NOT EXISTS idx_today_action_time ON events_today(action, timestamp);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_today_event_id ON events_today(event_id);
+
+-- ============================================================================
+-- Table : events_history (archive - toutes les données passées)
+-- ============================================================================
+CREATE TABLE IF NOT EXISTS events_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username VARCHAR(50) NOT NULL,
+    action CHAR(1) NOT NULL CHECK (action IN ('C', 'D', 'M')),
+    timestamp DATETIME NOT NULL,
+    hostname VARCHAR(100),
+    source_ip VARCHAR(45),
+    peer_ip VARCHAR(45),
+    server_timestamp DATETIME,
+    
+    -- Informations OS
+    os_name VARCHAR(50),
+    os_version VARCHAR(100),
+    kernel_version VARCHAR(50),
+    
+    -- Informations matérielles
+    hardware_info TEXT,
+    
+    -- Session
+    session_uuid VARCHAR(100),
+    
+    -- Identifiant généré par le client
+    event_id VARCHAR(36),
+    
+    -- Metadata
+    created_at DATETIME,
+    archived_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Index pour recherche dans l'historique
+CREATE INDEX IF NOT EXISTS idx_history_username ON events_history(username);
+CREATE INDEX IF NOT EXISTS idx_history_timestamp ON events_history(timestamp);
+CREATE INDEX IF NOT EXISTS idx_history_hostname ON events_history(hostname);
+CREATE INDEX IF NOT EXISTS idx_history_session ON events_history(session_uuid);
+CREATE INDEX IF NOT EXISTS idx_history_date ON events_history(DATE(timestamp));
+CREATE INDEX IF NOT EXISTS idx_history_event_id ON events_history(event_id);
+-- Dernière rotation (MAX(archived_at)) : rangement des événements reçus en retard
+CREATE INDEX IF NOT EXISTS idx_history_archived ON events_history(archived_at);
+
+-- ============================================================================
+-- Table : host_tokens (jetons d'enrôlement par machine)
+-- ============================================================================
+CREATE TABLE IF NOT EXISTS host_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    hostname VARCHAR(100) NOT NULL,
+    
+    -- Empreinte SHA-256 (hex) du jeton : le jeton en clair n'est jamais stocké
+    token_hash CHAR(64) NOT NULL UNIQUE,
+    
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    revoked_at DATETIME
+);
+
+-- Un seul jeton actif par machine (ré-enrôlement après révocation uniquement)
+CREATE UNIQUE INDEX IF NOT EXISTS idx_tokens_active_host ON host_tokens(hostname) WHERE revoked_at IS NULL;
+
+-- ============================================================================
+-- Table : sessions (une ligne par session, tenue à jour avec les événements)
+-- ============================================================================
+CREATE TABLE IF NOT EXISTS sessions (
+    session_uuid VARCHAR(100) PRIMARY KEY,
+    username VARCHAR(50) NOT NULL,
+    hostname VARCHAR(100),
+    
+    -- Début : connexion (action='C')
+    started_at DATETIME NOT NULL,
+    start_ip VARCHAR(45),
+    os_name VARCHAR(50),
+    os_version VARCHAR(100),
+    
+    -- Fin : NULL tant que la session est ouverte
+    ended_at DATETIME,
+    end_ip VARCHAR(45),
+    close_reason VARCHAR(20) CHECK (close_reason IN ('explicit', 'auto_closed', 'timed_out')),
+    
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Sessions ouvertes (index partiel : recherche de la session en cours)
+CREATE INDEX IF NOT EXISTS idx_sessions_open ON sessions(username, hostname, started_at) WHERE ended_at IS NULL;
+-- Sessions d'un user@host dans le temps (événements reçus en retard)
+CREATE INDEX IF NOT EXISTS idx_sessions_user_host ON sessions(username, hostname, started_at);
+
+-- ============================================================================
+-- Vue combinée (pour requêtes globales)
+-- ============================================================================
+CREATE VIEW IF NOT EXISTS events_all AS
+    SELECT id, username, action, timestamp, hostname, source_ip, peer_ip,
+           server_timestamp, os_name, os_version, kernel_version,
+           hardware_info, session_uuid, event_id, created_at, 'today' as source
+    FROM events_today
+    UNION ALL
+    SELECT id, username, action, timestamp, hostname, source_ip, peer_ip,
+           server_timestamp, os_name, os_version, kernel_version,
+           hardware_info, session_uuid, event_id, created_at, 'history' as source
+    FROM events_history;
@@ -0,0 +1,31 @@
+-- ============================================================================
+-- Migration 0002 : journal des rotations quotidiennes
+--
+-- Une ligne par exécution de la rotation events_today → events_history
+-- (planifiée, rattrapage au démarrage ou déclenchée par l'administrateur),
+-- avec le nombre de lignes avant et après.
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS rotations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    
+    -- Début de la rotation (UTC) : valeur de archived_at des lignes déplacées
+    rotated_at DATETIME NOT NULL,
+    triggered_by VARCHAR(20) NOT NULL CHECK (triggered_by IN ('scheduled', 'catch_up', 'manual')),
+    status VARCHAR(10) NOT NULL CHECK (status IN ('success', 'failed')),
+    
+    -- Comptages (NULL si la rotation a échoué)
+    today_before INTEGER,
+    history_before INTEGER,
+    today_after INTEGER,
+    history_after INTEGER,
+    archived INTEGER,
+    
+    -- Sessions encore ouvertes, reportées sur la nouvelle journée
+    open_sessions INTEGER,
+    
+    duration_ms INTEGER NOT NULL,
+    error TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_rotations_date ON rotations(rotated_at);
@@ -0,0 +1,30 @@
+-- ============================================================================
+-- Migration 0003 : journal des purges de conservation
+--
+-- Une ligne par règle appliquée lors de chaque purge (events_history par
+-- type d'action, puis sessions terminées dont les événements ont disparu) :
+-- date limite, nombre de lignes supprimées et nombre de lots.
+-- Sert de preuve de l'application de la politique de conservation.
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS purges (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    
+    -- Début de la purge (UTC)
+    purged_at DATETIME NOT NULL,
+    target VARCHAR(20) NOT NULL CHECK (target IN ('events_history', 'sessions')),
+    
+    -- Règle appliquée (NULL pour la table sessions)
+    action VARCHAR(10),
+    retention_days INTEGER,
+    -- Événements antérieurs à cette date supprimés (ISO 8601 UTC)
+    cutoff DATETIME,
+    
+    deleted INTEGER NOT NULL,
+    batches INTEGER NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    status VARCHAR(10) NOT NULL CHECK (status IN ('success', 'failed')),
+    error TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_purges_date ON purges(purged_at);
@@ -0,0 +1,16 @@
+-- ============================================================================
+-- Migration 0004 : archivage Parquet avant purge
+--
+-- Nombre de fichiers Parquet écrits par la purge avant suppression des lignes
+-- (NULL : archivage désactivé, section [database.retention] sans archive_dir).
+--
+-- La purge marque les lignes écrites dans une archive (exported_at = début de
+-- la purge). Si la suppression échoue ensuite, la purge suivante ne les
+-- exporte pas une seconde fois : elles sont seulement supprimées. La colonne
+-- ne figure pas dans les archives ni dans events_all.
+-- ============================================================================
+
+ALTER TABLE purges ADD COLUMN archive_files INTEGER;
+
+-- Export Parquet de la ligne (UTC, NULL : pas encore exportée)
+ALTER TABLE events_history ADD COLUMN exported_at DATETIME;
@@ -0,0 +1,59 @@
+-- ============================================================================
+-- Migration 0005 : identifiants de session UUIDv7
+--
+-- Les anciens identifiants (username@hostname@hash6, avec les préfixes
+-- orphan_ et hardware_) sont remplacés par des UUIDv7 dont l'horodatage est
+-- celui du premier événement de la session. Le préfixe est conservé :
+--   jdupont@PC-01@a3f7e9          → 0190a1b2-c3d4-7e5f-8a6b-1c2d3e4f5a6b
+--   orphan_jdupont@PC-01@a3f7e9   → orphan_0190a1b2-…
+--   hardware_jdupont@PC-01@a3f7e9 → hardware_0190a1b2-…
+-- Une même valeur reçoit le même nouvel identifiant dans events_today,
+-- events_history et sessions. Utilisateur et machine restent dans les
+-- colonnes username et hostname (indexées).
+-- ============================================================================
+
+-- Correspondance ancien → nouvel identifiant (anciens formats : contiennent '@')
+CREATE TEMP TABLE session_uuid_map AS
+SELECT
+    old_uuid,
+    CASE
+        WHEN old_uuid LIKE 'orphan\_%' ESCAPE '\' THEN 'orphan_'
+        WHEN old_uuid LIKE 'hardware\_%' ESCAPE '\' THEN 'hardware_'
+        ELSE ''
+    END
+    -- 48 bits : millisecondes Unix, puis version 7, variant RFC 4122 et bits aléatoires
+    || substr(printf('%012x', ms), 1, 8) || '-'
+    || substr(printf('%012x', ms), 9, 4) || '-'
+    || '7' || lower(substr(hex(randomblob(2)), 2, 3)) || '-'
+    || substr('89ab', 1 + abs(random() % 4), 1) || lower(substr(hex(randomblob(2)), 2, 3)) || '-'
+    || lower(hex(randomblob(6))) AS new_uuid
+FROM (
+    SELECT
+        session_uuid AS old_uuid,
+        MAX(0, CAST(ROUND((MIN(julianday(ts)) - 2440587.5) * 86400000) AS INTEGER)) AS ms
+    FROM (
+        SELECT session_uuid, timestamp AS ts FROM events_today
+        UNION ALL
+        SELECT session_uuid, timestamp FROM events_history
+        UNION ALL
+        SELECT session_uuid, started_at FROM sessions
+    )
+    WHERE session_uuid LIKE '%@%'
+    GROUP BY session_uuid
+);
+
+CREATE UNIQUE INDEX temp.idx_session_uuid_map ON session_uuid_map(old_uuid);
+
+UPDATE events_today
+SET session_uuid = (SELECT new_uuid FROM session_uuid_map WHERE old_uuid = events_today.session_uuid)
+WHERE session_uuid LIKE '%@%';
+
+UPDATE events_history
+SET session_uuid = (SELECT new_uuid FROM session_uuid_map WHERE old_uuid = events_history.session_uuid)
+WHERE session_uuid LIKE '%@%';
+
+UPDATE sessions
+SET session_uuid = (SELECT new_uuid FROM session_uuid_map WHERE old_uuid = sessions.session_uuid)
+WHERE session_uuid LIKE '%@%';
+
+DROP TABLE session_uuid_map;
@@ -0,0 +1,137 @@
+-- ============================================================================
+-- Migration 0006 : vocabulaire d'actions étendu, temps actif des sessions
+--
+-- Nouvelles actions (models::Action) : 'L' verrouillage, 'U' déverrouillage,
+-- 'B' démarrage, 'S' arrêt, 'H' signal de présence. SQLite ne permet pas de
+-- modifier une contrainte CHECK : events_today et events_history sont
+-- reconstruites (données, id et index conservés), la vue events_all est
+-- recréée à l'identique.
+--
+-- Table sessions : locked_at (verrouillage en cours) et locked_seconds (durée
+-- cumulée des verrouillages) donnent le temps actif d'une session.
+-- ============================================================================
+
+DROP VIEW IF EXISTS events_all;
+
+-- ============================================================================
+-- Table : events_today
+-- ============================================================================
+CREATE TABLE events_today_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username VARCHAR(50) NOT NULL,
+    action CHAR(1) NOT NULL CHECK (action IN ('C', 'D', 'M', 'L', 'U', 'B', 'S', 'H')),
+    timestamp DATETIME NOT NULL,
+    hostname VARCHAR(100),
+    source_ip VARCHAR(45),
+    -- Adresse de la connexion TCP (proxy de confiance le cas échéant)
+    peer_ip VARCHAR(45),
+    server_timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+
+    -- Informations OS
+    os_name VARCHAR(50),
+    os_version VARCHAR(100),
+    kernel_version VARCHAR(50),
+
+    -- Informations matérielles (JSON pour action='M')
+    hardware_info TEXT,
+
+    -- Identifiant unique de session
+    session_uuid VARCHAR(100),
+
+    -- Identifiant généré par le client (UUID, idempotence des renvois)
+    event_id VARCHAR(36),
+
+    -- Metadata
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO events_today_new
+SELECT id, username, action, timestamp, hostname, source_ip, peer_ip, server_timestamp,
+       os_name, os_version, kernel_version, hardware_info, session_uuid,
+       event_id, created_at
+FROM events_today;
+
+DROP TABLE events_today;
+ALTER TABLE events_today_new RENAME TO events_today;
+
+CREATE INDEX idx_today_username_action ON events_today(username, action);
+CREATE INDEX idx_today_timestamp ON events_today(timestamp);
+CREATE INDEX idx_today_hostname ON events_today(hostname);
+CREATE INDEX idx_today_session ON events_today(session_uuid);
+CREATE INDEX idx_today_action_time ON events_today(action, timestamp);
+CREATE UNIQUE INDEX idx_today_event_id ON events_today(event_id);
+
+-- ============================================================================
+-- Table : events_history
+-- ============================================================================
+CREATE TABLE events_history_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username VARCHAR(50) NOT NULL,
+    action CHAR(1) NOT NULL CHECK (action IN ('C', 'D', 'M', 'L', 'U', 'B', 'S', 'H')),
+    timestamp DATETIME NOT NULL,
+    hostname VARCHAR(100),
+    source_ip VARCHAR(45),
+    peer_ip VARCHAR(45),
+    server_timestamp DATETIME,
+
+    -- Informations OS
+    os_name VARCHAR(50),
+    os_version VARCHAR(100),
+    kernel_version VARCHAR(50),
+
+    -- Informations matérielles
+    hardware_info TEXT,
+
+    -- Session
+    session_uuid VARCHAR(100),
+
+    -- Identifiant généré par le client
+    event_id VARCHAR(36),
+
+    -- Metadata
+    created_at DATETIME,
+    archived_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+
+    -- Export Parquet de la ligne (migration 0004)
+    exported_at DATETIME
+);
+
+INSERT INTO events_history_new
+SELECT id, username, action, timestamp, hostname, source_ip, peer_ip, server_timestamp,
+       os_name, os_version, kernel_version, hardware_info, session_uuid,
+       event_id, created_at, archived_at, exported_at
+FROM events_history;
+
+DROP TABLE events_history;
+ALTER TABLE events_history_new RENAME TO events_history;
+
+CREATE INDEX idx_history_username ON events_history(username);
+CREATE INDEX idx_history_timestamp ON events_history(timestamp);
+CREATE INDEX idx_history_hostname ON events_history(hostname);
+CREATE INDEX idx_history_session ON events_history(session_uuid);
+CREATE INDEX idx_history_date ON events_history(DATE(timestamp));
+CREATE INDEX idx_history_event_id ON events_history(event_id);
+-- Dernière rotation (MAX(archived_at)) : rangement des événements reçus en retard
+CREATE INDEX idx_history_archived ON events_history(archived_at);
+
+-- ============================================================================
+-- Table : sessions (temps actif)
+-- ============================================================================
+-- Début du verrouillage en cours (NULL : écran déverrouillé)
+ALTER TABLE sessions ADD COLUMN locked_at DATETIME;
+-- Durée cumulée des verrouillages terminés (secondes)
+ALTER TABLE sessions ADD COLUMN locked_seconds INTEGER NOT NULL DEFAULT 0;
+
+-- ============================================================================
+-- Vue combinée (identique à la migration 0001)
+-- ============================================================================
+CREATE VIEW events_all AS
+    SELECT id, username, action, timestamp, hostname, source_ip, peer_ip,
+           server_timestamp, os_name, os_version, kernel_version,
+           hardware_info, session_uuid, event_id, created_at, 'today' as source
+    FROM events_today
+    UNION ALL
+    SELECT id, username, action, timestamp, hostname, source_ip, peer_ip,
+           server_timestamp, os_name, os_version, kernel_version,
+           hardware_info, session_uuid, event_id, created_at, 'history' as source
+    FROM events_history;
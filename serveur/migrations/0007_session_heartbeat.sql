@@ -0,0 +1,13 @@
+-- ============================================================================
+-- Migration 0007 : signal de présence des sessions
+--
+-- Timestamp du dernier signal de présence (action 'H') reçu pour la session.
+-- NULL : aucun signal (client qui n'en envoie pas), la session n'expire pas.
+-- Une session ouverte dont le dernier signal est plus ancien que
+-- [sessions] heartbeat_timeout_minutes est fermée ('timed_out').
+-- ============================================================================
+
+ALTER TABLE sessions ADD COLUMN last_heartbeat_at DATETIME;
+
+-- Sessions ouvertes suivies par signal de présence (recherche des expirées)
+CREATE INDEX IF NOT EXISTS idx_sessions_heartbeat ON sessions(last_heartbeat_at) WHERE ended_at IS NULL;
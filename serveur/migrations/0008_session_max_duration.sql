@@ -0,0 +1,100 @@
+-- ============================================================================
+-- Migration 0008 : durée maximale des sessions
+--
+-- Nouveau motif de fermeture 'policy_closed' : session fermée par le serveur
+-- au terme de la durée maximale configurée ([sessions] max_session_hours,
+-- globale ou par groupe de machines). SQLite ne permet pas de modifier une
+-- contrainte CHECK : la table sessions est reconstruite (données et index
+-- conservés).
+--
+-- Une déconnexion (D) insérée par le serveur porte désormais le motif de
+-- fermeture de sa session : 'auto_closed', 'timed_out' ou 'policy_closed'
+-- (NULL : événement envoyé par un client). events_all et les archives Parquet
+-- distinguent ainsi une fermeture serveur d'une déconnexion réelle.
+-- ============================================================================
+
+CREATE TABLE sessions_new (
+    session_uuid VARCHAR(100) PRIMARY KEY,
+    username VARCHAR(50) NOT NULL,
+    hostname VARCHAR(100),
+
+    -- Début : connexion (action='C')
+    started_at DATETIME NOT NULL,
+    start_ip VARCHAR(45),
+    os_name VARCHAR(50),
+    os_version VARCHAR(100),
+
+    -- Fin : NULL tant que la session est ouverte
+    ended_at DATETIME,
+    end_ip VARCHAR(45),
+    close_reason VARCHAR(20) CHECK (close_reason IN ('explicit', 'auto_closed', 'timed_out', 'policy_closed')),
+
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+
+    -- Temps actif (migration 0006)
+    locked_at DATETIME,
+    locked_seconds INTEGER NOT NULL DEFAULT 0,
+
+    -- Signal de présence (migration 0007)
+    last_heartbeat_at DATETIME
+);
+
+INSERT INTO sessions_new
+SELECT session_uuid, username, hostname, started_at, start_ip, os_name, os_version,
+       ended_at, end_ip, close_reason, updated_at, locked_at, locked_seconds,
+       last_heartbeat_at
+FROM sessions;
+
+DROP TABLE sessions;
+ALTER TABLE sessions_new RENAME TO sessions;
+
+-- Sessions ouvertes (index partiel : recherche de la session en cours)
+CREATE INDEX idx_sessions_open ON sessions(username, hostname, started_at) WHERE ended_at IS NULL;
+-- Sessions d'un user@host dans le temps (événements reçus en retard)
+CREATE INDEX idx_sessions_user_host ON sessions(username, hostname, started_at);
+-- Sessions ouvertes ayant reçu un signal de présence (expiration)
+CREATE INDEX idx_sessions_heartbeat ON sessions(last_heartbeat_at) WHERE ended_at IS NULL;
+-- Sessions ouvertes par début (dépassement de la durée maximale)
+CREATE INDEX idx_sessions_open_started ON sessions(started_at) WHERE ended_at IS NULL;
+
+-- ============================================================================
+-- Motif des déconnexions insérées par le serveur
+-- ============================================================================
+DROP VIEW IF EXISTS events_all;
+
+ALTER TABLE events_today ADD COLUMN close_reason VARCHAR(20)
+    CHECK (close_reason IN ('auto_closed', 'timed_out', 'policy_closed'));
+ALTER TABLE events_history ADD COLUMN close_reason VARCHAR(20)
+    CHECK (close_reason IN ('auto_closed', 'timed_out', 'policy_closed'));
+
+-- Déconnexions serveur existantes : sans event_id, à l'heure de fin d'une
+-- session fermée par le serveur
+UPDATE events_today SET close_reason = (
+    SELECT s.close_reason FROM sessions s
+    WHERE s.session_uuid = events_today.session_uuid
+      AND s.ended_at = events_today.timestamp
+      AND s.close_reason IN ('auto_closed', 'timed_out', 'policy_closed')
+)
+WHERE action = 'D' AND event_id IS NULL;
+
+UPDATE events_history SET close_reason = (
+    SELECT s.close_reason FROM sessions s
+    WHERE s.session_uuid = events_history.session_uuid
+      AND s.ended_at = events_history.timestamp
+      AND s.close_reason IN ('auto_closed', 'timed_out', 'policy_closed')
+)
+WHERE action = 'D' AND event_id IS NULL;
+
+-- Vue combinée (colonnes de la migration 0006, plus le motif de fermeture)
+CREATE VIEW events_all AS
+    SELECT id, username, action, timestamp, hostname, source_ip, peer_ip,
+           server_timestamp, os_name, os_version, kernel_version,
+           hardware_info, session_uuid, event_id, created_at, close_reason,
+           'today' as source
+    FROM events_today
+    UNION ALL
+    SELECT id, username, action, timestamp, hostname, source_ip, peer_ip,
+           server_timestamp, os_name, os_version, kernel_version,
+           hardware_info, session_uuid, event_id, created_at, close_reason,
+           'history' as source
+    FROM events_history;
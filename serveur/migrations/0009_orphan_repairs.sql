@@ -0,0 +1,82 @@
+-- ============================================================================
+-- Migration 0009 : réappariement des déconnexions orphelines
+--
+-- Une déconnexion sans session ouverte reçoit un identifiant 'orphan_…'
+-- (connexion perdue ou reçue après elle). La réconciliation (après chaque
+-- connexion, et à la rotation) la rattache à la connexion la plus proche du
+-- même user@host ; chaque réparation est journalisée dans orphan_repairs.
+--
+-- orphan_repairs contient des noms d'utilisateur et de machine : la purge de
+-- conservation la traite ([database.retention] orphan_repairs_days), par lots
+-- comme events_history. SQLite ne permet pas de modifier une contrainte
+-- CHECK : la table purges est reconstruite (données et id conservés) pour
+-- accepter la nouvelle cible.
+-- ============================================================================
+
+CREATE TABLE IF NOT EXISTS orphan_repairs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+
+    -- Réparation (UTC) et origine : 'late_connection' ou 'rotation'
+    repaired_at DATETIME NOT NULL,
+    triggered_by VARCHAR(20) NOT NULL CHECK (triggered_by IN ('late_connection', 'rotation')),
+
+    -- Déconnexion réappariée
+    username VARCHAR(50) NOT NULL,
+    hostname VARCHAR(100),
+    disconnect_timestamp DATETIME NOT NULL,
+    orphan_uuid VARCHAR(100) NOT NULL,
+
+    -- Session retrouvée et sa fin avant réparation (NULL : session ouverte)
+    session_uuid VARCHAR(100) NOT NULL,
+    session_started_at DATETIME NOT NULL,
+    previous_ended_at DATETIME,
+    previous_close_reason VARCHAR(20)
+);
+
+CREATE INDEX IF NOT EXISTS idx_orphan_repairs_date ON orphan_repairs(repaired_at);
+CREATE INDEX IF NOT EXISTS idx_orphan_repairs_session ON orphan_repairs(session_uuid);
+
+-- Déconnexions orphelines (index partiels : quelques lignes sur toute la table)
+CREATE INDEX IF NOT EXISTS idx_today_orphans ON events_today(username, hostname, timestamp)
+    WHERE action = 'D' AND session_uuid LIKE 'orphan\_%' ESCAPE '\';
+CREATE INDEX IF NOT EXISTS idx_history_orphans ON events_history(username, hostname, timestamp)
+    WHERE action = 'D' AND session_uuid LIKE 'orphan\_%' ESCAPE '\';
+
+-- Recherche des connexions d'un user@host par date (réappariement ; la
+-- rotation ne remonte que sur [rotation] orphan_window_hours)
+CREATE INDEX IF NOT EXISTS idx_today_user_host_action ON events_today(username, hostname, action, timestamp);
+CREATE INDEX IF NOT EXISTS idx_history_user_host_action ON events_history(username, hostname, action, timestamp);
+
+-- ============================================================================
+-- Table : purges
+-- ============================================================================
+CREATE TABLE purges_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+
+    -- Début de la purge (UTC)
+    purged_at DATETIME NOT NULL,
+    target VARCHAR(20) NOT NULL CHECK (target IN ('events_history', 'sessions', 'orphan_repairs')),
+
+    -- Règle appliquée (action NULL hors events_history, durée NULL pour sessions)
+    action VARCHAR(10),
+    retention_days INTEGER,
+    -- Lignes antérieures à cette date supprimées (ISO 8601 UTC)
+    cutoff DATETIME,
+
+    deleted INTEGER NOT NULL,
+    batches INTEGER NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    status VARCHAR(10) NOT NULL CHECK (status IN ('success', 'failed')),
+    error TEXT,
+    archive_files INTEGER
+);
+
+INSERT INTO purges_new
+SELECT id, purged_at, target, action, retention_days, cutoff,
+       deleted, batches, duration_ms, status, error, archive_files
+FROM purges;
+
+DROP TABLE purges;
+ALTER TABLE purges_new RENAME TO purges;
+
+CREATE INDEX idx_purges_date ON purges(purged_at);
@@ -0,0 +1,95 @@
+-- ============================================================================
+-- Migration 0010 : écart d'horloge des machines
+--
+-- L'écart (timestamp client - heure de réception) est mesuré sur chaque
+-- événement envoyé en direct (POST /api/v1/events) et suivi par machine dans
+-- host_clocks. Un événement hors tolérance ([clock] max_skew_secs) est marqué
+-- (clock_skew_secs), refusé, ou corrigé : timestamp recalculé avec l'écart
+-- de la machine, horodatage d'origine conservé dans client_timestamp.
+--
+-- host_clocks contient des noms de machine : la purge de conservation
+-- supprime les machines dont l'horloge n'a plus été mesurée depuis
+-- [database.retention] host_clocks_days. La table purges est reconstruite
+-- (données et id conservés) pour accepter cette nouvelle cible.
+-- ============================================================================
+
+DROP VIEW IF EXISTS events_all;
+
+-- Écart d'horloge hors tolérance (secondes, NULL : dans la tolérance ou non mesuré)
+ALTER TABLE events_today ADD COLUMN clock_skew_secs INTEGER;
+-- Timestamp envoyé par le client, lorsque le serveur l'a corrigé (NULL sinon)
+ALTER TABLE events_today ADD COLUMN client_timestamp DATETIME;
+
+ALTER TABLE events_history ADD COLUMN clock_skew_secs INTEGER;
+ALTER TABLE events_history ADD COLUMN client_timestamp DATETIME;
+
+-- ============================================================================
+-- Table : host_clocks (dernière mesure et bilan par machine)
+-- ============================================================================
+CREATE TABLE IF NOT EXISTS host_clocks (
+    hostname VARCHAR(100) PRIMARY KEY,
+
+    -- Dernière mesure : écart (secondes, positif = horloge en avance) et heure serveur
+    skew_secs INTEGER NOT NULL,
+    last_seen_at DATETIME NOT NULL,
+
+    -- Bilan depuis la première mesure
+    samples INTEGER NOT NULL DEFAULT 0,
+    min_skew_secs INTEGER NOT NULL,
+    max_skew_secs INTEGER NOT NULL,
+    out_of_tolerance INTEGER NOT NULL DEFAULT 0,
+    last_out_of_tolerance_at DATETIME
+);
+
+-- Machines qui ne sont plus mesurées (purge de conservation)
+CREATE INDEX IF NOT EXISTS idx_host_clocks_last_seen ON host_clocks(last_seen_at);
+
+-- ============================================================================
+-- Table : purges
+-- ============================================================================
+CREATE TABLE purges_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+
+    -- Début de la purge (UTC)
+    purged_at DATETIME NOT NULL,
+    target VARCHAR(20) NOT NULL CHECK (target IN ('events_history', 'sessions', 'orphan_repairs', 'host_clocks')),
+
+    -- Règle appliquée (action NULL hors events_history, durée NULL pour sessions)
+    action VARCHAR(10),
+    retention_days INTEGER,
+    -- Lignes antérieures à cette date supprimées (ISO 8601 UTC)
+    cutoff DATETIME,
+
+    deleted INTEGER NOT NULL,
+    batches INTEGER NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    status VARCHAR(10) NOT NULL CHECK (status IN ('success', 'failed')),
+    error TEXT,
+    archive_files INTEGER
+);
+
+INSERT INTO purges_new
+SELECT id, purged_at, target, action, retention_days, cutoff,
+       deleted, batches, duration_ms, status, error, archive_files
+FROM purges;
+
+DROP TABLE purges;
+ALTER TABLE purges_new RENAME TO purges;
+
+CREATE INDEX idx_purges_date ON purges(purged_at);
+
+-- ============================================================================
+-- Vue combinée (colonnes de la migration 0008, plus l'écart d'horloge)
+-- ============================================================================
+CREATE VIEW events_all AS
+    SELECT id, username, action, timestamp, hostname, source_ip, peer_ip,
+           server_timestamp, os_name, os_version, kernel_version,
+           hardware_info, session_uuid, event_id, created_at, close_reason,
+           clock_skew_secs, client_timestamp, 'today' as source
+    FROM events_today
+    UNION ALL
+    SELECT id, username, action, timestamp, hostname, source_ip, peer_ip,
+           server_timestamp, os_name, os_version, kernel_version,
+           hardware_info, session_uuid, event_id, created_at, close_reason,
+           clock_skew_secs, client_timestamp, 'history' as source
+    FROM events_history;
@@ -1,7 +1,9 @@
 #!/bin/bash
 ###############################################################################
-# Script de création de la base SQLite Winlog avec structure partitionnée
-# Crée deux tables : events_today (données du jour) et events_history (archive)
+# Script de création du fichier de base SQLite Winlog
+# Prépare le répertoire data/ et un fichier vide en mode WAL, avec les droits
+# de l'utilisateur courant. Le schéma (migrations/) est appliqué par le serveur
+# au démarrage.
 #
 # Usage: ./create_base.sh
 ###############################################################################
@@ -15,7 +17,7 @@ PROJECT_DIR="$(dirname "$SCRIPT_DIR")"
 DB_DIR="$PROJECT_DIR/data"
 DB_PATH="$DB_DIR/winlog.db"
 
-echo "=== Création de la base Winlog (structure partitionnée) ==="
+echo "=== Création de la base Winlog ==="
 echo ""
 
 # Vérifier que sqlite3 est installé
@@ -56,131 +58,23 @@ if [ -f "$DB_PATH" ]; then
     exit 1
 fi
 
-# Création de la base avec les deux tables
+# Création du fichier de base (le schéma est appliqué par le serveur)
 echo ""
-echo "Création de la structure de la base..."
+echo "Création du fichier de base..."
 
-sqlite3 "$DB_PATH" <<'EOF'
--- Configuration optimale SQLite
-PRAGMA journal_mode = WAL;
-PRAGMA synchronous = NORMAL;
-PRAGMA busy_timeout = 30000;
-PRAGMA cache_size = 10000;
-PRAGMA foreign_keys = ON;
-
--- ============================================================================
--- Table : events_today (données du jour - haute performance)
--- ============================================================================
-CREATE TABLE IF NOT EXISTS events_today (
-    id INTEGER PRIMARY KEY AUTOINCREMENT,
-    username VARCHAR(50) NOT NULL,
-    action CHAR(1) NOT NULL CHECK (action IN ('C', 'D', 'M')),
-    timestamp DATETIME NOT NULL,
-    hostname VARCHAR(100),
-    source_ip VARCHAR(45),
-    server_timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
-    
-    -- Informations OS
-    os_name VARCHAR(50),
-    os_version VARCHAR(100),
-    kernel_version VARCHAR(50),
-    
-    -- Informations matérielles (JSON pour action='M')
-    hardware_info TEXT,
-    
-    -- Identifiant unique de session
-    session_uuid VARCHAR(100),
-    
-    -- Metadata
-    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-);
-
--- Index optimisés pour recherche rapide (sessions actives)
-CREATE INDEX IF NOT EXISTS idx_today_username_action ON events_today(username, action);
-CREATE INDEX IF NOT EXISTS idx_today_timestamp ON events_today(timestamp);
-CREATE INDEX IF NOT EXISTS idx_today_hostname ON events_today(hostname);
-CREATE INDEX IF NOT EXISTS idx_today_session ON events_today(session_uuid);
-CREATE INDEX IF NOT EXISTS idx_today_action_time ON events_today(action, timestamp);
-
--- ============================================================================
--- Table : events_history (archive - toutes les données passées)
--- ============================================================================
-CREATE TABLE IF NOT EXISTS events_history (
-    id INTEGER PRIMARY KEY AUTOINCREMENT,
-    username VARCHAR(50) NOT NULL,
-    action CHAR(1) NOT NULL CHECK (action IN ('C', 'D', 'M')),
-    timestamp DATETIME NOT NULL,
-    hostname VARCHAR(100),
-    source_ip VARCHAR(45),
-    server_timestamp DATETIME,
-    
-    -- Informations OS
-    os_name VARCHAR(50),
-    os_version VARCHAR(100),
-    kernel_version VARCHAR(50),
-    
-    -- Informations matérielles
-    hardware_info TEXT,
-    
-    -- Session
-    session_uuid VARCHAR(100),
-    
-    -- Metadata
-    created_at DATETIME,
-    archived_at DATETIME DEFAULT CURRENT_TIMESTAMP
-);
-
--- Index pour recherche dans l'historique
-CREATE INDEX IF NOT EXISTS idx_history_username ON events_history(username);
-CREATE INDEX IF NOT EXISTS idx_history_timestamp ON events_history(timestamp);
-CREATE INDEX IF NOT EXISTS idx_history_hostname ON events_history(hostname);
-CREATE INDEX IF NOT EXISTS idx_history_session ON events_history(session_uuid);
-CREATE INDEX IF NOT EXISTS idx_history_date ON events_history(DATE(timestamp));
-
--- ============================================================================
--- Vue combinée (pour requêtes globales)
--- ============================================================================
-CREATE VIEW IF NOT EXISTS events_all AS
-    SELECT id, username, action, timestamp, hostname, source_ip, 
-           server_timestamp, os_name, os_version, kernel_version,
-           hardware_info, session_uuid, created_at, 'today' as source
-    FROM events_today
-    UNION ALL
-    SELECT id, username, action, timestamp, hostname, source_ip,
-           server_timestamp, os_name, os_version, kernel_version,
-           hardware_info, session_uuid, created_at, 'history' as source
-    FROM events_history;
-
-EOF
+sqlite3 "$DB_PATH" "PRAGMA journal_mode = WAL;" > /dev/null
 
 if [ $? -eq 0 ]; then
-    echo "✓ Tables créées : events_today, events_history"
-    echo "✓ Vue créée : events_all"
-    echo "✓ Index créés (11 au total)"
-    
-    # Afficher les informations finales
-    echo ""
-    echo "=== Création terminée avec succès ==="
-    echo "Base de données : $DB_PATH"
-    echo "Taille : $(du -h "$DB_PATH" | cut -f1)"
+    echo "✓ Base créée : $DB_PATH"
     echo "Permissions : $(stat -c '%a' "$DB_PATH")"
     
-    # Afficher la structure
-    echo ""
-    echo "Structure de la base :"
-    sqlite3 "$DB_PATH" ".tables"
-    
-    echo ""
-    echo "✓ La base est prête à recevoir les données Winlog !"
     echo ""
     echo "Notes :"
-    echo "  - events_today : Données du jour (auto-nettoyée quotidiennement)"
-    echo "  - events_history : Archive de toutes les données"
-    echo "  - events_all : Vue combinée pour requêtes globales"
+    echo "  - Le schéma (tables, index, vue events_all) est défini dans migrations/"
+    echo "  - Le serveur l'applique automatiquement à chaque démarrage"
+    echo "  - Ce script est facultatif : le serveur crée aussi la base si elle n'existe pas"
     echo ""
-    echo "Prochaines étapes :"
-    echo "  1. Configurer la rotation quotidienne : ./rotate_daily.sh"
-    echo "  2. Adapter index.php pour utiliser events_today"
+    echo "Prochaine étape : démarrer le serveur (cargo run --release)"
 else
     echo "❌ Erreur lors de la création de la base"
     exit 1
@@ -7,7 +7,7 @@
 # Options:
 #   --today   : Vide uniquement events_today
 #   --history : Vide uniquement events_history
-#   --all     : Vide les deux tables (défaut)
+#   --all     : Vide les deux tables et la table sessions (défaut)
 ###############################################################################
 
 set -e
@@ -43,7 +43,7 @@ case $TARGET in
         echo "Cible : events_history uniquement"
         ;;
     all)
-        echo "Cible : Toutes les tables (events_today + events_history)"
+        echo "Cible : Toutes les tables (events_today + events_history + sessions)"
         ;;
 esac
 
@@ -106,6 +106,11 @@ if [ "$TARGET" = "today" ] || [ "$TARGET" = "all" ]; then
                    WHEN 'C' THEN 'Connexions (C)'
                    WHEN 'D' THEN 'Déconnexions (D)'
                    WHEN 'M' THEN 'Matériel (M)'
+                   WHEN 'L' THEN 'Verrouillages (L)'
+                   WHEN 'U' THEN 'Déverrouillages (U)'
+                   WHEN 'B' THEN 'Démarrages (B)'
+                   WHEN 'S' THEN 'Arrêts (S)'
+                   WHEN 'H' THEN 'Signaux de présence (H)'
                    ELSE 'Autre'
                END || ' : ' || COUNT(*)
         FROM events_today 
@@ -122,6 +127,11 @@ if [ "$TARGET" = "history" ] || [ "$TARGET" = "all" ]; then
                    WHEN 'C' THEN 'Connexions (C)'
                    WHEN 'D' THEN 'Déconnexions (D)'
                    WHEN 'M' THEN 'Matériel (M)'
+                   WHEN 'L' THEN 'Verrouillages (L)'
+                   WHEN 'U' THEN 'Déverrouillages (U)'
+                   WHEN 'B' THEN 'Démarrages (B)'
+                   WHEN 'S' THEN 'Arrêts (S)'
+                   WHEN 'H' THEN 'Signaux de présence (H)'
                    ELSE 'Autre'
                END || ' : ' || COUNT(*)
         FROM events_history 
@@ -187,11 +197,12 @@ EOF
 BEGIN TRANSACTION;
 DELETE FROM events_today;
 DELETE FROM events_history;
+DELETE FROM sessions;
 DELETE FROM sqlite_sequence WHERE name IN ('events_today', 'events_history');
 COMMIT;
 VACUUM;
 EOF
-        echo "✓ Tables events_today et events_history vidées"
+        echo "✓ Tables events_today, events_history et sessions vidées"
         ;;
 esac
 
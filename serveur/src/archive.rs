@@ -0,0 +1,264 @@
+//! # Archives Parquet de events_history
+//!
+//! Avant suppression par la purge de conservation, les lignes expirées sont
+//! exportées en fichiers Parquet compressés (zstd), partitionnés par date
+//! d'événement (UTC) :
+//!
+//! ```text
+//! <archive_dir>/date=2025-03-14/events_C_20260317T010000Z.parquet
+//! ```
+//!
+//! Le partitionnement `date=…` est reconnu par DuckDB
+//! (`read_parquet('archive/**/*.parquet', hive_partitioning = true)`).
+//!
+//! La sous-commande `winlog-server restore-archive <fichier|répertoire> <base>`
+//! recharge des archives dans une base de travail (schéma complet, lignes dans
+//! events_history avec leur id d'origine).
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arrow_array::{Array, ArrayRef, Int64Array, RecordBatch, StringArray};
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+
+use crate::config::DatabaseConfig;
+use crate::database::Database;
+use crate::models::{Action, ArchivedEvent};
+
+/// Colonnes texte nullables, dans l'ordre de la table events_history
+const OPTIONAL_COLUMNS: [&str; 12] = [
+    "hostname",
+    "source_ip",
+    "peer_ip",
+    "server_timestamp",
+    "os_name",
+    "os_version",
+    "kernel_version",
+    "hardware_info",
+    "session_uuid",
+    "event_id",
+    "created_at",
+    "archived_at",
+];
+
+/// Erreurs d'écriture ou de relecture des archives
+#[derive(Debug, thiserror::Error)]
+pub enum ArchiveError {
+    #[error("Erreur d'accès au fichier: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Erreur Parquet: {0}")]
+    Parquet(#[from] ParquetError),
+
+    #[error("Erreur Arrow: {0}")]
+    Arrow(#[from] ArrowError),
+
+    #[error("Erreur SQLite: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("Archive introuvable: {0}")]
+    NotFound(PathBuf),
+
+    #[error("Archive invalide {0}: colonne {1} absente ou de type inattendu")]
+    InvalidColumn(PathBuf, &'static str),
+
+    #[error("Usage : winlog-server restore-archive <fichier.parquet|répertoire> <base.db>")]
+    Usage,
+}
+
+/// Schéma Arrow d'une archive : colonnes de events_history, dates en texte
+fn schema() -> Schema {
+    let mut fields = vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("username", DataType::Utf8, false),
+        Field::new("action", DataType::Utf8, false),
+        Field::new("timestamp", DataType::Utf8, false),
+    ];
+    fields.extend(OPTIONAL_COLUMNS.iter().map(|name| Field::new(*name, DataType::Utf8, true)));
+    // Motif d'une déconnexion insérée par le serveur (migration 0008)
+    fields.push(Field::new("close_reason", DataType::Utf8, true));
+    // Écart d'horloge (migration 0010), absent des archives antérieures
+    fields.push(Field::new("clock_skew_secs", DataType::Int64, true));
+    fields.push(Field::new("client_timestamp", DataType::Utf8, true));
+    Schema::new(fields)
+}
+
+/// Écrit une partition d'archive et retourne le chemin du fichier créé
+///
+/// Le fichier est écrit sous un nom temporaire puis renommé : une archive
+/// présente est toujours complète.
+///
+/// # Arguments
+/// * `archive_dir` - Racine des archives (`[database.retention] archive_dir`)
+/// * `date` - Date (UTC) des événements, nom de la partition
+/// * `action` - Action des événements
+/// * `stamp` - Horodatage de la purge, rend le nom de fichier unique
+/// * `events` - Lignes de la partition
+pub fn write_partition(
+    archive_dir: &Path,
+    date: &str,
+    action: Action,
+    stamp: &str,
+    events: &[ArchivedEvent],
+) -> Result<PathBuf, ArchiveError> {
+    let dir = archive_dir.join(format!("date={}", date));
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("events_{}_{}.parquet", action, stamp));
+    let tmp_path = path.with_extension("parquet.tmp");
+
+    let optional = |get: fn(&ArchivedEvent) -> &Option<String>| -> ArrayRef {
+        Arc::new(events.iter().map(|e| get(e).as_deref()).collect::<StringArray>())
+    };
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(events.iter().map(|e| e.id).collect::<Int64Array>()),
+        Arc::new(events.iter().map(|e| Some(e.username.as_str())).collect::<StringArray>()),
+        Arc::new(events.iter().map(|e| Some(e.action.as_str())).collect::<StringArray>()),
+        Arc::new(events.iter().map(|e| Some(e.timestamp.as_str())).collect::<StringArray>()),
+        optional(|e| &e.hostname),
+        optional(|e| &e.source_ip),
+        optional(|e| &e.peer_ip),
+        optional(|e| &e.server_timestamp),
+        optional(|e| &e.os_name),
+        optional(|e| &e.os_version),
+        optional(|e| &e.kernel_version),
+        optional(|e| &e.hardware_info),
+        optional(|e| &e.session_uuid),
+        optional(|e| &e.event_id),
+        optional(|e| &e.created_at),
+        optional(|e| &e.archived_at),
+        optional(|e| &e.close_reason),
+        Arc::new(events.iter().map(|e| e.clock_skew_secs).collect::<Int64Array>()),
+        optional(|e| &e.client_timestamp),
+    ];
+    let batch = RecordBatch::try_new(Arc::new(schema()), columns)?;
+
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .build();
+    let mut writer = ArrowWriter::try_new(File::create(&tmp_path)?, batch.schema(), Some(properties))?;
+    writer.write(&batch)?;
+    writer.close()?;
+
+    fs::rename(&tmp_path, &path)?;
+    Ok(path)
+}
+
+/// Relit toutes les lignes d'un fichier d'archive
+pub fn read_file(path: &Path) -> Result<Vec<ArchivedEvent>, ArchiveError> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?.build()?;
+    let mut events = Vec::new();
+
+    for batch in reader {
+        let batch = batch?;
+        let text = |name: &'static str| -> Result<&StringArray, ArchiveError> {
+            batch
+                .column_by_name(name)
+                .and_then(|column| column.as_any().downcast_ref::<StringArray>())
+                .ok_or_else(|| ArchiveError::InvalidColumn(path.to_path_buf(), name))
+        };
+        let ids = batch
+            .column_by_name("id")
+            .and_then(|column| column.as_any().downcast_ref::<Int64Array>())
+            .ok_or_else(|| ArchiveError::InvalidColumn(path.to_path_buf(), "id"))?;
+        let (username, action, timestamp) = (text("username")?, text("action")?, text("timestamp")?);
+        let optional = OPTIONAL_COLUMNS.map(text);
+        let optional = optional.into_iter().collect::<Result<Vec<_>, _>>()?;
+        let value = |column: usize, row: usize| {
+            let array = optional[column];
+            (!array.is_null(row)).then(|| array.value(row).to_string())
+        };
+        // Colonnes des migrations 0008 et 0010 : absentes des archives antérieures
+        let late_text = |name: &str| {
+            batch
+                .column_by_name(name)
+                .and_then(|column| column.as_any().downcast_ref::<StringArray>())
+        };
+        let (close_reason, client_timestamp) = (late_text("close_reason"), late_text("client_timestamp"));
+        let clock_skew = batch
+            .column_by_name("clock_skew_secs")
+            .and_then(|column| column.as_any().downcast_ref::<Int64Array>());
+        let late_value = |array: Option<&StringArray>, row: usize| {
+            array.and_then(|array| (!array.is_null(row)).then(|| array.value(row).to_string()))
+        };
+
+        for row in 0..batch.num_rows() {
+            events.push(ArchivedEvent {
+                id: ids.value(row),
+                username: username.value(row).to_string(),
+                action: action.value(row).to_string(),
+                timestamp: timestamp.value(row).to_string(),
+                hostname: value(0, row),
+                source_ip: value(1, row),
+                peer_ip: value(2, row),
+                server_timestamp: value(3, row),
+                os_name: value(4, row),
+                os_version: value(5, row),
+                kernel_version: value(6, row),
+                hardware_info: value(7, row),
+                session_uuid: value(8, row),
+                event_id: value(9, row),
+                created_at: value(10, row),
+                archived_at: value(11, row),
+                close_reason: late_value(close_reason, row),
+                clock_skew_secs: clock_skew
+                    .and_then(|array| (!array.is_null(row)).then(|| array.value(row))),
+                client_timestamp: late_value(client_timestamp, row),
+            });
+        }
+    }
+
+    Ok(events)
+}
+
+/// Sous-commande `restore-archive` : recharge des archives dans une base de travail
+///
+/// # Arguments
+/// * `args` - `<fichier.parquet|répertoire> <base.db>` ; un répertoire est
+///   parcouru récursivement. La base est créée (et migrée) si besoin.
+pub async fn restore_command(args: &[String]) -> Result<(), ArchiveError> {
+    let [source, target] = args else {
+        return Err(ArchiveError::Usage);
+    };
+
+    let source = Path::new(source);
+    let mut files = Vec::new();
+    if !source.exists() {
+        return Err(ArchiveError::NotFound(source.to_path_buf()));
+    } else if source.is_dir() {
+        collect_files(source, &mut files)?;
+        files.sort();
+    } else {
+        files.push(source.to_path_buf());
+    }
+
+    let db = Database::new(&DatabaseConfig::scratch(target)).await?;
+    let mut total = 0;
+    for file in &files {
+        let events = read_file(file)?;
+        let inserted = db.insert_archived_events(&events).await?;
+        total += inserted;
+        println!("✓ {} : {} ligne(s) lue(s), {} restaurée(s)", file.display(), events.len(), inserted);
+    }
+
+    println!("✓ {} fichier(s), {} ligne(s) restaurée(s) dans {} (table events_history)", files.len(), total, target);
+    Ok(())
+}
+
+/// Fichiers `.parquet` d'un répertoire et de ses sous-répertoires (partitions)
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), ArchiveError> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "parquet") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,152 @@
+//! # Module d'authentification des clients
+//!
+//! Vérifie la signature HMAC-SHA256 des requêtes de collecte.
+//!
+//! ## Schéma de signature
+//!
+//! Le client calcule, pour chaque envoi :
+//!
+//! ```text
+//! X-Winlog-Timestamp : <horodatage Unix en secondes>
+//! X-Winlog-Signature : hex(HMAC-SHA256(secret, "<timestamp>.<corps brut>"))
+//! ```
+//!
+//! Le serveur accepte la requête si la signature correspond à l'un des secrets
+//! de `security.hmac_secrets` et si l'horodatage est à moins de
+//! `security.signature_max_age_secs` de l'heure serveur. L'horodatage étant
+//! signé, une requête capturée ne peut pas être rejouée au-delà de cette fenêtre.
+//!
+//! La signature est vérifiée avant l'horodatage : `SignatureError::Expired`
+//! désigne une requête authentique mais hors fenêtre (horloge de la machine
+//! décalée, ou rejeu).
+//!
+//! ## Jetons d'enrôlement
+//!
+//! Chaque machine enrôlée reçoit un jeton Bearer aléatoire. Seule son empreinte
+//! SHA-256 est stockée (table `host_tokens`) : une fuite de la base ne permet
+//! pas de se faire passer pour une machine.
+
+use axum::http::{header, HeaderMap};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// En-tête portant la signature hexadécimale
+pub const SIGNATURE_HEADER: &str = "x-winlog-signature";
+
+/// En-tête portant l'horodatage Unix (secondes) inclus dans la signature
+pub const TIMESTAMP_HEADER: &str = "x-winlog-timestamp";
+
+/// En-tête portant la clé d'enrôlement (POST /api/v1/enroll)
+pub const ENROLLMENT_KEY_HEADER: &str = "x-winlog-enrollment-key";
+
+/// Vérifie la signature HMAC d'une requête
+///
+/// # Arguments
+/// * `secrets` - Secrets acceptés (rotation : plusieurs peuvent être valides)
+/// * `max_age_secs` - Écart maximal toléré entre l'horodatage signé et `now`
+/// * `timestamp` - Valeur de l'en-tête `X-Winlog-Timestamp`
+/// * `signature` - Valeur de l'en-tête `X-Winlog-Signature`
+/// * `body` - Corps brut de la requête
+/// * `now` - Heure serveur (horodatage Unix en secondes)
+///
+/// # Erreurs
+/// Retourne une `SignatureError` distincte selon la cause du rejet
+pub fn verify_signature(
+    secrets: &[String],
+    max_age_secs: u64,
+    timestamp: Option<&str>,
+    signature: Option<&str>,
+    body: &[u8],
+    now: i64,
+) -> Result<(), SignatureError> {
+    let (timestamp, signature) = match (timestamp, signature) {
+        (Some(t), Some(s)) => (t, s),
+        _ => return Err(SignatureError::Missing),
+    };
+
+    let signed_at: i64 = timestamp
+        .trim()
+        .parse()
+        .map_err(|_| SignatureError::InvalidTimestamp)?;
+
+    // 1. Signature hexadécimale
+    let signature = hex::decode(signature.trim()).map_err(|_| SignatureError::Invalid)?;
+
+    // 2. Comparaison à temps constant avec chacun des secrets actifs
+    let valid = secrets.iter().any(|secret| {
+        let Ok(mut mac) = HmacSha256::new_from_slice(secret.as_bytes()) else {
+            return false;
+        };
+        mac.update(timestamp.trim().as_bytes());
+        mac.update(b".");
+        mac.update(body);
+        mac.verify_slice(&signature).is_ok()
+    });
+
+    if !valid {
+        return Err(SignatureError::Invalid);
+    }
+
+    // 3. Horodatage dans la fenêtre tolérée (dans les deux sens : dérive d'horloge)
+    if now.abs_diff(signed_at) > max_age_secs {
+        return Err(SignatureError::Expired);
+    }
+
+    Ok(())
+}
+
+/// Génère un jeton d'enrôlement aléatoire (deux UUID v4, 244 bits aléatoires, hexadécimal)
+pub fn generate_token() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+/// Calcule l'empreinte SHA-256 (hex) d'un jeton, seule forme stockée en base
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Extrait le jeton de l'en-tête `Authorization: Bearer <jeton>`
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+/// Compare un secret présenté au secret attendu sans fuite temporelle
+///
+/// Les deux valeurs sont hachées avant comparaison : la durée ne dépend ni de
+/// la longueur ni du préfixe commun.
+pub fn secret_matches(presented: &str, expected: &str) -> bool {
+    let presented = Sha256::digest(presented.as_bytes());
+    let expected = Sha256::digest(expected.as_bytes());
+    presented
+        .iter()
+        .zip(expected.iter())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+/// Causes de rejet d'une signature
+#[derive(Debug, thiserror::Error)]
+pub enum SignatureError {
+    #[error("Missing signature headers")]
+    Missing,
+
+    #[error("Invalid signature timestamp")]
+    InvalidTimestamp,
+
+    #[error("Signature timestamp expired")]
+    Expired,
+
+    #[error("Invalid signature")]
+    Invalid,
+}
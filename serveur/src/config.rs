@@ -3,16 +3,35 @@
 //! Charge et valide la configuration depuis le fichier `config.toml`.
 //! Utilise serde pour désérialiser automatiquement le TOML en structures Rust.
 
-use serde::Deserialize;
+use chrono::NaiveTime;
+use chrono_tz::Tz;
+use ipnet::IpNet;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
 use std::fs;
+use std::net::IpAddr;
 use std::path::{Path, PathBuf};
 
+use crate::models::Action;
+
 /// Configuration complète du serveur
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     pub server: ServerConfig,
     pub database: DatabaseConfig,
     pub security: SecurityConfig,
+    /// Limitation de débit de l'ingestion (section `[limits]`, absente = valeurs par défaut)
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    /// Rotation quotidienne (section `[rotation]`, absente = valeurs par défaut)
+    #[serde(default)]
+    pub rotation: RotationConfig,
+    /// Expiration des sessions (section `[sessions]`, absente = désactivée)
+    #[serde(default)]
+    pub sessions: SessionsConfig,
+    /// Écart d'horloge des machines (section `[clock]`, absente = mesure sans contrôle)
+    #[serde(default)]
+    pub clock: ClockConfig,
     #[allow(dead_code)]  // Logging prévu pour évolutions futures
     pub logging: LoggingConfig,
 }
@@ -24,6 +43,65 @@ pub struct ServerConfig {
     pub host: String,
     /// Port d'écoute (ex: 3000)
     pub port: u16,
+    /// Nombre maximum d'événements acceptés par POST /api/v1/events/batch
+    #[serde(default = "default_max_batch_size")]
+    pub max_batch_size: usize,
+    /// Écoute HTTPS native (section `[server.tls]`, absente = HTTP en clair)
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Proxys/répartiteurs dont les en-têtes X-Forwarded-For et CF-Connecting-IP
+    /// sont pris en compte (adresses ou réseaux CIDR)
+    /// Liste vide = l'adresse IP de la connexion est toujours utilisée
+    #[serde(default)]
+    pub trusted_proxies: Vec<TrustedProxy>,
+}
+
+impl ServerConfig {
+    /// Indique si une adresse appartient à un proxy de confiance
+    pub fn is_trusted_proxy(&self, ip: &IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.trusted_proxies.iter().any(|proxy| proxy.0.contains(&ip))
+    }
+}
+
+/// Adresse ou réseau d'un proxy de confiance ("10.0.0.5", "10.0.0.0/8", "fd00::/8")
+#[derive(Debug, Clone)]
+pub struct TrustedProxy(pub IpNet);
+
+impl<'de> Deserialize<'de> for TrustedProxy {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value
+            .parse::<IpNet>()
+            .or_else(|_| value.parse::<IpAddr>().map(IpNet::from))
+            .map(TrustedProxy)
+            .map_err(|_| serde::de::Error::custom(format!("proxy de confiance invalide: {}", value)))
+    }
+}
+
+fn default_max_batch_size() -> usize {
+    500
+}
+
+/// Configuration TLS du serveur (section `[server.tls]`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct TlsConfig {
+    /// Certificat serveur au format PEM (chaîne complète : certificat puis intermédiaires)
+    pub cert_path: String,
+    /// Clé privée au format PEM (PKCS#8, PKCS#1 ou SEC1)
+    pub key_path: String,
+    /// Autorités (PEM) des certificats clients acceptés
+    /// Présent = mTLS obligatoire : seuls les postes provisionnés peuvent se connecter
+    #[serde(default)]
+    pub client_ca_path: Option<String>,
+    /// Intervalle (secondes) de vérification des fichiers pour rechargement à chaud
+    /// 0 = rechargement désactivé
+    #[serde(default = "default_tls_reload_interval_secs")]
+    pub reload_interval_secs: u64,
+}
+
+fn default_tls_reload_interval_secs() -> u64 {
+    60
 }
 
 /// Configuration de la base de données SQLite
@@ -39,6 +117,9 @@ pub struct DatabaseConfig {
     pub pragma_busy_timeout: u32,
     /// Taille du cache (nombre de pages)
     pub pragma_cache_size: i32,
+    /// Conservation de events_history (section `[database.retention]`, absente = aucune purge)
+    #[serde(default)]
+    pub retention: RetentionConfig,
 }
 
 impl DatabaseConfig {
@@ -50,6 +131,18 @@ impl DatabaseConfig {
         PathBuf::from(&self.path)
     }
 
+    /// Configuration d'une base de travail (restauration d'archives), hors config.toml
+    pub fn scratch(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            pragma_journal_mode: "WAL".to_string(),
+            pragma_synchronous: "NORMAL".to_string(),
+            pragma_busy_timeout: 30000,
+            pragma_cache_size: 10000,
+            retention: RetentionConfig::default(),
+        }
+    }
+
     /// Retourne l'URL SQLite complète pour SQLx
     ///
     /// Format : "sqlite://chemin/vers/base.db" ou "sqlite:chemin/vers/base.db"
@@ -62,13 +155,361 @@ impl DatabaseConfig {
     }
 }
 
+/// Politique de conservation de events_history, orphan_repairs et host_clocks (section `[database.retention]`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct RetentionConfig {
+    /// Durée de conservation en jours par code action (ex : { C = 365, D = 365, M = 90 })
+    /// Action absente = conservée indéfiniment
+    #[serde(default)]
+    pub days: BTreeMap<Action, u32>,
+    /// Lignes supprimées par transaction (verrou d'écriture court)
+    #[serde(default = "default_retention_batch_size")]
+    pub batch_size: u32,
+    /// Intervalle entre deux purges (heures), la première ayant lieu au démarrage
+    #[serde(default = "default_retention_interval_hours")]
+    pub interval_hours: u64,
+    /// Répertoire des archives Parquet (zstd) écrites avant suppression
+    /// Absent = les lignes purgées ne sont pas archivées
+    #[serde(default)]
+    pub archive_dir: Option<String>,
+    /// Durée de conservation (jours) du journal orphan_repairs, selon `repaired_at`
+    /// Absente = conservé indéfiniment
+    #[serde(default)]
+    pub orphan_repairs_days: Option<u32>,
+    /// Durée (jours) après la dernière mesure au-delà de laquelle une machine
+    /// est supprimée de host_clocks. Absente = conservée indéfiniment
+    #[serde(default)]
+    pub host_clocks_days: Option<u32>,
+}
+
+impl RetentionConfig {
+    /// Indique si au moins une règle de conservation est configurée
+    pub fn has_rules(&self) -> bool {
+        !self.days.is_empty() || self.orphan_repairs_days.is_some() || self.host_clocks_days.is_some()
+    }
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            days: BTreeMap::new(),
+            batch_size: default_retention_batch_size(),
+            interval_hours: default_retention_interval_hours(),
+            archive_dir: None,
+            orphan_repairs_days: None,
+            host_clocks_days: None,
+        }
+    }
+}
+
+fn default_retention_batch_size() -> u32 {
+    1000
+}
+
+fn default_retention_interval_hours() -> u64 {
+    24
+}
+
 /// Configuration de sécurité
 #[derive(Debug, Clone, Deserialize)]
 pub struct SecurityConfig {
-    /// User-Agent attendu des clients
+    /// User-Agent attendu des clients (vérifié uniquement si aucun secret HMAC n'est configuré)
     pub expected_user_agent: String,
-    /// Actions autorisées (C, D, M)
-    pub valid_actions: Vec<String>,
+    /// Actions acceptées (codes de `models::Action`, les autres sont rejetées)
+    pub valid_actions: Vec<Action>,
+    /// Secrets partagés acceptés pour la signature HMAC-SHA256 des requêtes
+    ///
+    /// Plusieurs secrets peuvent être valides simultanément pour permettre la
+    /// rotation : ajouter le nouveau, redéployer les clients, retirer l'ancien.
+    /// Liste vide = signature désactivée (contrôle User-Agent seul).
+    #[serde(default)]
+    pub hmac_secrets: Vec<String>,
+    /// Écart maximal (en secondes) toléré entre l'horodatage signé et l'heure serveur
+    #[serde(default = "default_signature_max_age_secs")]
+    pub signature_max_age_secs: u64,
+    /// Exige un jeton d'enrôlement valide, correspondant au hostname déclaré
+    #[serde(default)]
+    pub require_host_token: bool,
+    /// Clé à présenter pour enrôler une machine (POST /api/v1/enroll)
+    /// Absente = enrôlement désactivé
+    #[serde(default)]
+    pub enrollment_key: Option<String>,
+    /// Jeton d'accès aux endpoints d'administration (/api/v1/admin/*)
+    /// Absent = administration désactivée
+    #[serde(default)]
+    pub admin_token: Option<String>,
+}
+
+fn default_signature_max_age_secs() -> u64 {
+    300
+}
+
+impl SecurityConfig {
+    /// Indique si les requêtes doivent être signées
+    pub fn signature_required(&self) -> bool {
+        !self.hmac_secrets.is_empty()
+    }
+}
+
+/// Limitation de débit de POST /api/v1/events (section `[limits]`)
+///
+/// Seaux à jetons : `*_burst` requêtes consécutives au maximum, puis
+/// `*_per_minute` requêtes par minute. `*_per_minute = 0` désactive la limite.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LimitsConfig {
+    /// Requêtes par minute par adresse IP source
+    #[serde(default = "default_per_ip_per_minute")]
+    pub per_ip_per_minute: u32,
+    /// Rafale maximale par adresse IP source
+    #[serde(default = "default_per_ip_burst")]
+    pub per_ip_burst: u32,
+    /// Requêtes par minute par couple `username@hostname`
+    #[serde(default = "default_per_host_per_minute")]
+    pub per_host_per_minute: u32,
+    /// Rafale maximale par couple `username@hostname`
+    #[serde(default = "default_per_host_burst")]
+    pub per_host_burst: u32,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            per_ip_per_minute: default_per_ip_per_minute(),
+            per_ip_burst: default_per_ip_burst(),
+            per_host_per_minute: default_per_host_per_minute(),
+            per_host_burst: default_per_host_burst(),
+        }
+    }
+}
+
+fn default_per_ip_per_minute() -> u32 {
+    120
+}
+
+fn default_per_ip_burst() -> u32 {
+    60
+}
+
+fn default_per_host_per_minute() -> u32 {
+    6
+}
+
+fn default_per_host_burst() -> u32 {
+    5
+}
+
+/// Rotation quotidienne events_today → events_history (section `[rotation]`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct RotationConfig {
+    /// Rotation planifiée active (sinon uniquement via POST /api/v1/admin/rotate)
+    #[serde(default = "default_rotation_enabled")]
+    pub enabled: bool,
+    /// Heure de la rotation ("HH:MM") dans le fuseau `timezone`
+    #[serde(default = "default_rotation_time")]
+    pub time: NaiveTime,
+    /// Fuseau horaire IANA de `time` (ex : "Europe/Paris", "UTC")
+    #[serde(default = "default_rotation_timezone")]
+    pub timezone: Tz,
+    /// Fenêtre (heures) des déconnexions orphelines réappariées à la rotation
+    /// (0 = sans limite). Les plus anciennes restent orphelines.
+    #[serde(default = "default_orphan_window_hours")]
+    pub orphan_window_hours: u32,
+}
+
+impl Default for RotationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_rotation_enabled(),
+            time: default_rotation_time(),
+            timezone: default_rotation_timezone(),
+            orphan_window_hours: default_orphan_window_hours(),
+        }
+    }
+}
+
+fn default_rotation_enabled() -> bool {
+    true
+}
+
+fn default_rotation_time() -> NaiveTime {
+    NaiveTime::from_hms_opt(1, 0, 0).unwrap_or_default()
+}
+
+fn default_rotation_timezone() -> Tz {
+    Tz::Europe__Paris
+}
+
+fn default_orphan_window_hours() -> u32 {
+    72
+}
+
+/// Expiration des sessions ouvertes et politique des machines (section `[sessions]`)
+///
+/// Une session dont le dernier signal de présence (action 'H') est plus ancien
+/// que `heartbeat_timeout_minutes` est fermée (`timed_out`). Les sessions sans
+/// aucun signal (client qui n'en envoie pas) ne sont pas concernées.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SessionsConfig {
+    /// Délai sans signal de présence avant fermeture (minutes, 0 = désactivé)
+    #[serde(default)]
+    pub heartbeat_timeout_minutes: u32,
+    /// Intervalle entre deux recherches de sessions expirées (secondes)
+    #[serde(default = "default_sessions_check_interval_secs")]
+    pub check_interval_secs: u64,
+    /// Durée maximale d'une session (heures, 0 = illimitée), sauf groupe qui la redéfinit
+    #[serde(default)]
+    pub max_session_hours: u32,
+    /// Politique des machines n'appartenant à aucun groupe
+    #[serde(default)]
+    pub default_seat: SeatPolicy,
+    /// Groupes de machines (`[[sessions.host_groups]]`), le premier qui correspond s'applique
+    #[serde(default)]
+    pub host_groups: Vec<HostGroup>,
+}
+
+impl Default for SessionsConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat_timeout_minutes: 0,
+            check_interval_secs: default_sessions_check_interval_secs(),
+            max_session_hours: 0,
+            default_seat: SeatPolicy::default(),
+            host_groups: Vec::new(),
+        }
+    }
+}
+
+impl SessionsConfig {
+    /// Groupe d'une machine (premier groupe dont un motif correspond)
+    pub fn host_group(&self, hostname: &str) -> Option<&HostGroup> {
+        self.host_groups.iter().find(|group| group.matches(hostname))
+    }
+
+    /// Politique de poste d'une machine (celle de son groupe, sinon `default_seat`)
+    pub fn seat_policy(&self, hostname: &str) -> SeatPolicy {
+        self.host_group(hostname).map_or(self.default_seat, |group| group.seat)
+    }
+
+    /// Durée maximale d'une session sur une machine (celle de son groupe, sinon
+    /// `max_session_hours`), `None` si illimitée
+    pub fn max_session_duration(&self, hostname: &str) -> Option<chrono::Duration> {
+        let hours = self
+            .host_group(hostname)
+            .and_then(|group| group.max_session_hours)
+            .unwrap_or(self.max_session_hours);
+        (hours > 0).then(|| chrono::Duration::hours(i64::from(hours)))
+    }
+
+    /// Plus courte durée maximale configurée (globale ou d'un groupe), `None` si aucune
+    pub fn shortest_max_session(&self) -> Option<chrono::Duration> {
+        std::iter::once(self.max_session_hours)
+            .chain(self.host_groups.iter().filter_map(|group| group.max_session_hours))
+            .filter(|hours| *hours > 0)
+            .min()
+            .map(|hours| chrono::Duration::hours(i64::from(hours)))
+    }
+}
+
+/// Politique de poste d'une machine
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeatPolicy {
+    /// Plusieurs sessions simultanées possibles (serveur RDS, changement rapide d'utilisateur)
+    #[default]
+    MultiSeat,
+    /// Un seul utilisateur à la fois (poste de salle de TP) : une connexion
+    /// ferme les sessions ouvertes des autres utilisateurs
+    SingleSeat,
+}
+
+/// Groupe de machines (section `[[sessions.host_groups]]`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct HostGroup {
+    /// Nom du groupe (journaux)
+    pub name: String,
+    /// Motifs de hostname, insensibles à la casse (`*` : toute suite de
+    /// caractères, `?` : un caractère), ex : ["TP-*", "LABO-??"]
+    pub hosts: Vec<String>,
+    /// Politique de poste du groupe
+    #[serde(default)]
+    pub seat: SeatPolicy,
+    /// Durée maximale d'une session (heures, 0 = illimitée)
+    /// Absente = `max_session_hours` de la section `[sessions]`
+    #[serde(default)]
+    pub max_session_hours: Option<u32>,
+}
+
+impl HostGroup {
+    /// Indique si une machine appartient au groupe
+    pub fn matches(&self, hostname: &str) -> bool {
+        self.hosts.iter().any(|pattern| wildcard_match(pattern, hostname))
+    }
+}
+
+/// Correspondance d'un hostname avec un motif `*`/`?` (insensible à la casse ASCII)
+fn wildcard_match(pattern: &str, hostname: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let hostname: Vec<char> = hostname.chars().map(|c| c.to_ascii_lowercase()).collect();
+
+    // Parcours glouton avec retour au dernier '*' rencontré
+    let (mut p, mut h) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while h < hostname.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == hostname[h]) {
+            p += 1;
+            h += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, h));
+            p += 1;
+        } else if let Some((star_p, star_h)) = star {
+            p = star_p + 1;
+            h = star_h + 1;
+            star = Some((star_p, star_h + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn default_sessions_check_interval_secs() -> u64 {
+    60
+}
+
+/// Écart d'horloge des machines (section `[clock]`)
+///
+/// L'écart (heure d'envoi du client - heure de réception) est mesuré sur
+/// chaque requête qui porte l'heure d'envoi et conservé par machine. Au-delà
+/// de `max_skew_secs`, `on_skew` s'applique avec l'écart de la machine.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ClockConfig {
+    /// Écart toléré (secondes, 0 = aucun contrôle, l'écart reste mesuré)
+    #[serde(default)]
+    pub max_skew_secs: u64,
+    /// Traitement d'un événement hors tolérance
+    #[serde(default)]
+    pub on_skew: SkewPolicy,
+}
+
+impl ClockConfig {
+    /// Indique si un écart (secondes) dépasse la tolérance
+    pub fn out_of_tolerance(&self, skew_secs: i64) -> bool {
+        self.max_skew_secs > 0 && skew_secs.unsigned_abs() > self.max_skew_secs
+    }
+}
+
+/// Traitement d'un événement dont l'horloge est hors tolérance
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkewPolicy {
+    /// Événement enregistré tel quel, écart conservé (`clock_skew_secs`)
+    #[default]
+    Flag,
+    /// Événement refusé (422 CLOCK_SKEW)
+    Reject,
+    /// Timestamp recalculé avec l'écart, timestamp d'origine conservé
+    /// (`client_timestamp`)
+    Correct,
 }
 
 /// Configuration du logging (prévu pour personnalisation future)
@@ -103,20 +544,86 @@ impl Config {
         format!("{}:{}", self.server.host, self.server.port)
     }
 
-    /// Valide que les actions configurées sont valides
+    /// Valide la cohérence de la configuration
+    ///
+    /// Les codes d'action (`valid_actions`, clés de `retention.days`) sont
+    /// déjà vérifiés à la lecture du fichier (type `Action`).
     pub fn validate(&self) -> Result<(), ConfigError> {
-        // Vérifier que les actions sont valides
-        for action in &self.security.valid_actions {
-            if !["C", "D", "M"].contains(&action.as_str()) {
-                return Err(ConfigError::InvalidAction(action.clone()));
-            }
-        }
-
         // Vérifier que le port est dans une plage valide
         if self.server.port == 0 {
             return Err(ConfigError::InvalidPort);
         }
 
+        // Un secret vide rendrait la signature triviale à forger
+        if self.security.hmac_secrets.iter().any(|secret| secret.is_empty()) {
+            return Err(ConfigError::EmptyHmacSecret);
+        }
+
+        // Une clé vide donnerait un accès sans authentification
+        if self.security.enrollment_key.as_deref() == Some("")
+            || self.security.admin_token.as_deref() == Some("")
+        {
+            return Err(ConfigError::EmptyAccessKey);
+        }
+
+        // Les fichiers TLS doivent exister au démarrage
+        if let Some(tls) = &self.server.tls {
+            let paths = [Some(&tls.cert_path), Some(&tls.key_path), tls.client_ca_path.as_ref()];
+            for path in paths.into_iter().flatten() {
+                if !Path::new(path).is_file() {
+                    return Err(ConfigError::TlsFileNotFound(path.clone()));
+                }
+            }
+        }
+
+        // Un lot vide ne permettrait aucun envoi groupé
+        if self.server.max_batch_size == 0 {
+            return Err(ConfigError::InvalidBatchSize);
+        }
+
+        // Règles de conservation : durées et lots non nuls
+        let retention = &self.database.retention;
+        for (action, days) in &retention.days {
+            if *days == 0 {
+                return Err(ConfigError::InvalidRetention(format!("durée nulle pour l'action {}", action)));
+            }
+        }
+        if retention.orphan_repairs_days == Some(0) || retention.host_clocks_days == Some(0) {
+            return Err(ConfigError::InvalidRetention(
+                "orphan_repairs_days et host_clocks_days doivent être supérieurs à 0".to_string(),
+            ));
+        }
+        if retention.batch_size == 0 || retention.interval_hours == 0 {
+            return Err(ConfigError::InvalidRetention(
+                "batch_size et interval_hours doivent être supérieurs à 0".to_string(),
+            ));
+        }
+
+        // Un intervalle nul ferait tourner la recherche de sessions expirées en boucle
+        if self.sessions.check_interval_secs == 0 {
+            return Err(ConfigError::InvalidSessions(
+                "check_interval_secs doit être supérieur à 0".to_string(),
+            ));
+        }
+
+        // Un groupe sans motif ne contiendrait aucune machine
+        for group in &self.sessions.host_groups {
+            if group.hosts.is_empty() || group.hosts.iter().any(|pattern| pattern.is_empty()) {
+                return Err(ConfigError::InvalidSessions(format!(
+                    "motif de hostname absent ou vide dans le groupe {}",
+                    group.name
+                )));
+            }
+        }
+
+        // Une rafale nulle bloquerait toute requête d'une limite active
+        let limits = &self.limits;
+        if (limits.per_ip_per_minute > 0 && limits.per_ip_burst == 0)
+            || (limits.per_host_per_minute > 0 && limits.per_host_burst == 0)
+        {
+            return Err(ConfigError::InvalidRateLimit);
+        }
+
         Ok(())
     }
 }
@@ -130,9 +637,151 @@ pub enum ConfigError {
     #[error("Erreur de parsing TOML: {0}")]
     Parse(String),
 
-    #[error("Action invalide: {0}")]
-    InvalidAction(String),
-
     #[error("Port invalide")]
     InvalidPort,
+
+    #[error("max_batch_size doit être supérieur à 0")]
+    InvalidBatchSize,
+
+    #[error("limits.per_ip_burst et limits.per_host_burst doivent être supérieurs à 0")]
+    InvalidRateLimit,
+
+    #[error("Secret HMAC vide dans security.hmac_secrets")]
+    EmptyHmacSecret,
+
+    #[error("security.enrollment_key et security.admin_token ne peuvent pas être vides")]
+    EmptyAccessKey,
+
+    #[error("Fichier TLS introuvable: {0}")]
+    TlsFileNotFound(String),
+
+    #[error("database.retention invalide: {0}")]
+    InvalidRetention(String),
+
+    #[error("sessions invalide: {0}")]
+    InvalidSessions(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sessions(content: &str) -> SessionsConfig {
+        toml::from_str(content).expect("section [sessions] de test")
+    }
+
+    #[test]
+    fn wildcard_matches_hostnames_case_insensitively() {
+        assert!(wildcard_match("TP-*", "tp-101"));
+        assert!(wildcard_match("TP-*", "TP-"));
+        assert!(wildcard_match("LABO-??", "labo-a1"));
+        assert!(!wildcard_match("LABO-??", "LABO-A"));
+        assert!(!wildcard_match("LABO-??", "LABO-A12"));
+        assert!(wildcard_match("*-PROF", "SALLE1-PROF"));
+        assert!(wildcard_match("S*-*-PC", "S1-ETAGE2-PC"));
+        assert!(!wildcard_match("S*-*-PC", "S1-ETAGE2-PC2"));
+        assert!(wildcard_match("*", ""));
+        assert!(!wildcard_match("TP-01", "TP-010"));
+    }
+
+    #[test]
+    fn first_matching_group_sets_seat_policy() {
+        let config = sessions(
+            r#"
+            default_seat = "multi_seat"
+
+            [[host_groups]]
+            name = "profs"
+            hosts = ["TP-PROF*"]
+            seat = "multi_seat"
+
+            [[host_groups]]
+            name = "salles-tp"
+            hosts = ["TP-*", "LABO-??"]
+            seat = "single_seat"
+            "#,
+        );
+        assert_eq!(config.seat_policy("TP-PROF1"), SeatPolicy::MultiSeat);
+        assert_eq!(config.seat_policy("tp-12"), SeatPolicy::SingleSeat);
+        assert_eq!(config.seat_policy("LABO-B2"), SeatPolicy::SingleSeat);
+        assert_eq!(config.seat_policy("RDS-01"), SeatPolicy::MultiSeat);
+        assert_eq!(config.host_group("LABO-B2").map(|group| group.name.as_str()), Some("salles-tp"));
+        assert!(config.host_group("RDS-01").is_none());
+    }
+
+    #[test]
+    fn group_max_session_hours_overrides_global_value() {
+        let config = sessions(
+            r#"
+            max_session_hours = 12
+
+            [[host_groups]]
+            name = "salles-tp"
+            hosts = ["TP-*"]
+            max_session_hours = 4
+
+            [[host_groups]]
+            name = "serveurs"
+            hosts = ["RDS-*"]
+            max_session_hours = 0
+
+            [[host_groups]]
+            name = "accueil"
+            hosts = ["ACCUEIL-*"]
+            "#,
+        );
+        assert_eq!(config.max_session_duration("TP-01"), Some(chrono::Duration::hours(4)));
+        assert_eq!(config.max_session_duration("RDS-01"), None);
+        assert_eq!(config.max_session_duration("ACCUEIL-01"), Some(chrono::Duration::hours(12)));
+        assert_eq!(config.max_session_duration("PC-01"), Some(chrono::Duration::hours(12)));
+        assert_eq!(config.shortest_max_session(), Some(chrono::Duration::hours(4)));
+    }
+
+    #[test]
+    fn unlimited_sessions_have_no_shortest_max_duration() {
+        assert_eq!(sessions("").shortest_max_session(), None);
+
+        let config = sessions(
+            r#"
+            [[host_groups]]
+            name = "salles-tp"
+            hosts = ["TP-*"]
+            max_session_hours = 3
+            "#,
+        );
+        assert_eq!(config.max_session_duration("PC-01"), None);
+        assert_eq!(config.shortest_max_session(), Some(chrono::Duration::hours(3)));
+    }
+
+    #[test]
+    fn clock_tolerance_is_exclusive_and_symmetric() {
+        let clock = ClockConfig { max_skew_secs: 300, on_skew: SkewPolicy::Flag };
+        assert!(!clock.out_of_tolerance(0));
+        assert!(!clock.out_of_tolerance(300));
+        assert!(!clock.out_of_tolerance(-300));
+        assert!(clock.out_of_tolerance(301));
+        assert!(clock.out_of_tolerance(-301));
+        assert!(clock.out_of_tolerance(i64::MIN));
+    }
+
+    #[test]
+    fn clock_tolerance_zero_disables_the_check() {
+        let clock = ClockConfig::default();
+        assert!(!clock.out_of_tolerance(0));
+        assert!(!clock.out_of_tolerance(i64::MAX));
+        assert!(!clock.out_of_tolerance(i64::MIN));
+    }
+
+    #[test]
+    fn clock_section_parses_policies() {
+        let clock: ClockConfig = toml::from_str("max_skew_secs = 120\non_skew = \"correct\"").unwrap();
+        assert_eq!(clock.max_skew_secs, 120);
+        assert_eq!(clock.on_skew, SkewPolicy::Correct);
+
+        let clock: ClockConfig = toml::from_str("").unwrap();
+        assert_eq!(clock.max_skew_secs, 0);
+        assert_eq!(clock.on_skew, SkewPolicy::Flag);
+
+        assert!(toml::from_str::<ClockConfig>("on_skew = \"ignore\"").is_err());
+    }
 }
@@ -4,14 +4,57 @@
 //! liée aux sessions (fermeture auto, génération UUID, etc.).
 //!
 //! Les requêtes SQL sont centralisées dans le module `queries`.
+//!
+//! Le schéma est défini par les migrations du répertoire `migrations/`,
+//! embarquées dans le binaire et appliquées à l'ouverture de la base.
+
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::time::Instant;
 
-use chrono::Utc;
-use md5;
-use sqlx::{SqlitePool, Row};
-use crate::config::DatabaseConfig;
-use crate::models::{ClientEvent, OpenSession, CurrentSession};
+use chrono::{DateTime, Utc};
+use sqlx::migrate::{MigrateError, Migrator};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{Connection, Row, Sqlite, SqliteConnection, SqlitePool, Transaction};
+use uuid::Uuid;
+use crate::config::{DatabaseConfig, SessionsConfig};
+use crate::models::{
+    Action, ArchivedEvent, ClientEvent, CloseReason, ClosedSession, CurrentSession, HostClock, HostSession, HostToken,
+    InsertedEvent, OpenSession, OrphanHost, OrphanPairing, OrphanRepair, OrphanRepairTrigger, OrphanStats,
+    OverdueSession, Purge, Rotation, RotationTrigger, StaleSession, StoredEvent,
+};
 use crate::queries;  // Import du module de requêtes SQL
 
+/// Migrations du schéma (répertoire `migrations/`, embarquées à la compilation)
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Format de `CURRENT_TIMESTAMP` (UTC), utilisé pour `archived_at`, `created_at` et `purged_at`
+pub const SQLITE_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Comptages d'une rotation réussie
+struct RotationCounts {
+    today_before: i64,
+    history_before: i64,
+    today_after: i64,
+    history_after: i64,
+    open_sessions: i64,
+}
+
+/// Déconnexion insérée par le serveur (connexion suivante, poste à place unique,
+/// session expirée ou trop longue)
+struct ServerDisconnect<'a> {
+    username: &'a str,
+    timestamp: &'a str,
+    hostname: Option<&'a str>,
+    source_ip: Option<&'a str>,
+    os_name: Option<&'a str>,
+    os_version: Option<&'a str>,
+    kernel_version: Option<&'a str>,
+    session_uuid: &'a str,
+    /// Motif de fermeture de la session, conservé sur la ligne de l'événement
+    reason: CloseReason,
+}
+
 /// Gestionnaire de base de données
 #[derive(Clone)]
 pub struct Database {
@@ -23,15 +66,22 @@ impl Database {
     ///
     /// Utilise l'URL SQLite générée par `DatabaseConfig::sqlite_url()` qui gère
     /// automatiquement les séparateurs de chemin Windows (\) et Unix (/).
+    /// Le fichier (et son répertoire) est créé s'il n'existe pas, puis les
+    /// migrations en attente sont appliquées.
     ///
     /// # Arguments
     /// * `config` - Configuration de la base de données
     ///
     /// # Erreurs
-    /// Retourne une erreur si la connexion échoue
+    /// Retourne une erreur si la connexion ou une migration échoue, ou si la
+    /// base a été migrée par une version plus récente du serveur
     pub async fn new(config: &DatabaseConfig) -> Result<Self, sqlx::Error> {
-        let sqlite_url = config.sqlite_url();
-        let pool = SqlitePool::connect(&sqlite_url).await?;
+        if let Some(dir) = config.path_buf().parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let options = SqliteConnectOptions::from_str(&config.sqlite_url())?.create_if_missing(true);
+        let pool = SqlitePool::connect_with(options).await?;
 
         // Configuration des PRAGMA SQLite
         let pragmas = vec![
@@ -45,54 +95,118 @@ impl Database {
             sqlx::query(&pragma).execute(&pool).await?;
         }
 
+        Self::migrate(&pool).await?;
+
         Ok(Self { pool })
     }
 
-    /// Génère un identifiant de session unique
+    /// Applique les migrations embarquées qui ne l'ont pas encore été
+    ///
+    /// Une base contenant une migration inconnue de ce binaire (serveur plus
+    /// récent puis retour arrière) est refusée : le code ne connaît pas son schéma.
+    async fn migrate(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        MIGRATOR.run(pool).await.map_err(|e| {
+            if let MigrateError::VersionMissing(version) = e {
+                tracing::error!(
+                    "Schéma de la base plus récent que ce serveur (migration {} inconnue) : mettre à jour le binaire",
+                    version
+                );
+            }
+            sqlx::Error::from(e)
+        })?;
+
+        let version = MIGRATOR.iter().map(|m| m.version).max().unwrap_or(0);
+        tracing::info!("✓ Schéma de la base à jour (version {})", version);
+        Ok(())
+    }
+
+    /// Ouvre une transaction sur le pool
+    ///
+    /// Les requêtes liées à un événement (recherche de session, déconnexion
+    /// automatique, insertion) s'exécutent sur la connexion de la transaction
+    /// pour être validées ensemble. Le traitement par lot ouvre un point de
+    /// sauvegarde par élément via [`Database::savepoint`].
+    ///
+    /// `BEGIN IMMEDIATE` prend le verrou d'écriture dès l'ouverture : deux
+    /// connexions simultanées du même utilisateur sont traitées l'une après
+    /// l'autre (la seconde voit la session créée par la première) au lieu de
+    /// lire toutes deux « aucune session ouverte ».
+    pub async fn begin(&self) -> Result<Transaction<'static, Sqlite>, sqlx::Error> {
+        self.pool.begin_with("BEGIN IMMEDIATE").await
+    }
+
+    /// Ouvre un point de sauvegarde (SAVEPOINT) dans une transaction existante
     ///
-    /// Format : username@hostname@hash6
-    /// Hash = MD5(username + hostname + date + timestamp_nanos)
+    /// Permet d'annuler un seul élément d'un lot sans perdre les autres.
+    pub async fn savepoint(
+        conn: &mut SqliteConnection,
+    ) -> Result<Transaction<'_, Sqlite>, sqlx::Error> {
+        conn.begin().await
+    }
+
+    /// Génère un identifiant de session unique (UUIDv7)
+    ///
+    /// Les 48 premiers bits sont l'instant de l'événement (ms) : les identifiants
+    /// sont triés chronologiquement, y compris pour un événement reçu en retard.
+    /// Les 74 bits restants sont aléatoires. L'identifiant ne contient ni
+    /// utilisateur ni machine (colonnes `username` et `hostname`).
     ///
     /// # Arguments
-    /// * `username` - Nom d'utilisateur
-    /// * `hostname` - Nom de la machine
-    /// * `timestamp` - Timestamp ISO 8601
-    pub fn generate_session_id(username: &str, hostname: &str, timestamp: &str) -> String {
-        // Extraire la date (YYYY-MM-DD) - safe slice avec get()
-        let date = timestamp.get(..10).unwrap_or("1970-01-01");
-        
-        // Créer un hash unique avec timestamp nano pour éviter collisions
-        let now_nanos = Utc::now().timestamp_nanos_opt().unwrap_or(0);
-        let data = format!("{}{}{}{}", username, hostname, date, now_nanos);
-        let hash = format!("{:x}", md5::compute(data));
-        
-        // Prendre les 6 premiers caractères du hash - safe slice
-        // MD5 produit toujours 32 caractères hex, donc .get(..6) ne peut jamais échouer
-        let short_hash = hash.get(..6).unwrap_or("000000");
-        
-        format!("{}@{}@{}", username, hostname, short_hash)
-    }
-
-    /// Recherche une session ouverte aujourd'hui pour un utilisateur/machine
+    /// * `timestamp` - Timestamp ISO 8601 de l'événement (heure courante si illisible)
+    pub fn generate_session_id(timestamp: &str) -> String {
+        let at = DateTime::parse_from_rfc3339(timestamp)
+            .map(|at| at.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now());
+        let seconds = u64::try_from(at.timestamp()).unwrap_or(0);
+        let uuid_timestamp = uuid::Timestamp::from_unix(uuid::NoContext, seconds, at.timestamp_subsec_nanos());
+
+        Uuid::new_v7(uuid_timestamp).to_string()
+    }
+
+    /// Recherche un événement déjà enregistré par son identifiant client
+    ///
+    /// # Arguments
+    /// * `conn` - Connexion (ou transaction) sur laquelle exécuter la requête
+    /// * `event_id` - UUID généré par le client
+    ///
+    /// # Retourne
+    /// `Some(StoredEvent)` si l'événement a déjà été enregistré, `None` sinon
+    pub async fn find_event_by_event_id(
+        conn: &mut SqliteConnection,
+        event_id: &uuid::Uuid,
+    ) -> Result<Option<StoredEvent>, sqlx::Error> {
+        let event_id = event_id.to_string();
+        sqlx::query_as::<_, StoredEvent>(queries::SQL_FIND_EVENT_BY_EVENT_ID)
+            .bind(&event_id)
+            .bind(&event_id)
+            .fetch_optional(&mut *conn)
+            .await
+    }
+
+    /// Recherche une session ouverte dans les 24 heures précédant `timestamp`
+    ///
+    /// Seules les sessions ouvertes au plus tard à `timestamp` sont considérées.
     ///
     /// # Arguments
+    /// * `conn` - Connexion (ou transaction) sur laquelle exécuter la requête
     /// * `username` - Nom d'utilisateur
     /// * `hostname` - Nom de la machine
     /// * `timestamp` - Timestamp de référence
     ///
     /// # Retourne
     /// `Some(OpenSession)` si une session ouverte existe, `None` sinon
-    pub async fn find_open_session_today(
-        &self,
+    pub async fn find_recent_open_session(
+        conn: &mut SqliteConnection,
         username: &str,
         hostname: &str,
         timestamp: &str,
     ) -> Result<Option<OpenSession>, sqlx::Error> {
-        let result = sqlx::query_as::<_, OpenSession>(queries::SQL_FIND_OPEN_SESSION_TODAY)
+        let result = sqlx::query_as::<_, OpenSession>(queries::SQL_FIND_RECENT_OPEN_SESSION)
             .bind(username)
             .bind(hostname)
             .bind(timestamp)
-            .fetch_optional(&self.pool)
+            .bind(timestamp)
+            .fetch_optional(&mut *conn)
             .await?;
 
         Ok(result)
@@ -101,73 +215,647 @@ impl Database {
     /// Trouve la dernière session ouverte (pour associer une déconnexion)
     ///
     /// # Arguments
+    /// * `conn` - Connexion (ou transaction) sur laquelle exécuter la requête
     /// * `username` - Nom d'utilisateur
     /// * `hostname` - Nom de la machine
+    /// * `timestamp` - Timestamp de la déconnexion (les sessions ouvertes après sont ignorées)
     ///
     /// # Retourne
     /// `Some(session_uuid)` si trouvée, `None` sinon
     pub async fn find_last_open_session(
-        &self,
+        conn: &mut SqliteConnection,
         username: &str,
         hostname: &str,
+        timestamp: &str,
     ) -> Result<Option<String>, sqlx::Error> {
         let result = sqlx::query(queries::SQL_FIND_LAST_OPEN_SESSION)
             .bind(username)
             .bind(hostname)
-            .fetch_optional(&self.pool)
+            .bind(timestamp)
+            .fetch_optional(&mut *conn)
             .await?;
 
         // Utilisation de try_get au lieu de get pour éviter panic si colonne manquante
         Ok(result.and_then(|row| row.try_get::<String, _>("session_uuid").ok()))
     }
 
+    /// Trouve la session en cours à `timestamp` mais fermée après cet instant
+    ///
+    /// Utilisé pour replacer un événement reçu en retard (voir
+    /// `queries::SQL_FIND_SESSION_CLOSED_AFTER`).
+    pub async fn find_session_closed_after(
+        conn: &mut SqliteConnection,
+        username: &str,
+        hostname: &str,
+        timestamp: &str,
+    ) -> Result<Option<ClosedSession>, sqlx::Error> {
+        sqlx::query_as::<_, ClosedSession>(queries::SQL_FIND_SESSION_CLOSED_AFTER)
+            .bind(username)
+            .bind(hostname)
+            .bind(timestamp)
+            .bind(timestamp)
+            .fetch_optional(&mut *conn)
+            .await
+    }
+
+    /// Trouve la dernière session expirée (`timed_out`) avant `timestamp`
+    ///
+    /// Utilisé pour une déconnexion réelle reçue après l'expiration (voir
+    /// `queries::SQL_FIND_TIMED_OUT_SESSION_BEFORE`).
+    pub async fn find_timed_out_session_before(
+        conn: &mut SqliteConnection,
+        username: &str,
+        hostname: &str,
+        timestamp: &str,
+    ) -> Result<Option<ClosedSession>, sqlx::Error> {
+        sqlx::query_as::<_, ClosedSession>(queries::SQL_FIND_TIMED_OUT_SESSION_BEFORE)
+            .bind(username)
+            .bind(hostname)
+            .bind(timestamp)
+            .bind(timestamp)
+            .fetch_optional(&mut *conn)
+            .await
+    }
+
+    /// Timestamp de la première connexion de l'utilisateur sur la machine après `timestamp`
+    pub async fn find_next_connection(
+        conn: &mut SqliteConnection,
+        username: &str,
+        hostname: &str,
+        timestamp: &str,
+    ) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar(queries::SQL_FIND_NEXT_CONNECTION)
+            .bind(username)
+            .bind(hostname)
+            .bind(timestamp)
+            .fetch_optional(&mut *conn)
+            .await
+    }
+
+    /// Date de la dernière rotation si `timestamp` lui est antérieur
+    ///
+    /// # Retourne
+    /// `Some(archived_at)` : l'événement appartient à une journée archivée et doit
+    /// être rangé dans events_history ; `None` : events_today
+    pub async fn rotation_after(
+        conn: &mut SqliteConnection,
+        timestamp: &str,
+    ) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar(queries::SQL_FIND_ROTATION_AFTER)
+            .bind(timestamp)
+            .fetch_optional(&mut *conn)
+            .await
+    }
+
+    /// Rattache un événement (`source` : "today" ou "history") à une autre session
+    pub async fn reassign_event(
+        conn: &mut SqliteConnection,
+        source: &str,
+        id: i64,
+        session_uuid: &str,
+    ) -> Result<(), sqlx::Error> {
+        let sql = if source == "history" {
+            queries::SQL_UPDATE_EVENT_SESSION_HISTORY
+        } else {
+            queries::SQL_UPDATE_EVENT_SESSION_TODAY
+        };
+        sqlx::query(sql)
+            .bind(session_uuid)
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Supprime une déconnexion automatique (`source` : "today" ou "history")
+    pub async fn delete_auto_disconnect(
+        conn: &mut SqliteConnection,
+        source: &str,
+        id: i64,
+    ) -> Result<(), sqlx::Error> {
+        let sql = if source == "history" {
+            queries::SQL_DELETE_AUTO_DISCONNECT_HISTORY
+        } else {
+            queries::SQL_DELETE_AUTO_DISCONNECT_TODAY
+        };
+        sqlx::query(sql).bind(id).execute(&mut *conn).await?;
+        Ok(())
+    }
+
+    /// Crée la ligne de session d'une connexion (table sessions)
+    ///
+    /// # Arguments
+    /// * `conn` - Connexion (ou transaction) sur laquelle exécuter la requête
+    /// * `event` - Événement de connexion
+    /// * `session_uuid` - UUID de la nouvelle session
+    /// * `source_ip` - Adresse IP source
+    pub async fn insert_session(
+        conn: &mut SqliteConnection,
+        event: &ClientEvent,
+        session_uuid: &str,
+        source_ip: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(queries::SQL_INSERT_SESSION)
+            .bind(session_uuid)
+            .bind(&event.username)
+            .bind(event.hostname.as_deref())
+            .bind(&event.timestamp)
+            .bind(source_ip)
+            .bind(event.os_info.as_ref().and_then(|os| os.os_name.as_deref()))
+            .bind(event.os_info.as_ref().and_then(|os| os.os_version.as_deref()))
+            .bind(Utc::now().to_rfc3339())
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Enregistre la fin d'une session (table sessions)
+    ///
+    /// Une fin déjà enregistrée est remplacée (événement reçu en retard). Un
+    /// verrouillage en cours prend fin avec la session.
+    ///
+    /// # Arguments
+    /// * `conn` - Connexion (ou transaction) sur laquelle exécuter la requête
+    /// * `session_uuid` - UUID de la session
+    /// * `ended_at` - Timestamp de la déconnexion
+    /// * `end_ip` - Adresse IP de la déconnexion
+    /// * `reason` - Motif de fermeture
+    pub async fn close_session(
+        conn: &mut SqliteConnection,
+        session_uuid: &str,
+        ended_at: &str,
+        end_ip: Option<&str>,
+        reason: CloseReason,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(queries::SQL_UPDATE_CLOSE_SESSION)
+            .bind(ended_at)
+            .bind(ended_at)
+            .bind(end_ip)
+            .bind(reason)
+            .bind(Utc::now().to_rfc3339())
+            .bind(session_uuid)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Enregistre le verrouillage de l'écran d'une session
+    ///
+    /// Sans effet si la session est déjà verrouillée (verrouillage répété) :
+    /// le début d'inactivité reste le premier verrouillage.
+    pub async fn lock_session(
+        conn: &mut SqliteConnection,
+        session_uuid: &str,
+        locked_at: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(queries::SQL_UPDATE_LOCK_SESSION)
+            .bind(locked_at)
+            .bind(Utc::now().to_rfc3339())
+            .bind(session_uuid)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Enregistre le déverrouillage de l'écran d'une session
+    ///
+    /// La durée écoulée depuis le verrouillage s'ajoute à `locked_seconds`.
+    /// Sans effet si la session n'est pas verrouillée.
+    pub async fn unlock_session(
+        conn: &mut SqliteConnection,
+        session_uuid: &str,
+        unlocked_at: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(queries::SQL_UPDATE_UNLOCK_SESSION)
+            .bind(unlocked_at)
+            .bind(Utc::now().to_rfc3339())
+            .bind(session_uuid)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
     /// Insère une déconnexion automatique (pour fermer une session orpheline)
     ///
+    /// La déconnexion est rangée dans events_history si elle précède la
+    /// dernière rotation. La session est fermée (`auto_closed`) dans la table
+    /// sessions.
+    ///
     /// # Arguments
+    /// * `conn` - Connexion (ou transaction) sur laquelle exécuter la requête
     /// * `event` - Événement de connexion qui provoque la fermeture
     /// * `session_uuid` - UUID de la session à fermer
     /// * `source_ip` - Adresse IP source
+    /// * `next_connection` - Timestamp de la connexion suivante (déconnexion 1 seconde avant)
+    ///
+    /// # Retourne
+    /// Le timestamp de la déconnexion insérée
     pub async fn insert_auto_disconnect(
-        &self,
+        conn: &mut SqliteConnection,
         event: &ClientEvent,
         session_uuid: &str,
         source_ip: &str,
-    ) -> Result<(), sqlx::Error> {
-        // Timestamp 1 seconde avant la nouvelle connexion
-        let disconnect_time = chrono::DateTime::parse_from_rfc3339(&event.timestamp)
+        next_connection: &str,
+    ) -> Result<String, sqlx::Error> {
+        // Timestamp 1 seconde avant la connexion suivante
+        let disconnect_time = chrono::DateTime::parse_from_rfc3339(next_connection)
             .map(|dt| dt - chrono::Duration::seconds(1))
             .unwrap_or_else(|e| {
                 tracing::warn!(
                     "Timestamp invalide pour déconnexion auto: {} - Erreur: {} - Utilisation de l'heure serveur",
-                    event.timestamp, e
+                    next_connection, e
                 );
                 Utc::now().into()
             })
             .to_rfc3339();
 
+        let os_info = event.os_info.as_ref();
+        Self::insert_server_disconnect(
+            conn,
+            &ServerDisconnect {
+                username: &event.username,
+                timestamp: &disconnect_time,
+                hostname: event.hostname.as_deref(),
+                source_ip: Some(source_ip),
+                os_name: os_info.and_then(|os| os.os_name.as_deref()),
+                os_version: os_info.and_then(|os| os.os_version.as_deref()),
+                kernel_version: os_info.and_then(|os| os.kernel_version.as_deref()),
+                session_uuid,
+                reason: CloseReason::AutoClosed,
+            },
+        )
+        .await?;
+        Self::close_session(conn, session_uuid, &disconnect_time, Some(source_ip), CloseReason::AutoClosed)
+            .await?;
+
+        tracing::info!("Déconnexion automatique insérée pour session: {}", session_uuid);
+        Ok(disconnect_time)
+    }
+
+    /// Insère une déconnexion générée par le serveur (sans event_id)
+    ///
+    /// Rangée dans events_history si elle précède la dernière rotation. Une
+    /// déconnexion réelle reçue en retard peut la remplacer
+    /// (`delete_auto_disconnect`).
+    async fn insert_server_disconnect(
+        conn: &mut SqliteConnection,
+        disconnect: &ServerDisconnect<'_>,
+    ) -> Result<(), sqlx::Error> {
         let server_timestamp = Utc::now().to_rfc3339();
+        let archived_at = Self::rotation_after(conn, disconnect.timestamp).await?;
+        let sql = if archived_at.is_some() {
+            queries::SQL_INSERT_AUTO_DISCONNECT_HISTORY
+        } else {
+            queries::SQL_INSERT_AUTO_DISCONNECT
+        };
 
-        sqlx::query(queries::SQL_INSERT_AUTO_DISCONNECT)
-            .bind(&event.username)
-            .bind(&disconnect_time)
-            .bind(event.hostname.as_deref())
-            .bind(source_ip)
+        let mut query = sqlx::query(sql)
+            .bind(disconnect.username)
+            .bind(disconnect.timestamp)
+            .bind(disconnect.hostname)
+            .bind(disconnect.source_ip)
             .bind(&server_timestamp)
-            .bind(event.os_info.as_ref().and_then(|os| os.os_name.as_deref()))
-            .bind(event.os_info.as_ref().and_then(|os| os.os_version.as_deref()))
-            .bind(event.os_info.as_ref().and_then(|os| os.kernel_version.as_deref()))
+            .bind(disconnect.os_name)
+            .bind(disconnect.os_version)
+            .bind(disconnect.kernel_version)
+            .bind(disconnect.session_uuid)
+            .bind(disconnect.reason);
+        if let Some(archived_at) = &archived_at {
+            query = query.bind(archived_at);
+        }
+        query.execute(&mut *conn).await?;
+        Ok(())
+    }
+
+    /// Enregistre un signal de présence d'une session (action 'H')
+    pub async fn record_heartbeat(
+        conn: &mut SqliteConnection,
+        session_uuid: &str,
+        timestamp: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(queries::SQL_UPDATE_SESSION_HEARTBEAT)
+            .bind(timestamp)
+            .bind(timestamp)
+            .bind(Utc::now().to_rfc3339())
             .bind(session_uuid)
-            .execute(&self.pool)
+            .execute(&mut *conn)
             .await?;
+        Ok(())
+    }
 
-        tracing::info!("Déconnexion automatique insérée pour session: {}", session_uuid);
+    /// Ferme les sessions sans signal de présence depuis `cutoff`
+    ///
+    /// Une transaction (`BEGIN IMMEDIATE`) pour la recherche et les fermetures :
+    /// un signal reçu entre-temps attend la fin et trouve la session fermée.
+    /// Chaque session reçoit une déconnexion à l'heure de son dernier signal et
+    /// est fermée avec le motif `timed_out`.
+    ///
+    /// # Retourne
+    /// Les sessions fermées
+    pub async fn close_stale_sessions(&self, cutoff: &str) -> Result<Vec<StaleSession>, sqlx::Error> {
+        let mut tx = self.begin().await?;
+        let stale = sqlx::query_as::<_, StaleSession>(queries::SQL_FIND_STALE_SESSIONS)
+            .bind(cutoff)
+            .fetch_all(&mut *tx)
+            .await?;
+
+        for session in &stale {
+            Self::insert_server_disconnect(
+                &mut tx,
+                &ServerDisconnect {
+                    username: &session.username,
+                    timestamp: &session.last_heartbeat_at,
+                    hostname: session.hostname.as_deref(),
+                    source_ip: None,
+                    os_name: session.os_name.as_deref(),
+                    os_version: session.os_version.as_deref(),
+                    kernel_version: None,
+                    session_uuid: &session.session_uuid,
+                    reason: CloseReason::TimedOut,
+                },
+            )
+            .await?;
+            Self::close_session(
+                &mut tx,
+                &session.session_uuid,
+                &session.last_heartbeat_at,
+                None,
+                CloseReason::TimedOut,
+            )
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(stale)
+    }
+
+    /// Ferme les sessions qui dépassent leur durée maximale
+    ///
+    /// Une transaction (`BEGIN IMMEDIATE`) pour la recherche et les fermetures.
+    /// La durée maximale dépend de la machine (`SessionsConfig::max_session_duration`) ;
+    /// chaque session dépassée reçoit une déconnexion à l'instant exact où la
+    /// durée est atteinte et est fermée avec le motif `policy_closed`.
+    ///
+    /// # Arguments
+    /// * `sessions` - Durées maximales (globale et par groupe de machines)
+    /// * `now` - Instant de référence
+    ///
+    /// # Retourne
+    /// Les sessions fermées, avec l'heure de la déconnexion insérée
+    pub async fn close_overdue_sessions(
+        &self,
+        sessions: &SessionsConfig,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<(OverdueSession, String)>, sqlx::Error> {
+        let Some(shortest) = sessions.shortest_max_session() else {
+            return Ok(Vec::new());
+        };
+
+        let mut tx = self.begin().await?;
+        let candidates = sqlx::query_as::<_, OverdueSession>(queries::SQL_FIND_OPEN_SESSIONS_STARTED_BEFORE)
+            .bind((now - shortest).to_rfc3339())
+            .fetch_all(&mut *tx)
+            .await?;
+
+        let mut closed = Vec::new();
+        for session in candidates {
+            let hostname = session.hostname.as_deref().unwrap_or("unknown");
+            let Some(max_duration) = sessions.max_session_duration(hostname) else {
+                continue;
+            };
+            let started_at = match DateTime::parse_from_rfc3339(&session.started_at) {
+                Ok(started_at) => started_at,
+                Err(e) => {
+                    tracing::warn!(
+                        "Début de session invalide ({}): {} - Erreur: {}",
+                        session.session_uuid, session.started_at, e
+                    );
+                    continue;
+                }
+            };
+            let ended_at = started_at + max_duration;
+            if ended_at > now {
+                continue;
+            }
+            let ended_at = ended_at.to_rfc3339();
+
+            Self::insert_server_disconnect(
+                &mut tx,
+                &ServerDisconnect {
+                    username: &session.username,
+                    timestamp: &ended_at,
+                    hostname: session.hostname.as_deref(),
+                    source_ip: None,
+                    os_name: session.os_name.as_deref(),
+                    os_version: session.os_version.as_deref(),
+                    kernel_version: None,
+                    session_uuid: &session.session_uuid,
+                    reason: CloseReason::PolicyClosed,
+                },
+            )
+            .await?;
+            Self::close_session(&mut tx, &session.session_uuid, &ended_at, None, CloseReason::PolicyClosed)
+                .await?;
+            closed.push((session, ended_at));
+        }
+
+        tx.commit().await?;
+        Ok(closed)
+    }
+
+    /// Ferme les sessions des autres utilisateurs d'un poste à place unique
+    ///
+    /// Chaque session ouverte avant la connexion reçoit une déconnexion à
+    /// l'heure de cette connexion et est fermée avec le motif `auto_closed` :
+    /// une déconnexion réelle reçue en retard la remplace.
+    ///
+    /// # Arguments
+    /// * `conn` - Connexion (ou transaction) sur laquelle exécuter la requête
+    /// * `event` - Événement de connexion qui provoque la fermeture
+    /// * `hostname` - Machine de la connexion
+    /// * `source_ip` - Adresse IP source
+    ///
+    /// # Retourne
+    /// Les sessions fermées
+    pub async fn close_other_host_sessions(
+        conn: &mut SqliteConnection,
+        event: &ClientEvent,
+        hostname: &str,
+        source_ip: &str,
+    ) -> Result<Vec<HostSession>, sqlx::Error> {
+        let sessions = sqlx::query_as::<_, HostSession>(queries::SQL_FIND_OTHER_OPEN_SESSIONS_ON_HOST)
+            .bind(hostname)
+            .bind(&event.username)
+            .bind(&event.timestamp)
+            .fetch_all(&mut *conn)
+            .await?;
+
+        for session in &sessions {
+            Self::insert_server_disconnect(
+                conn,
+                &ServerDisconnect {
+                    username: &session.username,
+                    timestamp: &event.timestamp,
+                    hostname: Some(hostname),
+                    source_ip: Some(source_ip),
+                    os_name: session.os_name.as_deref(),
+                    os_version: session.os_version.as_deref(),
+                    kernel_version: None,
+                    session_uuid: &session.session_uuid,
+                    reason: CloseReason::AutoClosed,
+                },
+            )
+            .await?;
+            Self::close_session(
+                conn,
+                &session.session_uuid,
+                &event.timestamp,
+                Some(source_ip),
+                CloseReason::AutoClosed,
+            )
+            .await?;
+        }
+
+        Ok(sessions)
+    }
+
+    /// Réapparie les déconnexions orphelines avec leur connexion
+    ///
+    /// Voir `queries::SQL_FIND_ORPHAN_PAIRINGS` pour le choix de la session.
+    /// Pour chaque déconnexion réappariée : la déconnexion insérée par le
+    /// serveur (s'il y en a une) est supprimée, la déconnexion reprend le
+    /// session_uuid de la session, fermée à son heure (`explicit`), et la
+    /// réparation est journalisée dans orphan_repairs. Une session ne reçoit
+    /// que la première déconnexion orpheline qui la suit.
+    ///
+    /// # Arguments
+    /// * `conn` - Connexion (ou transaction) sur laquelle exécuter la requête
+    /// * `scope` - `(username, hostname)` à traiter, `None` = toutes les machines
+    /// * `since` - Déconnexions antérieures ignorées (ISO 8601 UTC), `None` = toutes
+    /// * `trigger` - Origine de la réconciliation
+    ///
+    /// # Retourne
+    /// Les réparations journalisées
+    pub async fn reconcile_orphans(
+        conn: &mut SqliteConnection,
+        scope: Option<(&str, &str)>,
+        since: Option<&str>,
+        trigger: OrphanRepairTrigger,
+    ) -> Result<Vec<OrphanRepair>, sqlx::Error> {
+        let pairings = sqlx::query_as::<_, OrphanPairing>(queries::SQL_FIND_ORPHAN_PAIRINGS)
+            .bind(scope.map(|(username, _)| username))
+            .bind(scope.map(|(username, _)| username))
+            .bind(scope.map(|(_, hostname)| hostname))
+            .bind(since)
+            .bind(since)
+            .fetch_all(&mut *conn)
+            .await?;
+
+        let repaired_at = Utc::now().format(SQLITE_TIMESTAMP_FORMAT).to_string();
+        let mut repaired_sessions = HashSet::new();
+        let mut repairs = Vec::new();
+        for pairing in pairings {
+            if !repaired_sessions.insert(pairing.session_uuid.clone()) {
+                continue;
+            }
+
+            if let (Some(id), Some(source)) = (pairing.server_disconnect_id, &pairing.server_disconnect_source) {
+                Self::delete_auto_disconnect(conn, source, id).await?;
+            }
+            Self::reassign_event(conn, &pairing.orphan_source, pairing.orphan_id, &pairing.session_uuid).await?;
+            Self::close_session(
+                conn,
+                &pairing.session_uuid,
+                &pairing.disconnect_timestamp,
+                pairing.source_ip.as_deref(),
+                CloseReason::Explicit,
+            )
+            .await?;
+
+            let repair = sqlx::query_as::<_, OrphanRepair>(queries::SQL_INSERT_ORPHAN_REPAIR)
+                .bind(&repaired_at)
+                .bind(trigger)
+                .bind(&pairing.username)
+                .bind(pairing.hostname.as_deref())
+                .bind(&pairing.disconnect_timestamp)
+                .bind(&pairing.orphan_uuid)
+                .bind(&pairing.session_uuid)
+                .bind(&pairing.session_started_at)
+                .bind(pairing.previous_ended_at.as_deref())
+                .bind(pairing.previous_close_reason)
+                .fetch_one(&mut *conn)
+                .await?;
+            repairs.push(repair);
+        }
+
+        Ok(repairs)
+    }
+
+    /// Bilan des déconnexions orphelines : non réappariées par machine, réparations
+    pub async fn orphan_stats(&self) -> Result<OrphanStats, sqlx::Error> {
+        let hosts = sqlx::query_as::<_, OrphanHost>(queries::SQL_COUNT_ORPHANS_BY_HOST)
+            .fetch_all(&self.pool)
+            .await?;
+        let repaired = sqlx::query_scalar(queries::SQL_COUNT_ORPHAN_REPAIRS)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(OrphanStats {
+            unrepaired: hosts.iter().map(|host| host.unrepaired).sum(),
+            repaired,
+            hosts,
+        })
+    }
+
+    /// Enregistre l'écart d'horloge mesuré sur un événement d'une machine
+    ///
+    /// Exécuté dans la transaction de l'événement : annulé avec lui s'il est refusé.
+    ///
+    /// # Arguments
+    /// * `conn` - Transaction (ou point de sauvegarde) de l'événement
+    /// * `hostname` - Machine de l'événement
+    /// * `skew_secs` - Timestamp client - heure de réception (secondes)
+    /// * `received_at` - Heure de réception de l'événement
+    /// * `out_of_tolerance` - Écart supérieur à `[clock] max_skew_secs`
+    pub async fn record_clock_skew(
+        conn: &mut SqliteConnection,
+        hostname: &str,
+        skew_secs: i64,
+        received_at: DateTime<Utc>,
+        out_of_tolerance: bool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(queries::SQL_UPSERT_HOST_CLOCK)
+            .bind(hostname)
+            .bind(skew_secs)
+            .bind(received_at.to_rfc3339())
+            .bind(out_of_tolerance)
+            .execute(&mut *conn)
+            .await?;
         Ok(())
     }
 
+    /// Dernier écart d'horloge mesuré d'une machine (`None` si jamais mesuré)
+    pub async fn host_clock_skew(
+        conn: &mut SqliteConnection,
+        hostname: &str,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar(queries::SQL_FIND_HOST_CLOCK_SKEW)
+            .bind(hostname)
+            .fetch_optional(&mut *conn)
+            .await
+    }
+
+    /// Écart d'horloge de toutes les machines, les plus décalées en premier
+    pub async fn list_host_clocks(&self) -> Result<Vec<HostClock>, sqlx::Error> {
+        sqlx::query_as::<_, HostClock>(queries::SQL_LIST_HOST_CLOCKS)
+            .fetch_all(&self.pool)
+            .await
+    }
+
     /// Récupère les sessions actuellement ouvertes
     ///
-    /// Retourne toutes les sessions avec action='C' qui n'ont pas de action='D' correspondant,
-    /// triées par hostname puis timestamp.
+    /// Retourne toutes les sessions sans fin de la table sessions,
+    /// triées par hostname puis début de session.
     ///
     /// # Retourne
     /// Liste des sessions ouvertes avec leurs détails (username, hostname, timestamp, etc.)
@@ -179,20 +867,30 @@ impl Database {
 
     /// Insère un nouvel événement dans events_today
     ///
+    /// Un événement antérieur à la dernière rotation (reçu en retard) est rangé
+    /// directement dans events_history.
+    ///
     /// # Arguments
+    /// * `conn` - Connexion (ou transaction) sur laquelle exécuter la requête
     /// * `event` - Événement client
     /// * `session_uuid` - UUID de session généré
     /// * `source_ip` - Adresse IP source
     ///
     /// # Retourne
-    /// L'ID de l'événement inséré
+    /// L'ID de l'événement inséré et sa table
     pub async fn insert_event(
-        &self,
+        conn: &mut SqliteConnection,
         event: &ClientEvent,
         session_uuid: &str,
         source_ip: &str,
-    ) -> Result<i64, sqlx::Error> {
+    ) -> Result<InsertedEvent, sqlx::Error> {
         let server_timestamp = Utc::now().to_rfc3339();
+        let archived_at = Self::rotation_after(conn, &event.timestamp).await?;
+        let sql = if archived_at.is_some() {
+            queries::SQL_INSERT_EVENT_HISTORY
+        } else {
+            queries::SQL_INSERT_EVENT
+        };
 
         // Sérialiser hardware_info si présent
         let hardware_json = event.hardware_info.as_ref()
@@ -208,9 +906,9 @@ impl Database {
                     .ok()
             });
 
-        let result = sqlx::query(queries::SQL_INSERT_EVENT)
+        let mut query = sqlx::query(sql)
             .bind(&event.username)
-            .bind(&event.action)
+            .bind(event.action)
             .bind(&event.timestamp)
             .bind(event.hostname.as_deref())
             .bind(source_ip)
@@ -220,9 +918,637 @@ impl Database {
             .bind(event.os_info.as_ref().and_then(|os| os.kernel_version.as_deref()))
             .bind(hardware_json.as_deref())
             .bind(session_uuid)
+            .bind(event.event_id.map(|id| id.to_string()))
+            .bind(event.peer_ip.as_deref())
+            .bind(event.clock_skew_secs)
+            .bind(event.client_timestamp.as_deref());
+        if let Some(archived_at) = &archived_at {
+            query = query.bind(archived_at);
+        }
+        let result = query.execute(&mut *conn).await?;
+
+        Ok(InsertedEvent {
+            id: result.last_insert_rowid(),
+            archived: archived_at.is_some(),
+        })
+    }
+
+    /// Rotation quotidienne : déplace events_today vers events_history
+    ///
+    /// Copie, vidage et comptages s'exécutent dans une seule transaction
+    /// (`BEGIN IMMEDIATE` : la collecte attend la fin de la rotation). Les lignes
+    /// déplacées reçoivent `archived_at` = début de la rotation. Les sessions
+    /// ouvertes restent ouvertes dans la table sessions. Les déconnexions
+    /// orphelines des `orphan_window_hours` dernières heures (0 = toutes) sont
+    /// d'abord réappariées (`reconcile_orphans`).
+    ///
+    /// Chaque exécution, réussie ou non, est journalisée dans la table rotations.
+    pub async fn rotate(&self, trigger: RotationTrigger, orphan_window_hours: u32) -> Result<Rotation, sqlx::Error> {
+        let started = Instant::now();
+        let now = Utc::now();
+        let rotated_at = now.format(SQLITE_TIMESTAMP_FORMAT).to_string();
+        let orphans_since = (orphan_window_hours > 0).then(|| {
+            (now - chrono::Duration::hours(i64::from(orphan_window_hours)))
+                .format("%Y-%m-%dT%H:%M:%SZ")
+                .to_string()
+        });
+
+        let result = self
+            .rotate_in_transaction(&rotated_at, orphans_since.as_deref(), trigger, started)
+            .await;
+
+        if let Err(e) = &result {
+            // Journalisation de l'échec hors de la transaction annulée
+            let mut conn = self.pool.acquire().await?;
+            let elapsed = started.elapsed().as_millis() as i64;
+            if let Err(log_error) =
+                Self::insert_rotation(&mut conn, &rotated_at, trigger, None, elapsed, Some(&e.to_string())).await
+            {
+                tracing::error!("Impossible de journaliser l'échec de la rotation: {}", log_error);
+            }
+        }
+
+        result
+    }
+
+    /// Étapes de la rotation, validées ensemble
+    async fn rotate_in_transaction(
+        &self,
+        rotated_at: &str,
+        orphans_since: Option<&str>,
+        trigger: RotationTrigger,
+        started: Instant,
+    ) -> Result<Rotation, sqlx::Error> {
+        let mut tx = self.begin().await?;
+
+        // Passe de réappariement (toutes les machines) avant l'archivage de la journée
+        let repairs = Self::reconcile_orphans(&mut tx, None, orphans_since, OrphanRepairTrigger::Rotation).await?;
+        if !repairs.is_empty() {
+            tracing::info!("Rotation : {} déconnexion(s) orpheline(s) réappariée(s)", repairs.len());
+        }
+
+        let today_before = Self::count(&mut tx, queries::SQL_COUNT_EVENTS_TODAY).await?;
+        let history_before = Self::count(&mut tx, queries::SQL_COUNT_EVENTS_HISTORY).await?;
+
+        sqlx::query(queries::SQL_INSERT_ROTATE_TO_HISTORY)
+            .bind(rotated_at)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(queries::SQL_DELETE_EVENTS_TODAY).execute(&mut *tx).await?;
+        sqlx::query(queries::SQL_DELETE_EVENTS_TODAY_SEQUENCE).execute(&mut *tx).await?;
+
+        let counts = RotationCounts {
+            today_before,
+            history_before,
+            today_after: Self::count(&mut tx, queries::SQL_COUNT_EVENTS_TODAY).await?,
+            history_after: Self::count(&mut tx, queries::SQL_COUNT_EVENTS_HISTORY).await?,
+            open_sessions: Self::count(&mut tx, queries::SQL_COUNT_OPEN_SESSIONS).await?,
+        };
+
+        let elapsed = started.elapsed().as_millis() as i64;
+        let rotation = Self::insert_rotation(&mut tx, rotated_at, trigger, Some(counts), elapsed, None).await?;
+        tx.commit().await?;
+
+        Ok(rotation)
+    }
+
+    /// Exécute une requête de comptage
+    async fn count(conn: &mut SqliteConnection, sql: &str) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(sql).fetch_one(&mut *conn).await
+    }
+
+    /// Journalise une rotation (`counts` absent = échec)
+    async fn insert_rotation(
+        conn: &mut SqliteConnection,
+        rotated_at: &str,
+        trigger: RotationTrigger,
+        counts: Option<RotationCounts>,
+        duration_ms: i64,
+        error: Option<&str>,
+    ) -> Result<Rotation, sqlx::Error> {
+        let status = if counts.is_some() { "success" } else { "failed" };
+        sqlx::query_as::<_, Rotation>(queries::SQL_INSERT_ROTATION)
+            .bind(rotated_at)
+            .bind(trigger)
+            .bind(status)
+            .bind(counts.as_ref().map(|c| c.today_before))
+            .bind(counts.as_ref().map(|c| c.history_before))
+            .bind(counts.as_ref().map(|c| c.today_after))
+            .bind(counts.as_ref().map(|c| c.history_after))
+            .bind(counts.as_ref().map(|c| c.history_after - c.history_before))
+            .bind(counts.as_ref().map(|c| c.open_sessions))
+            .bind(duration_ms)
+            .bind(error)
+            .fetch_one(&mut *conn)
+            .await
+    }
+
+    /// Supprime au plus `limit` événements `action` antérieurs à `cutoff` de events_history
+    ///
+    /// Une instruction par lot (transaction implicite courte) : la collecte
+    /// n'attend que le temps d'un lot. Seules les lignes d'id au plus `max_id`
+    /// (déjà archivées) sont concernées.
+    ///
+    /// # Retourne
+    /// Le nombre de lignes supprimées (inférieur à `limit` : plus rien à purger)
+    pub async fn purge_history_batch(
+        &self,
+        action: Action,
+        cutoff: &str,
+        max_id: i64,
+        limit: u32,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(queries::SQL_DELETE_HISTORY_EXPIRED)
+            .bind(action)
+            .bind(cutoff)
+            .bind(max_id)
+            .bind(limit)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Supprime au plus `limit` sessions terminées dont tous les événements ont été purgés
+    pub async fn purge_sessions_batch(&self, limit: u32) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(queries::SQL_DELETE_SESSIONS_EXPIRED)
+            .bind(limit)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Supprime au plus `limit` réappariements orphan_repairs antérieurs à `cutoff`
+    pub async fn purge_orphan_repairs_batch(&self, cutoff: &str, limit: u32) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(queries::SQL_DELETE_ORPHAN_REPAIRS_EXPIRED)
+            .bind(cutoff)
+            .bind(limit)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Supprime au plus `limit` machines de host_clocks dont la dernière mesure est antérieure à `cutoff`
+    pub async fn purge_host_clocks_batch(&self, cutoff: &str, limit: u32) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(queries::SQL_DELETE_HOST_CLOCKS_EXPIRED)
+            .bind(cutoff)
+            .bind(limit)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Plus grand id de events_history (borne des lignes à archiver puis supprimer)
+    pub async fn history_max_id(&self) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(queries::SQL_FIND_HISTORY_MAX_ID)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Dates (UTC) des événements `action` expirés, une par partition d'archive
+    pub async fn expired_history_dates(
+        &self,
+        action: Action,
+        cutoff: &str,
+        max_id: i64,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar(queries::SQL_FIND_EXPIRED_HISTORY_DATES)
+            .bind(action)
+            .bind(cutoff)
+            .bind(max_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Événements `action` expirés datés du jour `date` (contenu d'une partition)
+    pub async fn expired_history_on(
+        &self,
+        action: Action,
+        cutoff: &str,
+        max_id: i64,
+        date: &str,
+    ) -> Result<Vec<ArchivedEvent>, sqlx::Error> {
+        sqlx::query_as::<_, ArchivedEvent>(queries::SQL_FIND_EXPIRED_HISTORY_ON_DATE)
+            .bind(action)
+            .bind(cutoff)
+            .bind(max_id)
+            .bind(date)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Marque les événements d'une partition comme exportés (`exported_at`)
+    ///
+    /// Appelée après l'écriture du fichier Parquet : une purge interrompue
+    /// avant la suppression ne réexporte pas ces lignes.
+    pub async fn mark_history_exported(
+        &self,
+        exported_at: &str,
+        action: Action,
+        cutoff: &str,
+        max_id: i64,
+        date: &str,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(queries::SQL_MARK_HISTORY_EXPORTED)
+            .bind(exported_at)
+            .bind(action)
+            .bind(cutoff)
+            .bind(max_id)
+            .bind(date)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Restaure des lignes d'archive dans events_history (une transaction)
+    ///
+    /// # Retourne
+    /// Le nombre de lignes insérées (les id déjà présents sont ignorés)
+    pub async fn insert_archived_events(&self, events: &[ArchivedEvent]) -> Result<u64, sqlx::Error> {
+        let mut tx = self.begin().await?;
+        let mut inserted = 0;
+        for event in events {
+            let result = sqlx::query(queries::SQL_INSERT_ARCHIVED_EVENT)
+                .bind(event.id)
+                .bind(&event.username)
+                .bind(&event.action)
+                .bind(&event.timestamp)
+                .bind(&event.hostname)
+                .bind(&event.source_ip)
+                .bind(&event.peer_ip)
+                .bind(&event.server_timestamp)
+                .bind(&event.os_name)
+                .bind(&event.os_version)
+                .bind(&event.kernel_version)
+                .bind(&event.hardware_info)
+                .bind(&event.session_uuid)
+                .bind(&event.event_id)
+                .bind(&event.created_at)
+                .bind(&event.archived_at)
+                .bind(&event.close_reason)
+                .bind(event.clock_skew_secs)
+                .bind(&event.client_timestamp)
+                .execute(&mut *tx)
+                .await?;
+            inserted += result.rows_affected();
+        }
+        tx.commit().await?;
+
+        Ok(inserted)
+    }
+
+    /// Journalise le bilan d'une règle de conservation dans la table purges
+    pub async fn insert_purge(&self, purge: &Purge) -> Result<(), sqlx::Error> {
+        let status = if purge.error.is_none() { "success" } else { "failed" };
+        sqlx::query(queries::SQL_INSERT_PURGE)
+            .bind(&purge.purged_at)
+            .bind(purge.target)
+            .bind(purge.action)
+            .bind(purge.retention_days)
+            .bind(purge.cutoff.as_deref())
+            .bind(purge.deleted)
+            .bind(purge.batches)
+            .bind(purge.archive_files)
+            .bind(purge.duration_ms)
+            .bind(status)
+            .bind(purge.error.as_deref())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Indique si events_today contient des événements reçus avant `scheduled_at`
+    ///
+    /// Vrai si la rotation prévue à cet instant n'a pas eu lieu (serveur arrêté).
+    pub async fn rotation_overdue(&self, scheduled_at: DateTime<Utc>) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar(queries::SQL_FIND_ROTATION_OVERDUE)
+            .bind(scheduled_at.format(SQLITE_TIMESTAMP_FORMAT).to_string())
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Retourne la machine associée à un jeton valide (non révoqué)
+    ///
+    /// # Arguments
+    /// * `token_hash` - Empreinte SHA-256 (hex) du jeton présenté
+    pub async fn find_token_hostname(&self, token_hash: &str) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar(queries::SQL_FIND_TOKEN_HOSTNAME)
+            .bind(token_hash)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Enregistre un nouveau jeton d'enrôlement
+    ///
+    /// # Arguments
+    /// * `hostname` - Machine enrôlée
+    /// * `token_hash` - Empreinte SHA-256 (hex) du jeton délivré
+    ///
+    /// # Retourne
+    /// L'ID du jeton
+    ///
+    /// # Erreurs
+    /// Violation d'unicité si la machine possède déjà un jeton actif
+    pub async fn insert_host_token(&self, hostname: &str, token_hash: &str) -> Result<i64, sqlx::Error> {
+        let result = sqlx::query(queries::SQL_INSERT_HOST_TOKEN)
+            .bind(hostname)
+            .bind(token_hash)
+            .bind(Utc::now().to_rfc3339())
             .execute(&self.pool)
             .await?;
 
         Ok(result.last_insert_rowid())
     }
+
+    /// Liste tous les jetons d'enrôlement (actifs et révoqués)
+    pub async fn list_host_tokens(&self) -> Result<Vec<HostToken>, sqlx::Error> {
+        sqlx::query_as::<_, HostToken>(queries::SQL_LIST_HOST_TOKENS)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Révoque un jeton
+    ///
+    /// # Retourne
+    /// `true` si un jeton actif a été révoqué, `false` s'il n'existe pas ou l'était déjà
+    pub async fn revoke_host_token(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(queries::SQL_UPDATE_REVOKE_HOST_TOKEN)
+            .bind(Utc::now().to_rfc3339())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    /// Base SQLite temporaire (migrations appliquées), supprimée en fin de test
+    pub(crate) struct TestDb {
+        pub(crate) db: Database,
+        path: PathBuf,
+    }
+
+    impl TestDb {
+        pub(crate) async fn new() -> Self {
+            let path = std::env::temp_dir().join(format!("winlog-test-{}.db", Uuid::now_v7()));
+            let db = Database::new(&DatabaseConfig::scratch(&path.to_string_lossy()))
+                .await
+                .expect("base de test");
+            Self { db, path }
+        }
+
+        /// Ouvre une session (table sessions et connexion dans events_today)
+        pub(crate) async fn open_session(&self, username: &str, hostname: &str, timestamp: &str) -> String {
+            let event = event("C", username, hostname, timestamp);
+            let session_uuid = Database::generate_session_id(timestamp);
+            let mut tx = self.db.begin().await.expect("transaction");
+            Database::insert_session(&mut tx, &event, &session_uuid, "10.0.0.1").await.expect("session");
+            Database::insert_event(&mut tx, &event, &session_uuid, "10.0.0.1").await.expect("connexion");
+            tx.commit().await.expect("validation");
+            session_uuid
+        }
+
+        /// Enregistre une déconnexion orpheline (sans session ouverte)
+        async fn orphan_disconnect(&self, username: &str, hostname: &str, timestamp: &str) {
+            let event = event("D", username, hostname, timestamp);
+            let orphan_uuid = format!("orphan_{}", Database::generate_session_id(timestamp));
+            let mut tx = self.db.begin().await.expect("transaction");
+            Database::insert_event(&mut tx, &event, &orphan_uuid, "10.0.0.1").await.expect("déconnexion");
+            tx.commit().await.expect("validation");
+        }
+
+        /// Enregistre un signal de présence d'une session
+        async fn heartbeat(&self, session_uuid: &str, timestamp: &str) {
+            let mut tx = self.db.begin().await.expect("transaction");
+            Database::record_heartbeat(&mut tx, session_uuid, timestamp).await.expect("signal");
+            tx.commit().await.expect("validation");
+        }
+
+        /// Fin et motif de fermeture d'une session
+        pub(crate) async fn session_end(&self, session_uuid: &str) -> (Option<String>, Option<String>) {
+            sqlx::query_as("SELECT ended_at, close_reason FROM sessions WHERE session_uuid = ?")
+                .bind(session_uuid)
+                .fetch_one(&self.db.pool)
+                .await
+                .expect("session")
+        }
+
+        /// Horodatage et motif des déconnexions insérées par le serveur pour une session
+        pub(crate) async fn server_disconnects(&self, session_uuid: &str) -> Vec<(String, Option<String>)> {
+            sqlx::query_as(
+                "SELECT timestamp, close_reason FROM events_all
+                 WHERE session_uuid = ? AND action = 'D' AND event_id IS NULL",
+            )
+            .bind(session_uuid)
+            .fetch_all(&self.db.pool)
+            .await
+            .expect("déconnexions")
+        }
+    }
+
+    impl Drop for TestDb {
+        fn drop(&mut self) {
+            for suffix in ["", "-wal", "-shm"] {
+                let mut path = self.path.clone().into_os_string();
+                path.push(suffix);
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    /// Événement client de test (sans informations système)
+    pub(crate) fn event(action: &str, username: &str, hostname: &str, timestamp: &str) -> ClientEvent {
+        serde_json::from_value(serde_json::json!({
+            "username": username,
+            "action": action,
+            "timestamp": timestamp,
+            "hostname": hostname,
+        }))
+        .expect("événement de test")
+    }
+
+    /// Horodatage ISO 8601 décalé de `minutes` par rapport à maintenant
+    pub(crate) fn minutes_ago(minutes: i64) -> String {
+        (Utc::now() - chrono::Duration::minutes(minutes)).to_rfc3339()
+    }
+
+    #[tokio::test]
+    async fn stale_session_is_closed_at_last_heartbeat() {
+        let test = TestDb::new().await;
+
+        let heartbeat_at = minutes_ago(90);
+        let session = test.open_session("alice", "PC-01", &minutes_ago(120)).await;
+        test.heartbeat(&session, &heartbeat_at).await;
+
+        // Signal plus récent que la date limite : la session reste ouverte
+        assert!(test.db.close_stale_sessions(&minutes_ago(100)).await.expect("recherche").is_empty());
+
+        let closed = test.db.close_stale_sessions(&minutes_ago(15)).await.expect("recherche");
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].session_uuid, session);
+        assert_eq!(test.session_end(&session).await, (Some(heartbeat_at.clone()), Some("timed_out".to_string())));
+        assert_eq!(test.server_disconnects(&session).await, vec![(heartbeat_at, Some("timed_out".to_string()))]);
+    }
+
+    #[tokio::test]
+    async fn session_without_heartbeat_never_times_out() {
+        let test = TestDb::new().await;
+
+        let session = test.open_session("alice", "PC-01", &minutes_ago(600)).await;
+
+        assert!(test.db.close_stale_sessions(&minutes_ago(15)).await.expect("recherche").is_empty());
+        assert_eq!(test.session_end(&session).await, (None, None));
+    }
+
+    #[tokio::test]
+    async fn overdue_sessions_are_closed_when_max_duration_is_reached() {
+        let test = TestDb::new().await;
+        let sessions: SessionsConfig = toml::from_str(
+            r#"
+            max_session_hours = 8
+
+            [[host_groups]]
+            name = "salles-tp"
+            hosts = ["TP-*"]
+            max_session_hours = 2
+
+            [[host_groups]]
+            name = "serveurs"
+            hosts = ["RDS-*"]
+            max_session_hours = 0
+            "#,
+        )
+        .expect("section [sessions] de test");
+        let now = Utc::now();
+        let started = |hours: i64| {
+            (now - chrono::Duration::hours(hours))
+                .with_timezone(&chrono::FixedOffset::east_opt(2 * 3600).expect("fuseau de test"))
+                .to_rfc3339()
+        };
+
+        let office = test.open_session("alice", "PC-01", &started(10)).await;
+        let lab = test.open_session("bob", "TP-01", &started(3)).await;
+        let recent = test.open_session("carol", "PC-02", &started(7)).await;
+        let server = test.open_session("dave", "RDS-01", &started(20)).await;
+
+        let closed = test.db.close_overdue_sessions(&sessions, now).await.expect("recherche");
+        let mut closed: Vec<_> = closed.into_iter().map(|(session, ended_at)| (session.session_uuid, ended_at)).collect();
+        closed.sort();
+        let mut expected = vec![(office.clone(), started(2)), (lab.clone(), started(1))];
+        expected.sort();
+        assert_eq!(closed, expected);
+
+        // Déconnexion à l'instant exact où la durée est atteinte, fuseau d'origine conservé
+        assert_eq!(test.session_end(&office).await, (Some(started(2)), Some("policy_closed".to_string())));
+        assert_eq!(test.server_disconnects(&lab).await, vec![(started(1), Some("policy_closed".to_string()))]);
+        assert_eq!(test.session_end(&recent).await, (None, None));
+        assert_eq!(test.session_end(&server).await, (None, None));
+
+        // Seconde passe : rien à fermer
+        assert!(test.db.close_overdue_sessions(&sessions, now).await.expect("recherche").is_empty());
+    }
+
+    #[tokio::test]
+    async fn orphan_is_paired_with_earlier_connection_of_same_user_and_host() {
+        let test = TestDb::new().await;
+
+        let disconnected_at = minutes_ago(30);
+        test.orphan_disconnect("alice", "PC-01", &disconnected_at).await;
+        let other_user = test.open_session("bob", "PC-01", &minutes_ago(60)).await;
+        let later = test.open_session("alice", "PC-01", &minutes_ago(10)).await;
+
+        let mut conn = test.db.pool.acquire().await.expect("connexion");
+        let repairs = Database::reconcile_orphans(&mut conn, None, None, OrphanRepairTrigger::Rotation)
+            .await
+            .expect("réconciliation");
+        assert!(repairs.is_empty());
+        assert_eq!(test.session_end(&other_user).await, (None, None));
+        assert_eq!(test.session_end(&later).await, (None, None));
+
+        let session = test.open_session("alice", "PC-01", &minutes_ago(60)).await;
+        let scope = Some(("alice", "PC-01"));
+        let repairs = Database::reconcile_orphans(&mut conn, scope, None, OrphanRepairTrigger::LateConnection)
+            .await
+            .expect("réconciliation");
+        assert_eq!(repairs.len(), 1);
+        assert_eq!(repairs[0].session_uuid, session);
+        assert_eq!(test.session_end(&session).await, (Some(disconnected_at), Some("explicit".to_string())));
+        let stats = test.db.orphan_stats().await.expect("bilan");
+        assert_eq!((stats.unrepaired, stats.repaired), (0, 1));
+    }
+
+    #[tokio::test]
+    async fn rotation_re_pairs_only_orphans_within_window() {
+        let test = TestDb::new().await;
+
+        let (recent_at, old_at) = (minutes_ago(60), minutes_ago(5 * 24 * 60));
+        test.orphan_disconnect("alice", "PC-01", &recent_at).await;
+        test.orphan_disconnect("bob", "PC-02", &old_at).await;
+        let recent = test.open_session("alice", "PC-01", &minutes_ago(120)).await;
+        let old = test.open_session("bob", "PC-02", &minutes_ago(5 * 24 * 60 + 60)).await;
+
+        test.db.rotate(RotationTrigger::Manual, 72).await.expect("rotation");
+        assert_eq!(test.session_end(&recent).await, (Some(recent_at), Some("explicit".to_string())));
+        assert_eq!(test.session_end(&old).await, (None, None));
+        assert_eq!(test.db.orphan_stats().await.expect("bilan").unrepaired, 1);
+
+        // Sans fenêtre : toutes les déconnexions orphelines sont examinées
+        test.db.rotate(RotationTrigger::Manual, 0).await.expect("rotation");
+        assert_eq!(test.session_end(&old).await, (Some(old_at), Some("explicit".to_string())));
+        let stats = test.db.orphan_stats().await.expect("bilan");
+        assert_eq!((stats.unrepaired, stats.repaired), (0, 2));
+    }
+
+    #[tokio::test]
+    async fn orphan_repairs_are_purged_by_repair_date() {
+        let test = TestDb::new().await;
+
+        test.orphan_disconnect("alice", "PC-01", &minutes_ago(30)).await;
+        test.open_session("alice", "PC-01", &minutes_ago(60)).await;
+        test.orphan_disconnect("bob", "PC-02", &minutes_ago(30)).await;
+        test.open_session("bob", "PC-02", &minutes_ago(60)).await;
+        test.db.rotate(RotationTrigger::Manual, 0).await.expect("rotation");
+        assert_eq!(test.db.orphan_stats().await.expect("bilan").repaired, 2);
+
+        // Réparations du jour : conservées avec une date limite passée
+        assert_eq!(test.db.purge_orphan_repairs_batch(&minutes_ago(60), 10).await.expect("purge"), 0);
+        // Lots d'une ligne : deux lots pleins puis un lot vide
+        for expected in [1, 1, 0] {
+            assert_eq!(test.db.purge_orphan_repairs_batch(&minutes_ago(-60), 1).await.expect("purge"), expected);
+        }
+        assert_eq!(test.db.orphan_stats().await.expect("bilan").repaired, 0);
+    }
+
+    #[tokio::test]
+    async fn host_clocks_are_purged_when_no_longer_measured() {
+        let test = TestDb::new().await;
+
+        let now = Utc::now();
+        let mut tx = test.db.begin().await.expect("transaction");
+        for (hostname, days) in [("PC-01", 0), ("PC-OLD1", 100), ("PC-OLD2", 120)] {
+            Database::record_clock_skew(&mut tx, hostname, 2, now - chrono::Duration::days(days), false)
+                .await
+                .expect("mesure");
+        }
+        tx.commit().await.expect("validation");
+
+        // Lots d'une ligne : deux lots pleins puis un lot vide
+        let cutoff = (now - chrono::Duration::days(90)).to_rfc3339();
+        for expected in [1, 1, 0] {
+            assert_eq!(test.db.purge_host_clocks_batch(&cutoff, 1).await.expect("purge"), expected);
+        }
+        let clocks = test.db.list_host_clocks().await.expect("liste");
+        assert_eq!(clocks.iter().map(|clock| clock.hostname.as_str()).collect::<Vec<_>>(), ["PC-01"]);
+    }
 }
@@ -0,0 +1,84 @@
+//! # Expiration des sessions
+//!
+//! Ferme les sessions dont la machine a cessé d'envoyer des signaux de
+//! présence (action 'H') depuis `[sessions] heartbeat_timeout_minutes` :
+//! coupure de courant, plantage. Sans cela, la session resterait ouverte
+//! jusqu'à la connexion suivante du même utilisateur sur la machine.
+//!
+//! La session reçoit une déconnexion à l'heure de son dernier signal et le
+//! motif `timed_out`. Une déconnexion réelle reçue ensuite (file d'attente du
+//! client après une coupure réseau) la remplace.
+//!
+//! Ferme aussi les sessions qui dépassent la durée maximale
+//! (`max_session_hours`, globale ou par groupe de machines) : script de
+//! déconnexion jamais exécuté sur un poste de salle de TP. La déconnexion est
+//! insérée à l'instant où la durée est atteinte, motif `policy_closed`.
+
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::config::SessionsConfig;
+use crate::database::Database;
+
+/// Lance la recherche périodique des sessions expirées ou trop longues
+/// (sauf si aucun délai ni aucune durée maximale n'est configuré)
+pub fn spawn_reaper(db: Database, config: SessionsConfig) {
+    if config.heartbeat_timeout_minutes == 0 && config.shortest_max_session().is_none() {
+        tracing::info!(
+            "Expiration des sessions désactivée (heartbeat_timeout_minutes = 0, aucune durée maximale)"
+        );
+        return;
+    }
+
+    tokio::spawn(async move {
+        let interval = Duration::from_secs(config.check_interval_secs);
+        loop {
+            if config.heartbeat_timeout_minutes > 0 {
+                close_stale(&db, &config).await;
+            }
+            close_overdue(&db, &config).await;
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+/// Ferme les sessions sans signal de présence depuis le délai configuré
+async fn close_stale(db: &Database, config: &SessionsConfig) {
+    let cutoff = (Utc::now() - chrono::Duration::minutes(i64::from(config.heartbeat_timeout_minutes)))
+        .to_rfc3339();
+
+    match db.close_stale_sessions(&cutoff).await {
+        Ok(closed) => {
+            for session in &closed {
+                tracing::warn!(
+                    "Session {} de {}@{} expirée : aucun signal de présence depuis {}",
+                    session.session_uuid,
+                    session.username,
+                    session.hostname.as_deref().unwrap_or("unknown"),
+                    session.last_heartbeat_at
+                );
+            }
+        }
+        Err(e) => tracing::error!("❌ Recherche des sessions expirées impossible: {}", e),
+    }
+}
+
+/// Ferme les sessions qui dépassent la durée maximale de leur machine
+async fn close_overdue(db: &Database, config: &SessionsConfig) {
+    match db.close_overdue_sessions(config, Utc::now()).await {
+        Ok(closed) => {
+            for (session, ended_at) in &closed {
+                tracing::warn!(
+                    "Session {} de {}@{} fermée : durée maximale atteinte (début {}, fin {})",
+                    session.session_uuid,
+                    session.username,
+                    session.hostname.as_deref().unwrap_or("unknown"),
+                    session.started_at,
+                    ended_at
+                );
+            }
+        }
+        Err(e) => tracing::error!("❌ Recherche des sessions trop longues impossible: {}", e),
+    }
+}
@@ -4,16 +4,28 @@
 //! Implémente la même logique que le serveur PHP index.php.
 
 use axum::{
-    extract::{State, ConnectInfo},
+    body::Bytes,
+    extract::{Path, State, ConnectInfo},
     http::{StatusCode, HeaderMap, header},
     Json,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
-use std::net::SocketAddr;
+use serde::de::DeserializeOwned;
+use sqlx::SqliteConnection;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
 use crate::{
-    config::Config,
+    auth,
+    config::{ClockConfig, Config, SeatPolicy, ServerConfig, SessionsConfig, SkewPolicy},
     database::Database,
-    models::{ClientEvent, SuccessResponse, ErrorResponse, CurrentSession},
+    models::{
+        ClientEvent, SuccessResponse, ErrorCode, ErrorResponse, CurrentSession, BatchItemResult, BatchResponse,
+        EnrollRequest, EnrollResponse, HostToken, PairingChange, CloseReason, Rotation,
+        RotationTrigger, Action, OrphanRepairTrigger, OrphanStats, HostClock,
+    },
+    ratelimit::{RateLimits, TokenBuckets},
+    rotation,
 };
 
 /// État partagé de l'application
@@ -21,66 +33,58 @@ use crate::{
 pub struct AppState {
     pub config: Config,
     pub db: Database,
+    pub limits: Arc<RateLimits>,
 }
 
 /// Handler principal : collecte d'événements (POST /api/v1/events)
 ///
 /// Correspond à la logique de serveur/php/index.php :
-/// 1. Validation User-Agent et Content-Type
-/// 2. Validation de la structure JSON
-/// 3. Traitement selon l'action (C/D/M)
-/// 4. Insertion en base (events_today)
+/// 1. Limitation de débit par adresse IP source
+/// 2. Mesure de l'écart d'horloge, authentification (signature HMAC, ou
+///    User-Agent si aucun secret configuré)
+/// 3. Validation de la structure JSON, puis limitation par `username@hostname`
+/// 4. Écart d'horloge (`[clock]`), traitement selon l'action (C/D/M) et
+///    insertion en base (events_today)
 /// 5. Retour réponse JSON
+///
+/// # Réponse
+/// - 422 Unprocessable Entity : horloge hors tolérance (`[clock] on_skew = "reject"`)
+/// - 429 Too Many Requests : limite `[limits]` dépassée (en-tête `Retry-After`)
 pub async fn collect_event(
     State(state): State<AppState>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     headers: HeaderMap,
-    Json(event): Json<ClientEvent>,
-) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
-    
-    // 1. Validation User-Agent
-    let user_agent = headers
-        .get(header::USER_AGENT)
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("");
+    body: Bytes,
+) -> Result<Json<SuccessResponse>, Response> {
+    // Heure de réception, référence de la mesure de l'écart d'horloge
+    let received_at = chrono::Utc::now();
 
-    if !user_agent.starts_with(&state.config.security.expected_user_agent) {
-        tracing::warn!("Invalid User-Agent: {}", user_agent);
-        return Err((
-            StatusCode::FORBIDDEN,
-            Json(ErrorResponse::new("Invalid User-Agent")),
-        ));
-    }
-
-    // 2. Validation de la structure JSON
-    if event.username.is_empty() || event.action.is_empty() || event.timestamp.is_empty() {
-        tracing::warn!("Invalid JSON structure: missing required fields");
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse::new("Invalid JSON structure: missing required fields")),
-        ));
-    }
+    // 1. Extraction de l'adresse IP source et limitation par IP (avant tout traitement)
+    let source_ip = extract_real_ip(&state.config.server, &headers, addr);
+    let peer_ip = addr.ip().to_canonical().to_string();
+    check_rate_limit(&state.limits.per_ip, "IP", &source_ip)
+        .map_err(IntoResponse::into_response)?;
 
-    // 3. Validation de l'action
-    if !state.config.security.valid_actions.contains(&event.action) {
-        tracing::warn!("Invalid action: {}", event.action);
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse::new(format!("Invalid action: {}", event.action))),
-        ));
-    }
+    // 2. Écart d'horloge (mesuré avant l'authentification : une signature hors
+    // fenêtre l'enregistre), authentification sur le corps brut, puis jeton
+    let sample = measure_clock_skew(&headers, received_at);
+    authenticate_request(&state, &headers, &body, sample)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let token_host = authenticate_host(&state, &headers)
+        .await
+        .map_err(IntoResponse::into_response)?;
 
-    // 4. Validation du timestamp (format ISO 8601)
-    if chrono::DateTime::parse_from_rfc3339(&event.timestamp).is_err() {
-        tracing::warn!("Invalid timestamp format: {}", event.timestamp);
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse::new("Invalid timestamp format (expected ISO 8601)")),
-        ));
-    }
+    // 3. Validation de l'événement (JSON, structure, action, timestamp)
+    let mut event = parse_json(&body)
+        .and_then(|value| parse_event(&state, value))
+        .map_err(IntoResponse::into_response)?;
+    event.peer_ip = Some(peer_ip);
+    validate_event(&state, &event).map_err(IntoResponse::into_response)?;
+    check_token_hostname(token_host.as_deref(), &event).map_err(IntoResponse::into_response)?;
 
-    // 5. Extraction de l'adresse IP source
-    let source_ip = extract_real_ip(headers.clone(), addr);
+    check_rate_limit(&state.limits.per_host, "host", &host_key(&event))
+        .map_err(IntoResponse::into_response)?;
 
     // Log de réception
     tracing::info!(
@@ -91,194 +95,1276 @@ pub async fn collect_event(
         source_ip
     );
 
-    // 6. Traitement selon l'action
-    let session_uuid = match event.action.as_str() {
-        "C" => handle_connection(&state, &event, &source_ip).await?,
-        "D" => handle_disconnection(&state, &event).await?,
-        "M" => handle_hardware(&state, &event).await?,
-        _ => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(ErrorResponse::new("Unknown action")),
-            ))
-        }
-    };
-
-    // 7. Insertion de l'événement en base
-    let event_id = state.db.insert_event(&event, &session_uuid, &source_ip)
+    // 4. Traitement et insertion dans une transaction (dont l'écart d'horloge)
+    let response = store_event(&state, &mut event, &source_ip, sample)
         .await
-        .map_err(|e| {
-            tracing::error!("Database error: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse::new("Database error")),
-            )
-        })?;
+        .map_err(IntoResponse::into_response)?;
 
-    // 8. Log de succès
+    // Log de succès
     tracing::info!(
         "Data stored: ID={} - {} - {} - Session: {} from {}",
-        event_id,
+        response.event_id,
+        response.username,
+        response.action,
+        response.session_uuid,
+        source_ip
+    );
+
+    // 5. Réponse de succès
+    Ok(Json(response))
+}
+
+/// Traite et enregistre un événement isolé dans sa propre transaction
+async fn store_event(
+    state: &AppState,
+    event: &mut ClientEvent,
+    source_ip: &str,
+    sample: Option<ClockSample>,
+) -> Result<SuccessResponse, (StatusCode, Json<ErrorResponse>)> {
+    let mut tx = state.db.begin().await.map_err(database_error)?;
+
+    let response = process_event(&mut tx, &state.config, event, source_ip, sample).await?;
+
+    tx.commit().await.map_err(database_error)?;
+
+    Ok(response)
+}
+
+/// Requête refusée par la limitation de débit
+#[derive(Clone, Copy)]
+struct RateLimited {
+    scope: &'static str,
+    retry_after: u64,
+}
+
+impl RateLimited {
+    /// Erreur 429 sans en-tête (résultat d'un élément de lot)
+    fn into_error(self) -> (StatusCode, Json<ErrorResponse>) {
+        (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(ErrorResponse::with_details(
+                ErrorCode::RateLimited,
+                format!("Rate limit exceeded ({})", self.scope),
+                serde_json::json!({ "retry_after_secs": self.retry_after }),
+            )),
+        )
+    }
+}
+
+impl IntoResponse for RateLimited {
+    /// HTTP 429 avec l'en-tête `Retry-After` (secondes avant le prochain jeton)
+    fn into_response(self) -> Response {
+        let retry_after = self.retry_after;
+        let (status, body) = self.into_error();
+        (status, [(header::RETRY_AFTER, retry_after.to_string())], body).into_response()
+    }
+}
+
+/// Clé de la limite par poste : `username@hostname`
+fn host_key(event: &ClientEvent) -> String {
+    format!(
+        "{}@{}",
         event.username,
-        event.action,
-        session_uuid,
+        event.hostname.as_deref().unwrap_or("unknown")
+    )
+}
+
+/// Consomme un jeton du limiteur pour `key`
+fn check_rate_limit(
+    limiter: &TokenBuckets,
+    scope: &'static str,
+    key: &str,
+) -> Result<(), RateLimited> {
+    limiter.check(key).map_err(|retry_after| {
+        tracing::warn!(
+            "Rate limit exceeded ({}): {} - retry after {}s ({} rejected since startup)",
+            scope,
+            key,
+            retry_after,
+            limiter.rejected_count()
+        );
+        RateLimited { scope, retry_after }
+    })
+}
+
+/// Collecte par lot (POST /api/v1/events/batch)
+///
+/// Utilisé par les clients qui rejouent leurs événements en attente après une
+/// coupure réseau. Chaque élément passe par les mêmes validations et la même
+/// logique de session que `collect_event`, dans l'ordre du tableau reçu.
+///
+/// Tout le lot est traité dans une seule transaction ; chaque élément dispose
+/// de son propre point de sauvegarde, de sorte qu'un élément invalide ou en
+/// erreur est annulé sans affecter les autres.
+///
+/// Les limites `[limits]` s'appliquent aussi aux rejeux : un jeton par IP pour
+/// le lot, un jeton par `username@hostname` distinct du lot (une file d'attente
+/// rejouée ne vide pas le seau du poste). Si ce jeton est refusé, tous les
+/// éléments de ce poste sont en `RATE_LIMITED`, rejoués plus tard par le client.
+///
+/// L'écart d'horloge est mesuré sur l'heure d'envoi du lot (`X-Winlog-Sent-At`)
+/// et `[clock] on_skew` s'applique à chaque élément comme en envoi direct.
+///
+/// # Réponse
+/// - 200 OK : `BatchResponse` avec un résultat par élément
+/// - 400 Bad Request : corps JSON invalide (lot entier rejeté)
+/// - 401 Unauthorized : signature absente, invalide ou expirée (lot entier rejeté)
+/// - 403 Forbidden : User-Agent invalide (lot entier rejeté)
+/// - 413 Payload Too Large : lot supérieur à `server.max_batch_size`
+/// - 429 Too Many Requests : limite par IP dépassée (en-tête `Retry-After`)
+/// - 500 Internal Server Error : transaction impossible à ouvrir ou valider
+pub async fn collect_events_batch(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<BatchResponse>, Response> {
+    // Heure de réception, référence de la mesure de l'écart d'horloge
+    let received_at = chrono::Utc::now();
+
+    // 1. Limitation par IP (un jeton par lot), puis authentification
+    let source_ip = extract_real_ip(&state.config.server, &headers, addr);
+    let peer_ip = addr.ip().to_canonical().to_string();
+    check_rate_limit(&state.limits.per_ip, "IP", &source_ip)
+        .map_err(IntoResponse::into_response)?;
+
+    let sample = measure_clock_skew(&headers, received_at);
+    collect_batch(&state, &headers, &body, &source_ip, &peer_ip, sample)
+        .await
+        .map(Json)
+        .map_err(IntoResponse::into_response)
+}
+
+/// Authentifie, valide et enregistre un lot (voir `collect_events_batch`)
+async fn collect_batch(
+    state: &AppState,
+    headers: &HeaderMap,
+    body: &[u8],
+    source_ip: &str,
+    peer_ip: &str,
+    sample: Option<ClockSample>,
+) -> Result<BatchResponse, (StatusCode, Json<ErrorResponse>)> {
+    // Authentification (une seule fois pour tout le lot)
+    authenticate_request(state, headers, body, sample).await?;
+    let token_host = authenticate_host(state, headers).await?;
+    let events: Vec<serde_json::Value> = parse_json(body)?;
+
+    // 2. Validation de la taille du lot
+    let max_batch_size = state.config.server.max_batch_size;
+    if events.len() > max_batch_size {
+        tracing::warn!("Batch too large: {} events (max {})", events.len(), max_batch_size);
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(ErrorResponse::with_details(
+                ErrorCode::BatchTooLarge,
+                format!("Batch too large: {} events (max {})", events.len(), max_batch_size),
+                serde_json::json!({ "received": events.len(), "max_batch_size": max_batch_size }),
+            )),
+        ));
+    }
+
+    tracing::info!("Received batch: {} events from {}", events.len(), source_ip);
+
+    // 3. Traitement de chaque élément dans une transaction commune
+    let mut tx = state.db.begin().await.map_err(database_error)?;
+
+    let mut batch = BatchContext {
+        token_host: token_host.as_deref(),
+        source_ip,
+        peer_ip,
+        sample,
+        host_charges: HashMap::new(),
+    };
+    let mut results = Vec::with_capacity(events.len());
+    for (index, event) in events.into_iter().enumerate() {
+        let outcome = process_batch_item(state, &mut tx, &mut batch, event).await;
+        results.push(match outcome {
+            Ok(response) => {
+                tracing::debug!(
+                    "Batch item {} stored: ID={} - {} - {} - Session: {}",
+                    index,
+                    response.event_id,
+                    response.username,
+                    response.action,
+                    response.session_uuid
+                );
+                BatchItemResult {
+                    index,
+                    status: "success".to_string(),
+                    result: Some(response),
+                    code: None,
+                    error: None,
+                    details: None,
+                }
+            }
+            Err((_, Json(error))) => {
+                tracing::debug!("Batch item {} rejected: {}", index, error.error);
+                BatchItemResult {
+                    index,
+                    status: "error".to_string(),
+                    result: None,
+                    code: Some(error.code),
+                    error: Some(error.error),
+                    details: error.details,
+                }
+            }
+        });
+    }
+
+    tx.commit().await.map_err(database_error)?;
+
+    // 4. Synthèse (un seul log pour tout le lot)
+    let accepted = results.iter().filter(|r| r.result.is_some()).count();
+    let rejected = results.len() - accepted;
+    tracing::info!(
+        "Batch stored: {} accepted, {} rejected from {}",
+        accepted,
+        rejected,
         source_ip
     );
 
-    // 9. Réponse de succès
-    Ok(Json(SuccessResponse {
+    Ok(BatchResponse {
         status: "success".to_string(),
-        message: "Data stored in database".to_string(),
-        event_id,
-        session_uuid,
-        action: event.action.clone(),
-        username: event.username.clone(),
-    }))
+        accepted,
+        rejected,
+        results,
+    })
 }
 
-/// Traite une connexion (action='C')
+/// Contexte commun aux éléments d'un lot
+struct BatchContext<'a> {
+    token_host: Option<&'a str>,
+    source_ip: &'a str,
+    peer_ip: &'a str,
+    sample: Option<ClockSample>,
+    /// Résultat de l'unique jeton prélevé pour chaque `username@hostname` du lot
+    host_charges: HashMap<String, Result<(), RateLimited>>,
+}
+
+/// Traite un élément d'un lot dans son propre point de sauvegarde
 ///
-/// Logique :
-/// 1. Chercher si une session est ouverte aujourd'hui
-/// 2. Si oui, la fermer automatiquement (déconnexion auto)
-/// 3. Générer un nouveau session_uuid
-async fn handle_connection(
+/// Le point de sauvegarde est validé si l'élément est enregistré, annulé sinon
+/// (par exemple si la déconnexion automatique a été insérée mais pas l'événement).
+async fn process_batch_item(
     state: &AppState,
-    event: &ClientEvent,
-    source_ip: &str,
-) -> Result<String, (StatusCode, Json<ErrorResponse>)> {
-    let hostname = event.hostname.as_deref().unwrap_or("unknown");
+    conn: &mut SqliteConnection,
+    batch: &mut BatchContext<'_>,
+    event: serde_json::Value,
+) -> Result<SuccessResponse, (StatusCode, Json<ErrorResponse>)> {
+    let mut event = parse_event(state, event)?;
+    event.peer_ip = Some(batch.peer_ip.to_string());
+    validate_event(state, &event)?;
+    check_token_hostname(batch.token_host, &event)?;
+    let charge = *batch
+        .host_charges
+        .entry(host_key(&event))
+        .or_insert_with_key(|key| check_rate_limit(&state.limits.per_host, "host", key));
+    charge.map_err(RateLimited::into_error)?;
 
-    // Chercher session ouverte aujourd'hui
-    let open_session = state.db
-        .find_open_session_today(&event.username, hostname, &event.timestamp)
-        .await
-        .map_err(|e| {
-            tracing::error!("Database error: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse::new("Database error")),
+    let mut savepoint = Database::savepoint(conn).await.map_err(database_error)?;
+
+    // Écart mesuré sur l'heure d'envoi du lot (et non sur le timestamp de
+    // l'événement rejoué, en retard par nature)
+    let outcome =
+        process_event(&mut savepoint, &state.config, &mut event, batch.source_ip, batch.sample).await;
+    match outcome {
+        Ok(response) => {
+            savepoint.commit().await.map_err(database_error)?;
+            Ok(response)
+        }
+        Err(err) => {
+            if let Err(e) = savepoint.rollback().await {
+                tracing::error!("Failed to rollback batch item: {}", e);
+            }
+            Err(err)
+        }
+    }
+}
+
+/// Authentifie une requête de collecte
+///
+/// Si des secrets HMAC sont configurés, la signature est obligatoire et remplace
+/// le contrôle du User-Agent (falsifiable). Sinon, seul le User-Agent est vérifié.
+///
+/// `sample` : écart d'horloge mesuré sur la requête, enregistré si la signature
+/// est authentique mais hors fenêtre (voir `reject_expired_signature`).
+async fn authenticate_request(
+    state: &AppState,
+    headers: &HeaderMap,
+    body: &[u8],
+    sample: Option<ClockSample>,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let security = &state.config.security;
+    if !security.signature_required() {
+        return validate_user_agent(state, headers);
+    }
+
+    let header_str = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+    let verified = auth::verify_signature(
+        &security.hmac_secrets,
+        security.signature_max_age_secs,
+        header_str(auth::TIMESTAMP_HEADER),
+        header_str(auth::SIGNATURE_HEADER),
+        body,
+        chrono::Utc::now().timestamp(),
+    );
+
+    match verified {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            tracing::warn!("Signature rejected: {}", e);
+            let unauthorized = (
+                StatusCode::UNAUTHORIZED,
+                Json(ErrorResponse::new(ErrorCode::Unauthorized, e.to_string())),
+            );
+            match (e, sample) {
+                (auth::SignatureError::Expired, Some(sample)) => {
+                    Err(reject_expired_signature(state, body, sample, unauthorized).await)
+                }
+                _ => Err(unauthorized),
+            }
+        }
+    }
+}
+
+/// Refus d'une requête authentique dont la signature est hors fenêtre
+///
+/// La cause la plus probable est l'horloge de la machine : l'écart mesuré est
+/// enregistré pour chaque machine du corps signé (`host_clocks`), sans quoi une
+/// machine décalée de plus de `signature_max_age_secs` n'apparaîtrait jamais
+/// dans GET /api/v1/admin/clocks. La requête reste refusée (un rejeu est
+/// indiscernable d'une horloge décalée) : 422 CLOCK_SKEW si l'écart dépasse la
+/// tolérance avec `[clock] on_skew = "reject"`, `unauthorized` sinon.
+async fn reject_expired_signature(
+    state: &AppState,
+    body: &[u8],
+    sample: ClockSample,
+    unauthorized: (StatusCode, Json<ErrorResponse>),
+) -> (StatusCode, Json<ErrorResponse>) {
+    let clock = &state.config.clock;
+    let out_of_tolerance = clock.out_of_tolerance(sample.skew_secs);
+
+    let recorded = async {
+        let mut tx = state.db.begin().await?;
+        for hostname in signed_hostnames(body) {
+            Database::record_clock_skew(
+                &mut tx,
+                &hostname,
+                sample.skew_secs,
+                sample.received_at,
+                out_of_tolerance,
             )
-        })?;
+            .await?;
+        }
+        tx.commit().await
+    };
+    if let Err(e) = recorded.await {
+        tracing::error!("Failed to record clock skew of expired signature: {}", e);
+    }
 
-    // Si session ouverte trouvée, la fermer automatiquement
-    if let Some(session) = open_session {
+    if out_of_tolerance && clock.on_skew == SkewPolicy::Reject {
         tracing::warn!(
-            "Session ouverte détectée pour {}@{} - fermeture automatique",
-            event.username,
-            hostname
+            "Clock skew of {}s (max {}s): expired signature rejected",
+            sample.skew_secs,
+            clock.max_skew_secs
         );
-
-        state.db
-            .insert_auto_disconnect(event, &session.session_uuid, source_ip)
-            .await
-            .map_err(|e| {
-                tracing::error!("Failed to insert auto-disconnect: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ErrorResponse::new("Database error")),
-                )
-            })?;
+        return clock_skew_error(clock, sample.skew_secs);
     }
+    unauthorized
+}
 
-    // Générer nouveau session_uuid
-    Ok(Database::generate_session_id(&event.username, hostname, &event.timestamp))
+/// Machines nommées dans un corps de collecte (événement ou lot), sans doublon
+fn signed_hostnames(body: &[u8]) -> Vec<String> {
+    let items = match serde_json::from_slice(body) {
+        Ok(serde_json::Value::Array(items)) => items,
+        Ok(item) => vec![item],
+        Err(_) => Vec::new(),
+    };
+    let mut hostnames: Vec<String> = items
+        .iter()
+        .filter_map(|item| item.get("hostname")?.as_str())
+        .map(str::to_string)
+        .collect();
+    hostnames.sort();
+    hostnames.dedup();
+    hostnames
 }
 
-/// Traite une déconnexion (action='D')
+/// Authentifie la machine émettrice par son jeton d'enrôlement
 ///
-/// Logique :
-/// 1. Chercher la dernière session ouverte
-/// 2. Si trouvée, utiliser son UUID
-/// 3. Sinon, générer un UUID "orphan_"
-async fn handle_disconnection(
+/// # Retourne
+/// - `Ok(None)` si `security.require_host_token` est désactivé
+/// - `Ok(Some(hostname))` : machine à laquelle le jeton présenté a été délivré
+async fn authenticate_host(
     state: &AppState,
-    event: &ClientEvent,
-) -> Result<String, (StatusCode, Json<ErrorResponse>)> {
-    let hostname = event.hostname.as_deref().unwrap_or("unknown");
+    headers: &HeaderMap,
+) -> Result<Option<String>, (StatusCode, Json<ErrorResponse>)> {
+    if !state.config.security.require_host_token {
+        return Ok(None);
+    }
 
-    // Chercher dernière session ouverte
-    let session_uuid = state.db
-        .find_last_open_session(&event.username, hostname)
+    let Some(token) = auth::bearer_token(headers) else {
+        tracing::warn!("Missing host token");
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse::new(ErrorCode::Unauthorized, "Missing host token")),
+        ));
+    };
+
+    let hostname = state.db
+        .find_token_hostname(&auth::hash_token(token))
         .await
-        .map_err(|e| {
-            tracing::error!("Database error: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse::new("Database error")),
-            )
-        })?;
+        .map_err(database_error)?;
 
-    match session_uuid {
-        Some(uuid) => Ok(uuid),
+    match hostname {
+        Some(hostname) => Ok(Some(hostname)),
         None => {
-            tracing::warn!(
-                "Aucune session ouverte trouvée pour {}@{}",
-                event.username,
-                hostname
-            );
-            // UUID orphelin
-            Ok(format!(
-                "orphan_{}",
-                Database::generate_session_id(&event.username, hostname, &event.timestamp)
+            tracing::warn!("Invalid or revoked host token");
+            Err((
+                StatusCode::UNAUTHORIZED,
+                Json(ErrorResponse::new(ErrorCode::Unauthorized, "Invalid or revoked host token")),
             ))
         }
     }
 }
 
-/// Traite un événement matériel (action='M')
+/// Vérifie que l'événement est déclaré pour la machine du jeton
 ///
-/// Logique : Génère simplement un UUID préfixé "hardware_"
-async fn handle_hardware(
-    _state: &AppState,
+/// Empêche une machine compromise de poster des événements au nom d'une autre.
+/// Comparaison insensible à la casse (noms NetBIOS/DNS).
+fn check_token_hostname(
+    token_host: Option<&str>,
     event: &ClientEvent,
-) -> Result<String, (StatusCode, Json<ErrorResponse>)> {
-    let hostname = event.hostname.as_deref().unwrap_or("unknown");
-    Ok(format!(
-        "hardware_{}",
-        Database::generate_session_id(&event.username, hostname, &event.timestamp)
-    ))
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let Some(token_host) = token_host else {
+        return Ok(());
+    };
+
+    let claimed = event.hostname.as_deref().unwrap_or("");
+    if !claimed.eq_ignore_ascii_case(token_host) {
+        tracing::warn!(
+            "Hostname mismatch: event claims {:?}, token issued to {}",
+            event.hostname,
+            token_host
+        );
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse::new(ErrorCode::HostnameMismatch, "Hostname does not match host token")),
+        ));
+    }
+
+    Ok(())
 }
 
-/// Extrait l'adresse IP réelle du client (support proxies/CDN)
+/// Désérialise le corps JSON d'une requête (après authentification)
+fn parse_json<T: DeserializeOwned>(body: &[u8]) -> Result<T, (StatusCode, Json<ErrorResponse>)> {
+    serde_json::from_slice(body).map_err(|e| {
+        tracing::warn!("Invalid JSON: {}", e);
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::with_details(
+                ErrorCode::BadJson,
+                format!("Invalid JSON: {}", e),
+                serde_json::json!({ "line": e.line(), "column": e.column() }),
+            )),
+        )
+    })
+}
+
+/// Désérialise un événement (élément d'un lot ou corps de POST /api/v1/events)
 ///
-/// Ordre de priorité (comme dans le PHP) :
-/// 1. CF-Connecting-IP (Cloudflare)
-/// 2. X-Forwarded-For (Load balancer/proxy)
-/// 3. REMOTE_ADDR (direct)
-fn extract_real_ip(headers: HeaderMap, addr: SocketAddr) -> String {
-    // 1. Cloudflare
-    if let Some(ip) = headers.get("cf-connecting-ip") {
-        if let Ok(ip_str) = ip.to_str() {
-            return ip_str.to_string();
+/// Un code d'action inconnu (client plus récent que le serveur) est rejeté avec
+/// INVALID_ACTION, comme une action désactivée : dans un lot, seul cet élément
+/// est refusé.
+fn parse_event(
+    state: &AppState,
+    value: serde_json::Value,
+) -> Result<ClientEvent, (StatusCode, Json<ErrorResponse>)> {
+    if let Some(code) = value.get("action").and_then(serde_json::Value::as_str) {
+        if serde_json::from_value::<Action>(code.into()).is_err() {
+            return Err(invalid_action(state, code));
         }
     }
 
-    // 2. X-Forwarded-For
-    if let Some(forwarded) = headers.get("x-forwarded-for") {
-        if let Ok(forwarded_str) = forwarded.to_str() {
-            if let Some(first_ip) = forwarded_str.split(',').next() {
-                return first_ip.trim().to_string();
+    serde_json::from_value(value).map_err(|e| {
+        tracing::warn!("Invalid JSON structure: {}", e);
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(ErrorCode::BadJson, format!("Invalid JSON structure: {}", e))),
+        )
+    })
+}
+
+/// Réponse d'erreur pour une action inconnue ou absente de `security.valid_actions`
+fn invalid_action(state: &AppState, code: &str) -> (StatusCode, Json<ErrorResponse>) {
+    tracing::warn!("Invalid action: {}", code);
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::with_details(
+            ErrorCode::InvalidAction,
+            format!("Invalid action: {}", code),
+            serde_json::json!({
+                "field": "action",
+                "value": code,
+                "allowed": state.config.security.valid_actions,
+            }),
+        )),
+    )
+}
+
+/// Vérifie que le User-Agent correspond à celui attendu des clients Winlog
+fn validate_user_agent(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+
+    if !user_agent.starts_with(&state.config.security.expected_user_agent) {
+        tracing::warn!("Invalid User-Agent: {}", user_agent);
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse::new(ErrorCode::Forbidden, "Invalid User-Agent")),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Valide un événement : champs obligatoires, action autorisée, timestamp ISO 8601
+fn validate_event(
+    state: &AppState,
+    event: &ClientEvent,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    // Validation de la structure JSON
+    let missing: Vec<&str> = [
+        ("username", &event.username),
+        ("timestamp", &event.timestamp),
+    ]
+    .into_iter()
+    .filter(|(_, value)| value.is_empty())
+    .map(|(field, _)| field)
+    .collect();
+    if !missing.is_empty() {
+        tracing::warn!("Invalid JSON structure: missing required fields {:?}", missing);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::with_details(
+                ErrorCode::MissingFields,
+                "Invalid JSON structure: missing required fields",
+                serde_json::json!({ "fields": missing }),
+            )),
+        ));
+    }
+
+    // Validation de l'action (code inconnu : déjà rejeté par parse_event)
+    if !state.config.security.valid_actions.contains(&event.action) {
+        return Err(invalid_action(state, event.action.code()));
+    }
+
+    // Validation du timestamp (format ISO 8601)
+    if chrono::DateTime::parse_from_rfc3339(&event.timestamp).is_err() {
+        tracing::warn!("Invalid timestamp format: {}", event.timestamp);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::with_details(
+                ErrorCode::BadTimestamp,
+                "Invalid timestamp format (expected ISO 8601)",
+                serde_json::json!({ "field": "timestamp", "value": event.timestamp }),
+            )),
+        ));
+    }
+
+    Ok(())
+}
+
+/// En-tête portant l'heure d'envoi du client (horodatage Unix en secondes)
+const SENT_AT_HEADER: &str = "x-winlog-sent-at";
+
+/// Mesure de l'écart d'horloge d'une machine sur une requête
+#[derive(Debug, Clone, Copy)]
+struct ClockSample {
+    /// Heure client - heure de réception (secondes, positif = horloge en avance)
+    skew_secs: i64,
+    received_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Écart d'horloge de la machine émettrice d'une requête
+///
+/// Heure d'envoi du client (`X-Winlog-Sent-At`, sinon horodatage signé
+/// `X-Winlog-Timestamp`) - heure de réception. Le timestamp des événements
+/// n'est pas utilisé : un événement renvoyé après des échecs ou rejoué depuis
+/// la file d'attente est en retard, sans que l'horloge soit fausse.
+///
+/// `None` si aucun en-tête n'est présent ou lisible (client antérieur).
+fn measure_clock_skew(
+    headers: &HeaderMap,
+    received_at: chrono::DateTime<chrono::Utc>,
+) -> Option<ClockSample> {
+    let sent_at: i64 = [SENT_AT_HEADER, auth::TIMESTAMP_HEADER]
+        .into_iter()
+        .find_map(|name| headers.get(name))?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(ClockSample {
+        skew_secs: sent_at - received_at.timestamp(),
+        received_at,
+    })
+}
+
+/// Applique `[clock] on_skew` à un événement si l'écart de sa machine dépasse
+/// la tolérance
+///
+/// - `flag` : écart conservé dans `clock_skew_secs`
+/// - `reject` : 422 CLOCK_SKEW
+/// - `correct` : timestamp décalé de l'écart de la machine (fuseau d'origine
+///   conservé, retard éventuel de l'événement préservé), timestamp d'origine
+///   dans `client_timestamp`
+fn apply_clock_policy(
+    clock: &ClockConfig,
+    event: &mut ClientEvent,
+    skew_secs: i64,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    if !clock.out_of_tolerance(skew_secs) {
+        return Ok(());
+    }
+
+    let hostname = event.hostname.as_deref().unwrap_or("unknown");
+    match clock.on_skew {
+        SkewPolicy::Flag => {
+            tracing::warn!(
+                "Clock skew of {}s on {} (max {}s): event flagged",
+                skew_secs,
+                hostname,
+                clock.max_skew_secs
+            );
+        }
+        SkewPolicy::Reject => {
+            tracing::warn!(
+                "Clock skew of {}s on {} (max {}s): event rejected",
+                skew_secs,
+                hostname,
+                clock.max_skew_secs
+            );
+            return Err(clock_skew_error(clock, skew_secs));
+        }
+        SkewPolicy::Correct => {
+            if let Ok(timestamp) = chrono::DateTime::parse_from_rfc3339(&event.timestamp) {
+                let corrected = (timestamp - chrono::Duration::seconds(skew_secs)).to_rfc3339();
+                tracing::warn!(
+                    "Clock skew of {}s on {} (max {}s): timestamp {} corrected to {}",
+                    skew_secs,
+                    hostname,
+                    clock.max_skew_secs,
+                    event.timestamp,
+                    corrected
+                );
+                event.client_timestamp = Some(std::mem::replace(&mut event.timestamp, corrected));
             }
         }
     }
+    event.clock_skew_secs = Some(skew_secs);
 
-    // 3. Adresse directe
-    addr.ip().to_string()
+    Ok(())
 }
 
-/// Health check endpoint (GET /health)
-pub async fn health_check() -> impl IntoResponse {
-    Json(serde_json::json!({
-        "status": "ok",
-        "service": "winlog-server",
-        "version": env!("CARGO_PKG_VERSION")
-    }))
+/// Erreur 422 CLOCK_SKEW (`[clock] on_skew = "reject"`)
+fn clock_skew_error(clock: &ClockConfig, skew_secs: i64) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(ErrorResponse::with_details(
+            ErrorCode::ClockSkew,
+            format!("Clock skew of {}s exceeds tolerance", skew_secs),
+            serde_json::json!({
+                "skew_secs": skew_secs,
+                "max_skew_secs": clock.max_skew_secs,
+            }),
+        )),
+    )
 }
 
-/// Liste les sessions actuellement ouvertes (GET /api/v1/sessions/current)
+/// Traite un événement validé : détection des renvois, écart d'horloge, logique
+/// de session puis insertion
 ///
-/// Retourne un tableau JSON de toutes les sessions qui ont une action='C'
+/// Toutes les requêtes s'exécutent sur `conn` (transaction ou point de
+/// sauvegarde ouvert par l'appelant) : un renvoi ne compte pas de mesure
+/// d'horloge, un événement refusé n'en laisse pas.
+///
+/// # Arguments
+/// * `sample` - Écart mesuré sur la requête, enregistré comme écart de la machine
+///   (`None` : dernier écart connu de la machine)
+async fn process_event(
+    conn: &mut SqliteConnection,
+    config: &Config,
+    event: &mut ClientEvent,
+    source_ip: &str,
+    sample: Option<ClockSample>,
+) -> Result<SuccessResponse, (StatusCode, Json<ErrorResponse>)> {
+    let sessions = &config.sessions;
+
+    // 1. Renvoi d'un événement déjà enregistré : réponse d'origine, aucune insertion
+    //    (fait avant la logique de session pour éviter une déconnexion auto parasite)
+    if let Some(event_id) = &event.event_id {
+        let stored = Database::find_event_by_event_id(conn, event_id)
+            .await
+            .map_err(database_error)?;
+
+        if let Some(stored) = stored {
+            tracing::info!(
+                "Duplicate event {} ignored (already stored as ID={})",
+                event_id,
+                stored.id
+            );
+            return Ok(SuccessResponse {
+                status: "success".to_string(),
+                message: "Data stored in database".to_string(),
+                event_id: stored.id,
+                session_uuid: stored.session_uuid,
+                action: stored.action,
+                username: stored.username,
+                archived: stored.archived,
+                pairing_changes: Vec::new(),
+            });
+        }
+    }
+
+    // 2. Écart d'horloge : mesure suivie par machine, puis marquage, refus ou correction
+    let skew_secs = match (sample, event.hostname.as_deref()) {
+        (Some(sample), Some(hostname)) => {
+            let out_of_tolerance = config.clock.out_of_tolerance(sample.skew_secs);
+            Database::record_clock_skew(
+                conn,
+                hostname,
+                sample.skew_secs,
+                sample.received_at,
+                out_of_tolerance,
+            )
+            .await
+            .map_err(database_error)?;
+            Some(sample.skew_secs)
+        }
+        (Some(sample), None) => Some(sample.skew_secs),
+        (None, Some(hostname)) => Database::host_clock_skew(conn, hostname)
+            .await
+            .map_err(database_error)?,
+        (None, None) => None,
+    };
+    if let Some(skew_secs) = skew_secs {
+        apply_clock_policy(&config.clock, event, skew_secs)?;
+    }
+    let event = &*event;
+
+    // 3. Traitement selon l'action
+    let (session_uuid, pairing_changes) = match event.action {
+        Action::Connect => handle_connection(conn, sessions, event, source_ip).await?,
+        Action::Disconnect => handle_disconnection(conn, event, source_ip).await?,
+        Action::Hardware => (handle_hardware(event), Vec::new()),
+        Action::Lock | Action::Unlock | Action::Heartbeat => {
+            (handle_session_activity(conn, event).await?, Vec::new())
+        }
+        Action::Boot | Action::Shutdown => (handle_host_event(event), Vec::new()),
+    };
+
+    // 4. Insertion de l'événement en base (events_history s'il précède la dernière rotation)
+    let inserted = Database::insert_event(conn, event, &session_uuid, source_ip)
+        .await
+        .map_err(database_error)?;
+
+    if inserted.archived {
+        tracing::info!(
+            "Late event stored in events_history: ID={} - {} - {} at {}",
+            inserted.id,
+            event.username,
+            event.action,
+            event.timestamp
+        );
+    }
+
+    // 5. Connexion (éventuellement reçue après sa déconnexion) : réappariement
+    //    des déconnexions orphelines du même user@host
+    if event.action == Action::Connect {
+        let hostname = event.hostname.as_deref().unwrap_or("unknown");
+        let repairs = Database::reconcile_orphans(
+            conn,
+            Some((&event.username, hostname)),
+            None,
+            OrphanRepairTrigger::LateConnection,
+        )
+        .await
+        .map_err(database_error)?;
+        for repair in &repairs {
+            tracing::info!(
+                "Orphan disconnection {} at {} re-paired with session {}",
+                repair.orphan_uuid,
+                repair.disconnect_timestamp,
+                repair.session_uuid
+            );
+        }
+    }
+
+    Ok(SuccessResponse {
+        status: "success".to_string(),
+        message: "Data stored in database".to_string(),
+        event_id: inserted.id,
+        session_uuid,
+        action: event.action,
+        username: event.username.clone(),
+        archived: inserted.archived,
+        pairing_changes,
+    })
+}
+
+/// Convertit une erreur SQLx en réponse HTTP (détail uniquement dans les logs)
+fn database_error(e: sqlx::Error) -> (StatusCode, Json<ErrorResponse>) {
+    tracing::error!("Database error: {}", e);
+    database_error_response(&e)
+}
+
+/// Réponse HTTP d'une erreur SQLx, selon qu'un nouvel essai peut aboutir
+///
+/// Base occupée ou verrouillée, erreur d'entrée/sortie, pool épuisé : 503
+/// `DB_UNAVAILABLE` (transitoire). Toute autre erreur (contrainte, requête,
+/// donnée invalide) donnerait le même résultat : 500 `DATABASE_ERROR`.
+fn database_error_response(e: &sqlx::Error) -> (StatusCode, Json<ErrorResponse>) {
+    let transient = match e {
+        sqlx::Error::Io(_)
+        | sqlx::Error::PoolTimedOut
+        | sqlx::Error::PoolClosed
+        | sqlx::Error::WorkerCrashed => true,
+        // Code étendu SQLite : code primaire dans l'octet de poids faible
+        // (SQLITE_BUSY = 5, SQLITE_LOCKED = 6, SQLITE_IOERR = 10)
+        sqlx::Error::Database(db) => db
+            .code()
+            .and_then(|code| code.parse::<i32>().ok())
+            .is_some_and(|code| matches!(code & 0xff, 5 | 6 | 10)),
+        _ => false,
+    };
+
+    if transient {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ErrorResponse::new(ErrorCode::DbUnavailable, "Database unavailable")),
+        )
+    } else {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new(ErrorCode::DatabaseError, "Database error")),
+        )
+    }
+}
+
+/// Consigne un changement d'appariement dû à un événement reçu en retard
+fn report_pairing_change(change: &PairingChange) {
+    tracing::warn!(
+        "Pairing changed ({}): session {} now closed at {} (was {})",
+        change.reason,
+        change.session_uuid,
+        change.new_disconnect,
+        change.previous_disconnect
+    );
+}
+
+/// Traite une connexion (action='C')
+///
+/// Logique :
+/// 1. Poste à place unique (`SeatPolicy::SingleSeat`) : fermer les sessions
+///    ouvertes des autres utilisateurs à l'heure de cette connexion
+/// 2. Chercher si une session ouverte aujourd'hui a commencé avant cette connexion
+/// 3. Si oui, la fermer automatiquement (déconnexion auto)
+/// 4. Générer un nouveau session_uuid et créer sa ligne dans la table sessions
+///
+/// Connexion reçue en retard (file d'attente du client) :
+/// - si une session déjà fermée l'englobe, cette connexion reprend sa déconnexion
+///   et la session englobante est fermée automatiquement juste avant elle
+/// - sinon, si une connexion ultérieure existe, la nouvelle session est fermée
+///   automatiquement juste avant celle-ci
+async fn handle_connection(
+    conn: &mut SqliteConnection,
+    sessions: &SessionsConfig,
+    event: &ClientEvent,
+    source_ip: &str,
+) -> Result<(String, Vec<PairingChange>), (StatusCode, Json<ErrorResponse>)> {
+    let hostname = event.hostname.as_deref().unwrap_or("unknown");
+    let session_uuid = Database::generate_session_id(&event.timestamp);
+    let mut pairing_changes = Vec::new();
+
+    // Poste à place unique : la connexion met fin aux sessions des autres utilisateurs
+    if sessions.seat_policy(hostname) == SeatPolicy::SingleSeat {
+        let closed = Database::close_other_host_sessions(conn, event, hostname, source_ip)
+            .await
+            .map_err(database_error)?;
+        for session in closed {
+            tracing::warn!(
+                "Single-seat host {}: session of {} closed by logon of {} ({})",
+                hostname,
+                session.username,
+                event.username,
+                session.session_uuid
+            );
+        }
+    }
+
+    // Chercher une session ouverte récente (y compris commencée la veille)
+    let open_session = Database::find_recent_open_session(conn, &event.username, hostname, &event.timestamp)
+        .await
+        .map_err(database_error)?;
+
+    // Nouvelle session (après la recherche, pour ne pas se trouver elle-même)
+    Database::insert_session(conn, event, &session_uuid, source_ip)
+        .await
+        .map_err(database_error)?;
+
+    // Si session ouverte trouvée, la fermer automatiquement
+    if let Some(session) = open_session {
+        tracing::warn!(
+            "Session ouverte détectée pour {}@{} - fermeture automatique",
+            event.username,
+            hostname
+        );
+
+        Database::insert_auto_disconnect(conn, event, &session.session_uuid, source_ip, &event.timestamp)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to insert auto-disconnect: {}", e);
+                database_error_response(&e)
+            })?;
+
+        return Ok((session_uuid, pairing_changes));
+    }
+
+    // Connexion tardive au milieu d'une session déjà fermée : la déconnexion lui revient
+    let spanning = Database::find_session_closed_after(conn, &event.username, hostname, &event.timestamp)
+        .await
+        .map_err(database_error)?;
+
+    if let Some(spanning) = spanning {
+        Database::reassign_event(conn, &spanning.disconnect_source, spanning.disconnect_id, &session_uuid)
+            .await
+            .map_err(database_error)?;
+        Database::close_session(
+            conn,
+            &session_uuid,
+            &spanning.disconnect_timestamp,
+            spanning.end_ip.as_deref(),
+            spanning.close_reason,
+        )
+        .await
+        .map_err(database_error)?;
+        let new_disconnect = Database::insert_auto_disconnect(
+            conn,
+            event,
+            &spanning.session_uuid,
+            source_ip,
+            &event.timestamp,
+        )
+        .await
+        .map_err(database_error)?;
+
+        let change = PairingChange {
+            session_uuid: spanning.session_uuid,
+            previous_disconnect: spanning.disconnect_timestamp,
+            new_disconnect,
+            reason: "late_connection".to_string(),
+        };
+        report_pairing_change(&change);
+        pairing_changes.push(change);
+        return Ok((session_uuid, pairing_changes));
+    }
+
+    // Connexion tardive suivie d'une autre connexion : la session a pris fin avant
+    let next_connection = Database::find_next_connection(conn, &event.username, hostname, &event.timestamp)
+        .await
+        .map_err(database_error)?;
+
+    if let Some(next_connection) = next_connection {
+        tracing::warn!(
+            "Late connection for {}@{} at {} - closed before next connection at {}",
+            event.username,
+            hostname,
+            event.timestamp,
+            next_connection
+        );
+        Database::insert_auto_disconnect(conn, event, &session_uuid, source_ip, &next_connection)
+            .await
+            .map_err(database_error)?;
+    }
+
+    Ok((session_uuid, pairing_changes))
+}
+
+/// Traite une déconnexion (action='D')
+///
+/// Logique :
+/// 1. Chercher la dernière session ouverte avant cette déconnexion
+///    (y compris archivée pour une déconnexion reçue en retard)
+/// 2. Si trouvée, utiliser son UUID et la fermer dans la table sessions
+/// 3. Sinon, si la session en cours à cet instant a été fermée automatiquement
+///    plus tard (connexion suivante, expiration ou durée maximale), ou si la
+///    dernière session a expiré avant cet instant, cette déconnexion réelle
+///    remplace la déconnexion automatique
+/// 4. Sinon, générer un UUID "orphan_"
+async fn handle_disconnection(
+    conn: &mut SqliteConnection,
+    event: &ClientEvent,
+    source_ip: &str,
+) -> Result<(String, Vec<PairingChange>), (StatusCode, Json<ErrorResponse>)> {
+    let hostname = event.hostname.as_deref().unwrap_or("unknown");
+
+    // Chercher dernière session ouverte
+    let session_uuid = Database::find_last_open_session(conn, &event.username, hostname, &event.timestamp)
+        .await
+        .map_err(database_error)?;
+
+    if let Some(uuid) = session_uuid {
+        Database::close_session(conn, &uuid, &event.timestamp, Some(source_ip), CloseReason::Explicit)
+            .await
+            .map_err(database_error)?;
+        return Ok((uuid, Vec::new()));
+    }
+
+    // Déconnexion tardive d'une session fermée automatiquement entre-temps
+    // (une déconnexion postérieure à la durée maximale ne prolonge pas la session)
+    let closed = Database::find_session_closed_after(conn, &event.username, hostname, &event.timestamp)
+        .await
+        .map_err(database_error)?
+        .filter(|closed| {
+            matches!(
+                closed.close_reason,
+                CloseReason::AutoClosed | CloseReason::TimedOut | CloseReason::PolicyClosed
+            )
+        });
+
+    // Sinon, déconnexion réelle d'une session expirée avant elle (signaux interrompus)
+    let closed = match closed {
+        Some(closed) => Some(closed),
+        None => Database::find_timed_out_session_before(conn, &event.username, hostname, &event.timestamp)
+            .await
+            .map_err(database_error)?,
+    };
+
+    if let Some(closed) = closed {
+        Database::delete_auto_disconnect(conn, &closed.disconnect_source, closed.disconnect_id)
+            .await
+            .map_err(database_error)?;
+        Database::close_session(
+            conn,
+            &closed.session_uuid,
+            &event.timestamp,
+            Some(source_ip),
+            CloseReason::Explicit,
+        )
+        .await
+        .map_err(database_error)?;
+
+        let change = PairingChange {
+            session_uuid: closed.session_uuid.clone(),
+            previous_disconnect: closed.disconnect_timestamp,
+            new_disconnect: event.timestamp.clone(),
+            reason: "late_disconnect".to_string(),
+        };
+        report_pairing_change(&change);
+        return Ok((closed.session_uuid, vec![change]));
+    }
+
+    tracing::warn!(
+        "Aucune session ouverte trouvée pour {}@{}",
+        event.username,
+        hostname
+    );
+    // UUID orphelin
+    Ok((format!("orphan_{}", Database::generate_session_id(&event.timestamp)), Vec::new()))
+}
+
+/// Traite un événement d'activité d'une session ouverte (actions 'L', 'U' et 'H')
+///
+/// Logique :
+/// 1. Rattacher l'événement à la dernière session ouverte avant lui
+/// 2. Verrouillage : noter le début de l'inactivité ; déverrouillage : ajouter
+///    la durée écoulée depuis le verrouillage au temps verrouillé de la session ;
+///    signal de présence : dater le dernier signe de vie (expiration, module `expiry`)
+/// 3. Sans session ouverte, générer un UUID "orphan_"
+async fn handle_session_activity(
+    conn: &mut SqliteConnection,
+    event: &ClientEvent,
+) -> Result<String, (StatusCode, Json<ErrorResponse>)> {
+    let hostname = event.hostname.as_deref().unwrap_or("unknown");
+
+    let session_uuid = Database::find_last_open_session(conn, &event.username, hostname, &event.timestamp)
+        .await
+        .map_err(database_error)?;
+
+    let Some(uuid) = session_uuid else {
+        tracing::warn!(
+            "Aucune session ouverte trouvée pour {}@{} (action {})",
+            event.username,
+            hostname,
+            event.action
+        );
+        return Ok(format!("orphan_{}", Database::generate_session_id(&event.timestamp)));
+    };
+
+    match event.action {
+        Action::Lock => Database::lock_session(conn, &uuid, &event.timestamp)
+            .await
+            .map_err(database_error)?,
+        Action::Unlock => Database::unlock_session(conn, &uuid, &event.timestamp)
+            .await
+            .map_err(database_error)?,
+        Action::Heartbeat => Database::record_heartbeat(conn, &uuid, &event.timestamp)
+            .await
+            .map_err(database_error)?,
+        _ => {}
+    }
+
+    Ok(uuid)
+}
+
+/// Traite un événement de la machine (actions 'B' et 'S')
+///
+/// Logique : Génère simplement un UUID préfixé "host_" (hors session utilisateur)
+fn handle_host_event(event: &ClientEvent) -> String {
+    format!("host_{}", Database::generate_session_id(&event.timestamp))
+}
+
+/// Traite un événement matériel (action='M')
+///
+/// Logique : Génère simplement un UUID préfixé "hardware_"
+fn handle_hardware(event: &ClientEvent) -> String {
+    format!("hardware_{}", Database::generate_session_id(&event.timestamp))
+}
+
+/// Extrait l'adresse IP réelle du client (support proxies/CDN)
+///
+/// Les en-têtes de transfert ne sont pris en compte que si la connexion provient
+/// d'un proxy listé dans `server.trusted_proxies` : un client direct ne peut pas
+/// falsifier son adresse.
+///
+/// Ordre de priorité (connexion depuis un proxy de confiance) :
+/// 1. CF-Connecting-IP (Cloudflare)
+/// 2. Forwarded (RFC 7239), paramètre `for=`, lu de droite à gauche ; s'il est
+///    présent, X-Forwarded-For est ignoré (le proxy qui l'émet peut transmettre
+///    tel quel un X-Forwarded-For forgé par le client)
+/// 3. X-Forwarded-For, lu de droite à gauche
+/// 4. REMOTE_ADDR (direct)
+///
+/// Les chaînes Forwarded et X-Forwarded-For retiennent la première adresse qui
+/// n'est pas un proxy de confiance (les entrées de gauche sont contrôlées par
+/// le client). L'adresse de la connexion elle-même est conservée à part (`peer_ip`).
+fn extract_real_ip(server: &ServerConfig, headers: &HeaderMap, addr: SocketAddr) -> String {
+    let peer = addr.ip().to_canonical();
+    if !server.is_trusted_proxy(&peer) {
+        return peer.to_string();
+    }
+
+    // 1. Cloudflare
+    if let Some(ip) = headers
+        .get("cf-connecting-ip")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<IpAddr>().ok())
+    {
+        return ip.to_canonical().to_string();
+    }
+
+    // 2. Forwarded (toutes les occurrences de l'en-tête, dans l'ordre)
+    let forwarded: Vec<Option<IpAddr>> = headers
+        .get_all(header::FORWARDED)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(forwarded_for)
+        .collect();
+    if !forwarded.is_empty() {
+        return first_untrusted_hop(server, &forwarded).unwrap_or(peer).to_string();
+    }
+
+    // 3. X-Forwarded-For (toutes les occurrences de l'en-tête, dans l'ordre)
+    let hops: Vec<Option<IpAddr>> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|hop| hop.trim().parse::<IpAddr>().ok())
+        .collect();
+    if let Some(ip) = first_untrusted_hop(server, &hops) {
+        return ip.to_string();
+    }
+
+    // 4. Adresse directe
+    peer.to_string()
+}
+
+/// Première adresse d'une chaîne de proxys, lue de droite à gauche, qui n'est
+/// pas un proxy de confiance
+///
+/// Une entrée illisible (`None`) rend la chaîne non fiable au-delà : `None`.
+fn first_untrusted_hop(server: &ServerConfig, hops: &[Option<IpAddr>]) -> Option<IpAddr> {
+    for hop in hops.iter().rev() {
+        match hop.map(|ip| ip.to_canonical()) {
+            Some(ip) if server.is_trusted_proxy(&ip) => continue,
+            Some(ip) => return Some(ip),
+            None => return None,
+        }
+    }
+    None
+}
+
+/// Adresse du paramètre `for=` d'un élément de l'en-tête Forwarded (RFC 7239)
+///
+/// Formes acceptées : `for=192.0.2.43`, `for="192.0.2.43:4711"`,
+/// `for="[2001:db8::1]"`, `for="[2001:db8::1]:4711"`. `None` si le paramètre
+/// est absent, `unknown` ou obfusqué (`_hidden`).
+fn forwarded_for(element: &str) -> Option<IpAddr> {
+    let value = element.split(';').find_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        name.trim().eq_ignore_ascii_case("for").then(|| value.trim())
+    })?;
+    let value = value.trim_matches('"');
+
+    // IPv6 entre crochets, port éventuel après le crochet fermant
+    if let Some(rest) = value.strip_prefix('[') {
+        let (ip, _) = rest.split_once(']')?;
+        return ip.parse().ok();
+    }
+    // IPv4 avec port éventuel (une IPv6 sans crochets n'est pas conforme, mais lue telle quelle)
+    match value.split_once(':') {
+        Some((ip, port)) if !port.contains(':') => ip.parse().ok(),
+        _ => value.parse().ok(),
+    }
+}
+
+/// Health check endpoint (GET /health)
+///
+/// Expose également les compteurs de la limitation de débit (`[limits]`).
+pub async fn health_check(State(state): State<AppState>) -> impl IntoResponse {
+    let limits = &state.config.limits;
+    Json(serde_json::json!({
+        "status": "ok",
+        "service": "winlog-server",
+        "version": env!("CARGO_PKG_VERSION"),
+        "rate_limits": {
+            "per_ip": {
+                "per_minute": limits.per_ip_per_minute,
+                "burst": limits.per_ip_burst,
+                "rejected": state.limits.per_ip.rejected_count()
+            },
+            "per_host": {
+                "per_minute": limits.per_host_per_minute,
+                "burst": limits.per_host_burst,
+                "rejected": state.limits.per_host.rejected_count()
+            }
+        }
+    }))
+}
+
+/// Liste les sessions actuellement ouvertes (GET /api/v1/sessions/current)
+///
+/// Retourne un tableau JSON de toutes les sessions qui ont une action='C'
 /// sans action='D' correspondante, triées par hostname puis timestamp.
 ///
 /// # Réponse
@@ -295,13 +1381,756 @@ pub async fn get_current_sessions(
         .await
         .map_err(|e| {
             tracing::error!("Database error while fetching current sessions: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse::new("Database error")),
-            )
+            database_error_response(&e)
         })?;
     
     tracing::info!("Found {} open sessions", sessions.len());
     
     Ok(Json(sessions))
 }
+
+
+/// Enrôle une machine et lui délivre un jeton (POST /api/v1/enroll)
+///
+/// Requiert l'en-tête `X-Winlog-Enrollment-Key` égal à `security.enrollment_key`.
+/// Le jeton n'est retourné qu'une fois : seule son empreinte est conservée.
+///
+/// # Réponse
+/// - 200 OK : `EnrollResponse` (jeton en clair)
+/// - 400 Bad Request : hostname vide
+/// - 401 Unauthorized : clé d'enrôlement invalide
+/// - 403 Forbidden : enrôlement désactivé (aucune clé configurée)
+/// - 409 Conflict : la machine possède déjà un jeton actif (le révoquer d'abord)
+pub async fn enroll_host(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<EnrollRequest>,
+) -> Result<Json<EnrollResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let Some(expected_key) = state.config.security.enrollment_key.as_deref() else {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse::new(ErrorCode::Forbidden, "Enrollment disabled")),
+        ));
+    };
+
+    let presented_key = headers
+        .get(auth::ENROLLMENT_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    if !auth::secret_matches(presented_key, expected_key) {
+        tracing::warn!("Enrollment rejected for {}: invalid enrollment key", request.hostname);
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse::new(ErrorCode::Unauthorized, "Invalid enrollment key")),
+        ));
+    }
+
+    let hostname = request.hostname.trim();
+    if hostname.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::with_details(
+                ErrorCode::MissingFields,
+                "Invalid JSON structure: missing required fields",
+                serde_json::json!({ "fields": ["hostname"] }),
+            )),
+        ));
+    }
+
+    let token = auth::generate_token();
+    let token_id = state.db
+        .insert_host_token(hostname, &auth::hash_token(&token))
+        .await
+        .map_err(|e| {
+            if e.as_database_error().is_some_and(|db| db.is_unique_violation()) {
+                tracing::warn!("Enrollment rejected for {}: already enrolled", hostname);
+                return (
+                    StatusCode::CONFLICT,
+                    Json(ErrorResponse::new(ErrorCode::AlreadyEnrolled, "Host already enrolled (revoke the existing token first)")),
+                );
+            }
+            tracing::error!("Database error: {}", e);
+            database_error_response(&e)
+        })?;
+
+    tracing::info!("Host enrolled: {} (token ID={})", hostname, token_id);
+
+    Ok(Json(EnrollResponse {
+        token_id,
+        hostname: hostname.to_string(),
+        token,
+    }))
+}
+
+/// Liste les jetons d'enrôlement (GET /api/v1/admin/tokens)
+///
+/// Requiert `Authorization: Bearer <security.admin_token>`.
+pub async fn list_host_tokens(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<HostToken>>, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&state, &headers)?;
+
+    let tokens = state.db
+        .list_host_tokens()
+        .await
+        .map_err(|e| {
+            tracing::error!("Database error while listing host tokens: {}", e);
+            database_error_response(&e)
+        })?;
+
+    Ok(Json(tokens))
+}
+
+/// Révoque un jeton d'enrôlement (POST /api/v1/admin/tokens/:id/revoke)
+///
+/// Requiert `Authorization: Bearer <security.admin_token>`. La machine devra
+/// être ré-enrôlée pour émettre à nouveau.
+///
+/// # Réponse
+/// - 200 OK : jeton révoqué
+/// - 404 Not Found : jeton inexistant ou déjà révoqué
+pub async fn revoke_host_token(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(token_id): Path<i64>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&state, &headers)?;
+
+    let revoked = state.db
+        .revoke_host_token(token_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Database error while revoking token {}: {}", token_id, e);
+            database_error_response(&e)
+        })?;
+
+    if !revoked {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new(ErrorCode::NotFound, "Token not found or already revoked")),
+        ));
+    }
+
+    tracing::info!("Host token revoked: ID={}", token_id);
+
+    Ok(Json(serde_json::json!({
+        "status": "success",
+        "token_id": token_id
+    })))
+}
+
+/// Déclenche une rotation immédiate (POST /api/v1/admin/rotate)
+///
+/// Requiert `Authorization: Bearer <security.admin_token>`. La rotation est
+/// journalisée dans la table rotations (`triggered_by = manual`).
+///
+/// # Réponse
+/// - 200 OK : ligne de la table rotations (comptages avant/après)
+/// - 500 Internal Server Error : rotation annulée (échec journalisé)
+pub async fn trigger_rotation(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Rotation>, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&state, &headers)?;
+
+    let rotation = rotation::run(&state.db, &state.config.rotation, RotationTrigger::Manual)
+        .await
+        .map_err(database_error)?;
+
+    Ok(Json(rotation))
+}
+
+/// Bilan des déconnexions orphelines (GET /api/v1/admin/orphans)
+///
+/// Déconnexions non réappariées par machine et nombre de réparations
+/// effectuées. Requiert le jeton d'administration.
+pub async fn get_orphan_stats(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<OrphanStats>, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&state, &headers)?;
+
+    let stats = state.db.orphan_stats().await.map_err(database_error)?;
+
+    Ok(Json(stats))
+}
+
+/// Écart d'horloge des machines (GET /api/v1/admin/clocks)
+///
+/// Dernière mesure et bilan de chaque machine, les plus décalées en premier
+/// (NTP en panne, pile CMOS usée). Requiert le jeton d'administration.
+pub async fn list_host_clocks(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<HostClock>>, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&state, &headers)?;
+
+    let clocks = state.db.list_host_clocks().await.map_err(database_error)?;
+
+    Ok(Json(clocks))
+}
+
+/// Vérifie le jeton d'administration (`Authorization: Bearer <admin_token>`)
+fn require_admin(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let Some(admin_token) = state.config.security.admin_token.as_deref() else {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse::new(ErrorCode::Forbidden, "Administration disabled")),
+        ));
+    };
+
+    let presented = auth::bearer_token(headers).unwrap_or("");
+    if !auth::secret_matches(presented, admin_token) {
+        tracing::warn!("Admin request rejected: invalid admin token");
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse::new(ErrorCode::Unauthorized, "Invalid admin token")),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::TrustedProxy;
+    use crate::database::tests::{event, minutes_ago, TestDb};
+
+    const PEER_PROXY: &str = "10.0.0.5:443";
+
+    fn server(trusted: &[&str]) -> ServerConfig {
+        ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 3000,
+            max_batch_size: 500,
+            tls: None,
+            trusted_proxies: trusted
+                .iter()
+                .map(|proxy| TrustedProxy(proxy.parse().expect("réseau de test")))
+                .collect(),
+        }
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, value.parse().expect("en-tête de test"));
+        }
+        headers
+    }
+
+    fn real_ip(trusted: &[&str], pairs: &[(&'static str, &str)], peer: &str) -> String {
+        extract_real_ip(&server(trusted), &headers(pairs), peer.parse().expect("adresse de test"))
+    }
+
+    #[test]
+    fn untrusted_peer_ignores_forwarding_headers() {
+        let ip = real_ip(
+            &[],
+            &[("x-forwarded-for", "203.0.113.7"), ("cf-connecting-ip", "203.0.113.8")],
+            PEER_PROXY,
+        );
+        assert_eq!(ip, "10.0.0.5");
+    }
+
+    #[test]
+    fn cloudflare_header_wins_behind_trusted_proxy() {
+        let ip = real_ip(
+            &["10.0.0.0/8"],
+            &[("cf-connecting-ip", "203.0.113.8"), ("x-forwarded-for", "203.0.113.7")],
+            PEER_PROXY,
+        );
+        assert_eq!(ip, "203.0.113.8");
+    }
+
+    #[test]
+    fn x_forwarded_for_is_read_right_to_left() {
+        // Le client peut forger les entrées de gauche : seule la première adresse
+        // non fiable en partant de la droite est retenue
+        let ip = real_ip(
+            &["10.0.0.0/8"],
+            &[("x-forwarded-for", "1.2.3.4, 203.0.113.7, 10.0.0.9")],
+            PEER_PROXY,
+        );
+        assert_eq!(ip, "203.0.113.7");
+    }
+
+    #[test]
+    fn x_forwarded_for_spans_repeated_headers() {
+        let ip = real_ip(
+            &["10.0.0.0/8"],
+            &[("x-forwarded-for", "203.0.113.7"), ("x-forwarded-for", "10.0.0.9")],
+            PEER_PROXY,
+        );
+        assert_eq!(ip, "203.0.113.7");
+    }
+
+    #[test]
+    fn unreadable_hop_falls_back_to_peer() {
+        let ip = real_ip(&["10.0.0.0/8"], &[("x-forwarded-for", "203.0.113.7, garbage")], PEER_PROXY);
+        assert_eq!(ip, "10.0.0.5");
+    }
+
+    #[test]
+    fn only_trusted_hops_falls_back_to_peer() {
+        let ip = real_ip(&["10.0.0.0/8"], &[("x-forwarded-for", "10.0.0.8, 10.0.0.9")], PEER_PROXY);
+        assert_eq!(ip, "10.0.0.5");
+    }
+
+    #[test]
+    fn ipv4_mapped_peer_is_canonical() {
+        let ip = real_ip(&["10.0.0.0/8"], &[("x-forwarded-for", "203.0.113.7")], "[::ffff:10.0.0.5]:443");
+        assert_eq!(ip, "203.0.113.7");
+        assert_eq!(real_ip(&[], &[], "[::ffff:192.0.2.1]:443"), "192.0.2.1");
+    }
+
+    #[test]
+    fn forwarded_takes_precedence_over_x_forwarded_for() {
+        let ip = real_ip(
+            &["10.0.0.0/8"],
+            &[
+                ("forwarded", "for=198.51.100.2;proto=https, for=10.0.0.9"),
+                ("x-forwarded-for", "203.0.113.7"),
+            ],
+            PEER_PROXY,
+        );
+        assert_eq!(ip, "198.51.100.2");
+    }
+
+    #[test]
+    fn forwarded_unknown_does_not_fall_back_to_x_forwarded_for() {
+        let ip = real_ip(
+            &["10.0.0.0/8"],
+            &[("forwarded", "for=unknown"), ("x-forwarded-for", "203.0.113.7")],
+            PEER_PROXY,
+        );
+        assert_eq!(ip, "10.0.0.5");
+    }
+
+    #[test]
+    fn forwarded_for_parses_rfc7239_forms() {
+        let ip = |s: &str| s.parse::<IpAddr>().ok();
+        assert_eq!(forwarded_for("for=192.0.2.43"), ip("192.0.2.43"));
+        assert_eq!(forwarded_for("For=\"192.0.2.43:4711\""), ip("192.0.2.43"));
+        assert_eq!(forwarded_for("for=\"[2001:db8:cafe::17]:4711\""), ip("2001:db8:cafe::17"));
+        assert_eq!(forwarded_for("for=\"[2001:db8:cafe::17]\""), ip("2001:db8:cafe::17"));
+        assert_eq!(forwarded_for(" proto=https; for=198.51.100.2 ;by=10.0.0.5"), ip("198.51.100.2"));
+    }
+
+    #[test]
+    fn forwarded_for_rejects_obfuscated_and_missing_values() {
+        assert_eq!(forwarded_for("for=unknown"), None);
+        assert_eq!(forwarded_for("for=_hidden"), None);
+        assert_eq!(forwarded_for("proto=https;by=10.0.0.5"), None);
+        assert_eq!(forwarded_for("for=\"[2001:db8::1\""), None);
+    }
+
+    fn clock(max_skew_secs: u64, on_skew: SkewPolicy) -> ClockConfig {
+        ClockConfig { max_skew_secs, on_skew }
+    }
+
+    fn logon(timestamp: &str) -> ClientEvent {
+        event("C", "alice", "PC-01", timestamp)
+    }
+
+    #[test]
+    fn clock_skew_within_tolerance_leaves_event_untouched() {
+        for policy in [SkewPolicy::Flag, SkewPolicy::Reject, SkewPolicy::Correct] {
+            let mut event = logon("2026-10-17T10:00:00+02:00");
+            assert!(apply_clock_policy(&clock(300, policy), &mut event, -300).is_ok());
+            assert_eq!(event.timestamp, "2026-10-17T10:00:00+02:00");
+            assert_eq!(event.clock_skew_secs, None);
+            assert_eq!(event.client_timestamp, None);
+        }
+    }
+
+    #[test]
+    fn clock_skew_flag_keeps_timestamp() {
+        let mut event = logon("2026-10-17T10:00:00+02:00");
+        assert!(apply_clock_policy(&clock(300, SkewPolicy::Flag), &mut event, 301).is_ok());
+        assert_eq!(event.timestamp, "2026-10-17T10:00:00+02:00");
+        assert_eq!(event.clock_skew_secs, Some(301));
+        assert_eq!(event.client_timestamp, None);
+    }
+
+    #[test]
+    fn clock_skew_reject_returns_422_with_details() {
+        let mut event = logon("2026-10-17T10:00:00+02:00");
+        let (status, Json(error)) =
+            apply_clock_policy(&clock(300, SkewPolicy::Reject), &mut event, -3600).expect_err("refusé");
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error.code, ErrorCode::ClockSkew);
+        assert_eq!(
+            error.details,
+            Some(serde_json::json!({ "skew_secs": -3600, "max_skew_secs": 300 }))
+        );
+    }
+
+    #[test]
+    fn clock_skew_correct_shifts_timestamp_and_keeps_original() {
+        // Horloge en retard d'une heure : l'événement est recalé, fuseau conservé
+        let mut event = logon("2026-10-17T10:00:00+02:00");
+        assert!(apply_clock_policy(&clock(300, SkewPolicy::Correct), &mut event, -3600).is_ok());
+        assert_eq!(event.timestamp, "2026-10-17T11:00:00+02:00");
+        assert_eq!(event.client_timestamp.as_deref(), Some("2026-10-17T10:00:00+02:00"));
+        assert_eq!(event.clock_skew_secs, Some(-3600));
+    }
+
+    #[test]
+    fn clock_skew_correct_ignores_unparsable_timestamp() {
+        let mut event = logon("not a timestamp");
+        assert!(apply_clock_policy(&clock(300, SkewPolicy::Correct), &mut event, 3600).is_ok());
+        assert_eq!(event.timestamp, "not a timestamp");
+        assert_eq!(event.client_timestamp, None);
+        assert_eq!(event.clock_skew_secs, Some(3600));
+    }
+
+    #[test]
+    fn clock_skew_is_measured_from_send_time() {
+        let received_at = chrono::DateTime::from_timestamp(1_790_000_000, 0).expect("instant de test");
+
+        let sample = measure_clock_skew(&headers(&[(SENT_AT_HEADER, "1790000042")]), received_at);
+        assert_eq!(sample.map(|s| s.skew_secs), Some(42));
+
+        // Sans X-Winlog-Sent-At : horodatage signé
+        let sample = measure_clock_skew(&headers(&[(auth::TIMESTAMP_HEADER, "1789999700")]), received_at);
+        assert_eq!(sample.map(|s| s.skew_secs), Some(-300));
+
+        // X-Winlog-Sent-At prioritaire
+        let sample = measure_clock_skew(
+            &headers(&[(auth::TIMESTAMP_HEADER, "1789999700"), (SENT_AT_HEADER, "1790000000")]),
+            received_at,
+        );
+        assert_eq!(sample.map(|s| s.skew_secs), Some(0));
+
+        assert!(measure_clock_skew(&headers(&[]), received_at).is_none());
+        assert!(measure_clock_skew(&headers(&[(SENT_AT_HEADER, "hier")]), received_at).is_none());
+    }
+
+    const SINGLE_SEAT_LAB: &str = r#"
+        [[sessions.host_groups]]
+        name = "salles-tp"
+        hosts = ["TP-*"]
+        seat = "single_seat"
+    "#;
+
+    /// Sections obligatoires de config.toml ; `extra` ajoute les sections testées
+    fn test_config(extra: &str) -> Config {
+        let content = format!(
+            r#"
+            [server]
+            host = "127.0.0.1"
+            port = 3000
+
+            [database]
+            path = "unused.db"
+            pragma_journal_mode = "WAL"
+            pragma_synchronous = "NORMAL"
+            pragma_busy_timeout = 5000
+            pragma_cache_size = 1000
+
+            [security]
+            expected_user_agent = "Winlog/0.1.0"
+            valid_actions = ["C", "D", "M", "L", "U", "B", "S", "H"]
+
+            [logging]
+            level = "info"
+            format = "compact"
+
+            {}
+            "#,
+            extra
+        );
+        toml::from_str(&content).expect("configuration de test")
+    }
+
+    /// Traite un événement avec une mesure d'horloge ; transaction annulée en cas d'erreur
+    async fn try_send(
+        test: &TestDb,
+        config: &Config,
+        mut event: ClientEvent,
+        sample: Option<ClockSample>,
+    ) -> Result<SuccessResponse, (StatusCode, Json<ErrorResponse>)> {
+        let mut tx = test.db.begin().await.expect("transaction");
+        let response = process_event(&mut tx, config, &mut event, "10.0.0.1", sample).await?;
+        tx.commit().await.expect("validation");
+        Ok(response)
+    }
+
+    /// Traite un événement comme POST /api/v1/events (sans mesure d'horloge)
+    async fn send(test: &TestDb, config: &Config, event: ClientEvent) -> SuccessResponse {
+        try_send(test, config, event, None)
+            .await
+            .unwrap_or_else(|(status, Json(error))| panic!("{}: {}", status, error.error))
+    }
+
+    #[tokio::test]
+    async fn single_seat_logon_closes_other_users_sessions() {
+        let test = TestDb::new().await;
+        let config = test_config(SINGLE_SEAT_LAB);
+
+        let alice = send(&test, &config, event("C", "alice", "TP-01", &minutes_ago(60))).await.session_uuid;
+        let bob_at = minutes_ago(10);
+        let bob = send(&test, &config, event("C", "bob", "TP-01", &bob_at)).await.session_uuid;
+        assert_ne!(alice, bob);
+
+        assert_eq!(test.session_end(&alice).await, (Some(bob_at.clone()), Some("auto_closed".to_string())));
+        assert_eq!(test.server_disconnects(&alice).await, vec![(bob_at, Some("auto_closed".to_string()))]);
+        assert_eq!(test.session_end(&bob).await, (None, None));
+    }
+
+    #[tokio::test]
+    async fn multi_seat_host_keeps_concurrent_sessions() {
+        let test = TestDb::new().await;
+        let config = test_config(SINGLE_SEAT_LAB);
+
+        let alice = send(&test, &config, event("C", "alice", "RDS-01", &minutes_ago(60))).await.session_uuid;
+        send(&test, &config, event("C", "bob", "RDS-01", &minutes_ago(10))).await;
+
+        assert_eq!(test.session_end(&alice).await, (None, None));
+        assert!(test.server_disconnects(&alice).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn late_connection_re_pairs_orphan_disconnection() {
+        let test = TestDb::new().await;
+        let config = test_config("");
+
+        let disconnected_at = minutes_ago(30);
+        let orphan = send(&test, &config, event("D", "alice", "PC-01", &disconnected_at)).await.session_uuid;
+        assert!(orphan.starts_with("orphan_"));
+
+        let session = send(&test, &config, event("C", "alice", "PC-01", &minutes_ago(60))).await.session_uuid;
+
+        assert_eq!(test.session_end(&session).await, (Some(disconnected_at), Some("explicit".to_string())));
+        let stats = test.db.orphan_stats().await.expect("bilan");
+        assert_eq!((stats.unrepaired, stats.repaired), (0, 1));
+    }
+
+    fn sample(skew_secs: i64) -> Option<ClockSample> {
+        Some(ClockSample { skew_secs, received_at: chrono::Utc::now() })
+    }
+
+    #[tokio::test]
+    async fn clock_sample_is_recorded_once_per_accepted_event() {
+        let test = TestDb::new().await;
+        let config = test_config("[clock]\nmax_skew_secs = 300\non_skew = \"reject\"");
+        let mut logon = event("C", "alice", "PC-01", &minutes_ago(5));
+        logon.event_id = Some(uuid::Uuid::now_v7());
+
+        try_send(&test, &config, logon.clone(), sample(12)).await.expect("accepté");
+        // Renvoi après un timeout : même réponse, aucune nouvelle mesure
+        try_send(&test, &config, logon, sample(14)).await.expect("renvoi");
+
+        // Événement refusé : mesure annulée avec la transaction
+        let (status, _) = try_send(&test, &config, event("C", "bob", "PC-01", &minutes_ago(5)), sample(-3600))
+            .await
+            .expect_err("refusé");
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let clocks = test.db.list_host_clocks().await.expect("liste");
+        assert_eq!(clocks.len(), 1);
+        assert_eq!((clocks[0].skew_secs, clocks[0].samples), (12, 1));
+    }
+
+    #[tokio::test]
+    async fn replayed_event_is_corrected_with_host_offset() {
+        let test = TestDb::new().await;
+        let config = test_config("[clock]\nmax_skew_secs = 300\non_skew = \"correct\"");
+
+        // Mesure en direct : horloge en retard d'une heure
+        try_send(&test, &config, event("H", "alice", "PC-01", &minutes_ago(60)), sample(-3600))
+            .await
+            .expect("accepté");
+
+        // Événement rejoué sans mesure (lot) : corrigé avec l'écart de la machine,
+        // son retard de 30 minutes conservé
+        let replayed_at = minutes_ago(90);
+        let session = send(&test, &config, event("C", "alice", "PC-01", &replayed_at)).await.session_uuid;
+
+        let mut tx = test.db.begin().await.expect("transaction");
+        let (timestamp, client_timestamp, skew): (String, Option<String>, Option<i64>) = sqlx::query_as(
+            "SELECT timestamp, client_timestamp, clock_skew_secs FROM events_all
+             WHERE session_uuid = ? AND action = 'C'",
+        )
+        .bind(&session)
+        .fetch_one(&mut *tx)
+        .await
+        .expect("événement");
+        let corrected = chrono::DateTime::parse_from_rfc3339(&replayed_at).expect("horodatage de test")
+            + chrono::Duration::hours(1);
+        assert_eq!(timestamp, corrected.to_rfc3339());
+        assert_eq!(client_timestamp, Some(replayed_at));
+        assert_eq!(skew, Some(-3600));
+    }
+
+    #[tokio::test]
+    async fn duplicate_event_reports_where_the_original_is_stored() {
+        let test = TestDb::new().await;
+        let config = test_config("");
+        let mut inventory = event("M", "alice", "PC-01", &minutes_ago(5));
+        inventory.event_id = Some(uuid::Uuid::now_v7());
+
+        let original = send(&test, &config, inventory.clone()).await;
+        assert!(!original.archived);
+        assert!(!send(&test, &config, inventory.clone()).await.archived);
+
+        // Après rotation, le renvoi désigne la ligne de events_history
+        test.db.rotate(RotationTrigger::Manual, 0).await.expect("rotation");
+        let replayed = send(&test, &config, inventory).await;
+        assert!(replayed.archived);
+        assert_eq!(replayed.session_uuid, original.session_uuid);
+    }
+
+    /// Envoie un lot comme POST /api/v1/events/batch (après la limite par IP)
+    async fn send_batch(state: &AppState, events: serde_json::Value) -> BatchResponse {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::USER_AGENT, "Winlog/0.1.0".parse().expect("en-tête"));
+        let body = serde_json::to_vec(&events).expect("lot");
+        collect_batch(state, &headers, &body, "10.0.0.1", "10.0.0.1", None)
+            .await
+            .unwrap_or_else(|(status, Json(error))| panic!("{}: {}", status, error.error))
+    }
+
+    fn batch_item(action: &str, username: &str, hostname: &str, timestamp: &str) -> serde_json::Value {
+        serde_json::json!({
+            "username": username,
+            "action": action,
+            "timestamp": timestamp,
+            "hostname": hostname,
+        })
+    }
+
+    #[tokio::test]
+    async fn batch_charges_one_host_token_per_workstation() {
+        let test = TestDb::new().await;
+        let config = test_config("[limits]\nper_host_per_minute = 1\nper_host_burst = 1\n");
+        let state = AppState {
+            limits: Arc::new(RateLimits::new(&config.limits)),
+            config,
+            db: test.db.clone(),
+        };
+
+        // File d'attente rejouée : un seul jeton pour les trois éléments du poste
+        let replay = send_batch(
+            &state,
+            serde_json::json!([
+                batch_item("C", "alice", "PC-01", &minutes_ago(30)),
+                batch_item("M", "alice", "PC-01", &minutes_ago(20)),
+                batch_item("D", "alice", "PC-01", &minutes_ago(10)),
+            ]),
+        )
+        .await;
+        assert_eq!((replay.accepted, replay.rejected), (3, 0));
+
+        // Seau vide : tous les éléments du poste sont refusés, les autres postes non
+        let limited = send_batch(
+            &state,
+            serde_json::json!([
+                batch_item("C", "alice", "PC-01", &minutes_ago(5)),
+                batch_item("C", "bob", "PC-02", &minutes_ago(5)),
+                batch_item("D", "alice", "PC-01", &minutes_ago(1)),
+            ]),
+        )
+        .await;
+        let codes: Vec<_> = limited.results.iter().map(|item| item.code).collect();
+        assert_eq!(codes, [Some(ErrorCode::RateLimited), None, Some(ErrorCode::RateLimited)]);
+        assert_eq!(state.limits.per_host.rejected_count(), 1);
+    }
+
+    /// En-têtes d'une requête signée par une machine dont l'horloge retarde d'une heure
+    fn signed_one_hour_late(secret: &str, body: &[u8]) -> HeaderMap {
+        use hmac::Mac;
+
+        let sent_at = (chrono::Utc::now().timestamp() - 3600).to_string();
+        let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(secret.as_bytes()).expect("clé HMAC");
+        mac.update(sent_at.as_bytes());
+        mac.update(b".");
+        mac.update(body);
+        let signature = hex::encode(mac.finalize().into_bytes());
+        headers(&[
+            (auth::TIMESTAMP_HEADER, &sent_at),
+            (auth::SIGNATURE_HEADER, &signature),
+            (SENT_AT_HEADER, &sent_at),
+        ])
+    }
+
+    #[tokio::test]
+    async fn expired_signature_still_records_host_clock_skew() {
+        for (on_skew, status, code) in [
+            ("flag", StatusCode::UNAUTHORIZED, ErrorCode::Unauthorized),
+            ("reject", StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::ClockSkew),
+        ] {
+            let test = TestDb::new().await;
+            let mut config = test_config(&format!("[clock]\nmax_skew_secs = 60\non_skew = \"{}\"\n", on_skew));
+            config.security.hmac_secrets = vec!["secret-de-test".to_string()];
+            let state = AppState {
+                limits: Arc::new(RateLimits::new(&config.limits)),
+                config,
+                db: test.db.clone(),
+            };
+
+            let body = serde_json::to_vec(&batch_item("C", "alice", "PC-01", &minutes_ago(1))).expect("corps");
+            let headers = signed_one_hour_late("secret-de-test", &body);
+            let peer = "10.0.0.1:50000".parse().expect("adresse");
+            let response = collect_event(State(state), ConnectInfo(peer), headers, body.into())
+                .await
+                .expect_err("signature hors fenêtre");
+            assert_eq!(response.status(), status, "on_skew = {}", on_skew);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.expect("corps");
+            let error: serde_json::Value = serde_json::from_slice(&body).expect("erreur JSON");
+            assert_eq!(error["code"], serde_json::to_value(code).expect("code"));
+
+            // La dérive est visible dans GET /api/v1/admin/clocks malgré le refus
+            let clocks = test.db.list_host_clocks().await.expect("écarts");
+            assert_eq!(clocks.len(), 1);
+            assert_eq!(clocks[0].hostname, "PC-01");
+            assert!((clocks[0].skew_secs + 3600).abs() <= 1, "écart {}", clocks[0].skew_secs);
+            assert_eq!(clocks[0].out_of_tolerance, 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn only_busy_locked_or_io_database_errors_are_transient() {
+        use sqlx::sqlite::SqliteConnectOptions;
+        use sqlx::Connection;
+
+        let path = std::env::temp_dir().join(format!("winlog-test-{}.db", uuid::Uuid::now_v7()));
+        let options = SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true)
+            .busy_timeout(std::time::Duration::ZERO);
+        let mut writer = SqliteConnection::connect_with(&options).await.expect("connexion");
+        let mut other = SqliteConnection::connect_with(&options).await.expect("connexion");
+        sqlx::query("CREATE TABLE t (x INTEGER NOT NULL)").execute(&mut writer).await.expect("table");
+
+        // Écriture concurrente : SQLITE_BUSY
+        sqlx::query("BEGIN IMMEDIATE").execute(&mut writer).await.expect("verrou");
+        let busy = sqlx::query("INSERT INTO t VALUES (1)").execute(&mut other).await.expect_err("base verrouillée");
+        let (status, Json(error)) = database_error_response(&busy);
+        assert_eq!((status, error.code), (StatusCode::SERVICE_UNAVAILABLE, ErrorCode::DbUnavailable));
+        sqlx::query("ROLLBACK").execute(&mut writer).await.expect("annulation");
+
+        // Contrainte violée, requête invalide : un nouvel essai échouerait de même
+        for sql in ["INSERT INTO t VALUES (NULL)", "SELECT * FROM absente"] {
+            let error = sqlx::query(sql).execute(&mut other).await.expect_err("erreur permanente");
+            let (status, Json(error)) = database_error_response(&error);
+            assert_eq!((status, error.code), (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::DatabaseError));
+        }
+        let (status, _) = database_error_response(&sqlx::Error::PoolTimedOut);
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        drop((writer, other));
+        for suffix in ["", "-wal", "-shm", "-journal"] {
+            let mut file = path.clone().into_os_string();
+            file.push(suffix);
+            let _ = std::fs::remove_file(file);
+        }
+    }
+}
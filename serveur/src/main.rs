@@ -11,34 +11,76 @@
 //!
 //! ## Endpoints
 //! - `POST /api/v1/events` - Collecte d'événements (logique principale)
+//! - `POST /api/v1/events/batch` - Collecte par lot (rejeu après coupure réseau)
 //! - `GET /api/v1/sessions/current` - Liste des sessions actuellement ouvertes
-//! - `GET /health` - Health check
+//! - `POST /api/v1/enroll` - Enrôlement d'une machine (délivre un jeton)
+//! - `GET /api/v1/admin/tokens` - Liste des jetons d'enrôlement
+//! - `POST /api/v1/admin/tokens/:id/revoke` - Révocation d'un jeton
+//! - `POST /api/v1/admin/rotate` - Rotation quotidienne immédiate
+//! - `GET /api/v1/admin/orphans` - Bilan des déconnexions orphelines
+//! - `GET /api/v1/admin/clocks` - Écart d'horloge des machines
+//! - `GET /health` - Health check (et compteurs de limitation de débit)
 //!
 //! ## Configuration
 //! Le serveur charge sa configuration depuis `config.toml` au démarrage.
+//! Si la section `[server.tls]` est présente, il écoute en HTTPS (rustls),
+//! avec mTLS optionnel et rechargement à chaud des certificats.
+//! La rotation quotidienne events_today → events_history est planifiée par le
+//! serveur (section `[rotation]`), la purge de events_history selon les
+//! durées de conservation de `[database.retention]`, avec export Parquet
+//! préalable si `archive_dir` est configuré. Les sessions sans signal de
+//! présence récent ou trop longues sont fermées selon `[sessions]`.
+//!
+//! ## Sous-commande
+//! `winlog-server restore-archive <fichier.parquet|répertoire> <base.db>` :
+//! recharge des archives Parquet dans une base de travail.
 
+mod archive;
+mod auth;
 mod config;
 mod models;
 mod database;
+mod expiry;
 mod handlers;
 mod queries;  // Module contenant toutes les requêtes SQL
+mod ratelimit;
+mod retention;
+mod rotation;
+mod tls;
 
 use axum::{
     routing::{get, post},
     Router,
 };
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 use crate::{
     config::Config,
     database::Database,
-    handlers::{AppState, collect_event, health_check, get_current_sessions},
+    handlers::{
+        AppState, collect_event, collect_events_batch, health_check, get_current_sessions,
+        enroll_host, list_host_tokens, revoke_host_token, trigger_rotation, get_orphan_stats,
+        list_host_clocks,
+    },
+    ratelimit::RateLimits,
 };
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Sous-commande : restauration d'archives Parquet dans une base de travail
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("restore-archive") {
+        if let Err(e) = archive::restore_command(&args[2..]).await {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     // 1. Chargement de la configuration
     let config = Config::from_file("config.toml")
         .expect("Impossible de charger config.toml");
@@ -57,27 +99,64 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     tracing::info!("🚀 Démarrage du serveur Winlog...");
     tracing::info!("Configuration chargée depuis config.toml");
+    if config.security.signature_required() {
+        tracing::info!(
+            "Signature HMAC requise ({} secret(s) actif(s))",
+            config.security.hmac_secrets.len()
+        );
+    } else {
+        tracing::warn!("Aucun secret HMAC configuré : seul le User-Agent est contrôlé");
+    }
+    let limits = &config.limits;
+    tracing::info!(
+        "Limitation de débit : {}/min (rafale {}) par IP, {}/min (rafale {}) par username@hostname (0 = désactivée)",
+        limits.per_ip_per_minute,
+        limits.per_ip_burst,
+        limits.per_host_per_minute,
+        limits.per_host_burst
+    );
 
     // 3. Connexion à la base de données SQLite
     tracing::info!("Connexion à la base SQLite: {}", config.database.path_buf().display());
     let db = Database::new(&config.database)
         .await
-        .expect("Impossible de se connecter à la base SQLite");
+        .expect("Impossible d'ouvrir ou de migrer la base SQLite");
     tracing::info!("✓ Connexion SQLite établie");
 
+    // Rotation quotidienne planifiée
+    rotation::spawn_scheduler(db.clone(), config.rotation.clone());
+
+    // Purge périodique selon la politique de conservation
+    retention::spawn_purger(db.clone(), config.database.retention.clone());
+
+    // Fermeture des sessions sans signal de présence ou trop longues
+    expiry::spawn_reaper(db.clone(), config.sessions.clone());
+
     // 4. Création de l'état partagé
     let state = AppState {
         config: config.clone(),
         db,
+        limits: Arc::new(RateLimits::new(&config.limits)),
     };
 
     // 5. Définition des routes Axum
     let app = Router::new()
         // Route principale : collecte d'événements
         .route("/api/v1/events", post(collect_event))
+
+        // Collecte par lot
+        .route("/api/v1/events/batch", post(collect_events_batch))
         
         // Liste des sessions ouvertes
         .route("/api/v1/sessions/current", get(get_current_sessions))
+
+        // Enrôlement des machines et administration des jetons
+        .route("/api/v1/enroll", post(enroll_host))
+        .route("/api/v1/admin/tokens", get(list_host_tokens))
+        .route("/api/v1/admin/tokens/:id/revoke", post(revoke_host_token))
+        .route("/api/v1/admin/rotate", post(trigger_rotation))
+        .route("/api/v1/admin/orphans", get(get_orphan_stats))
+        .route("/api/v1/admin/clocks", get(list_host_clocks))
         
         // Health check
         .route("/health", get(health_check))
@@ -90,21 +169,53 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // 6. Démarrage du serveur
     let addr: SocketAddr = config.bind_address().parse()?;
+    let scheme = if config.server.tls.is_some() { "https" } else { "http" };
     
-    tracing::info!("✓ Serveur Winlog démarré sur http://{}", addr);
+    tracing::info!("✓ Serveur Winlog démarré sur {}://{}", scheme, addr);
     tracing::info!("  POST /api/v1/events            - Collecte d'événements");
+    tracing::info!("  POST /api/v1/events/batch      - Collecte par lot");
     tracing::info!("  GET  /api/v1/sessions/current  - Sessions ouvertes");
+    tracing::info!("  POST /api/v1/enroll            - Enrôlement d'une machine");
+    tracing::info!("  GET  /api/v1/admin/tokens      - Jetons d'enrôlement");
+    tracing::info!("  POST /api/v1/admin/tokens/:id/revoke - Révocation d'un jeton");
+    tracing::info!("  POST /api/v1/admin/rotate      - Rotation immédiate");
+    tracing::info!("  GET  /api/v1/admin/orphans     - Déconnexions orphelines");
+    tracing::info!("  GET  /api/v1/admin/clocks      - Écart d'horloge des machines");
     tracing::info!("  GET  /health                   - Health check");
     tracing::info!("");
     tracing::info!("Appuyez sur Ctrl+C pour arrêter le serveur");
 
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .with_graceful_shutdown(shutdown_signal())
-    .await?;
+    match &config.server.tls {
+        Some(tls_config) => {
+            // HTTPS natif : rustls + rechargement à chaud des certificats
+            let rustls_config = tls::load(tls_config)?;
+            tls::spawn_reload_task(rustls_config.clone(), tls_config.clone());
+            if tls_config.client_ca_path.is_some() {
+                tracing::info!("🔐 mTLS activé : certificat client obligatoire");
+            }
+
+            let handle = axum_server::Handle::new();
+            let shutdown_handle = handle.clone();
+            tokio::spawn(async move {
+                shutdown_signal().await;
+                shutdown_handle.graceful_shutdown(Some(Duration::from_secs(10)));
+            });
+
+            axum_server::bind_rustls(addr, rustls_config)
+                .handle(handle)
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                .await?;
+        }
+        None => {
+            let listener = tokio::net::TcpListener::bind(&addr).await?;
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown_signal())
+            .await?;
+        }
+    }
 
     Ok(())
 }
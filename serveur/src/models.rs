@@ -3,17 +3,82 @@
 //! Définit les structures de données échangées entre le client et le serveur,
 //! ainsi que les modèles de la base de données.
 
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
+/// Type d'événement (code d'une lettre dans le JSON et la colonne `action`)
+///
+/// Vocabulaire identique à `data_structures::Action` côté client. Une nouvelle
+/// action s'ajoute aux deux, ainsi qu'à la contrainte CHECK de events_today et
+/// events_history (migration).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, sqlx::Type)]
+pub enum Action {
+    /// Ouverture de session
+    #[serde(rename = "C")]
+    #[sqlx(rename = "C")]
+    Connect,
+    /// Fermeture de session
+    #[serde(rename = "D")]
+    #[sqlx(rename = "D")]
+    Disconnect,
+    /// Inventaire matériel
+    #[serde(rename = "M")]
+    #[sqlx(rename = "M")]
+    Hardware,
+    /// Verrouillage de l'écran (début d'inactivité de la session)
+    #[serde(rename = "L")]
+    #[sqlx(rename = "L")]
+    Lock,
+    /// Déverrouillage de l'écran (fin d'inactivité de la session)
+    #[serde(rename = "U")]
+    #[sqlx(rename = "U")]
+    Unlock,
+    /// Démarrage de la machine
+    #[serde(rename = "B")]
+    #[sqlx(rename = "B")]
+    Boot,
+    /// Arrêt de la machine
+    #[serde(rename = "S")]
+    #[sqlx(rename = "S")]
+    Shutdown,
+    /// Signal de présence d'une session ouverte
+    #[serde(rename = "H")]
+    #[sqlx(rename = "H")]
+    Heartbeat,
+}
+
+impl Action {
+    /// Code d'une lettre (JSON, colonne `action`, noms des archives)
+    pub fn code(self) -> &'static str {
+        match self {
+            Action::Connect => "C",
+            Action::Disconnect => "D",
+            Action::Hardware => "M",
+            Action::Lock => "L",
+            Action::Unlock => "U",
+            Action::Boot => "B",
+            Action::Shutdown => "S",
+            Action::Heartbeat => "H",
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
 /// Événement reçu du client (payload JSON)
 #[derive(Debug, Clone, Deserialize)]
 pub struct ClientEvent {
     /// Nom d'utilisateur
     pub username: String,
     
-    /// Code d'action : 'C' (connexion), 'D' (déconnexion), 'M' (matériel)
-    pub action: String,
+    /// Type d'événement (code 'C', 'D', 'M', 'L', 'U', 'B', 'S' ou 'H')
+    pub action: Action,
     
     /// Timestamp de l'événement (ISO 8601)
     pub timestamp: String,
@@ -26,6 +91,25 @@ pub struct ClientEvent {
     
     /// Informations matérielles (pour action='M' uniquement)
     pub hardware_info: Option<serde_json::Value>,
+
+    /// Identifiant généré par le client (UUID, optionnel)
+    ///
+    /// Permet de reconnaître un renvoi après un timeout : le serveur retourne
+    /// alors la réponse de l'enregistrement d'origine sans rien insérer.
+    pub event_id: Option<uuid::Uuid>,
+
+    /// Adresse de la connexion TCP (proxy de confiance le cas échéant),
+    /// renseignée par le serveur
+    #[serde(skip)]
+    pub peer_ip: Option<String>,
+
+    /// Écart d'horloge hors tolérance (secondes), renseigné par le serveur
+    #[serde(skip)]
+    pub clock_skew_secs: Option<i64>,
+
+    /// Timestamp envoyé par le client lorsque le serveur l'a corrigé
+    #[serde(skip)]
+    pub client_timestamp: Option<String>,
 }
 
 /// Informations système d'exploitation
@@ -52,6 +136,7 @@ pub struct DbEvent {
     pub kernel_version: Option<String>,
     pub hardware_info: Option<String>,
     pub session_uuid: String,
+    pub event_id: Option<String>,
     pub created_at: String,
 }
 
@@ -63,6 +148,128 @@ pub struct OpenSession {
     pub timestamp: String,
 }
 
+/// Motif de fermeture d'une session (colonne `sessions.close_reason`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum CloseReason {
+    /// Déconnexion envoyée par le client
+    Explicit,
+    /// Déconnexion automatique insérée par le serveur (connexion suivante du
+    /// même utilisateur, ou d'un autre utilisateur sur un poste à place unique)
+    AutoClosed,
+    /// Session expirée : plus de signal de présence (`[sessions]`)
+    TimedOut,
+    /// Session fermée au terme de la durée maximale (`max_session_hours`)
+    PolicyClosed,
+}
+
+/// Session en cours à un instant donné mais fermée après lui (événement reçu en retard)
+#[derive(Debug, Clone, FromRow)]
+pub struct ClosedSession {
+    pub session_uuid: String,
+    /// ID de la déconnexion dans sa table
+    pub disconnect_id: i64,
+    /// Table de la déconnexion ("today" ou "history")
+    pub disconnect_source: String,
+    /// Timestamp de la déconnexion
+    pub disconnect_timestamp: String,
+    /// Adresse IP de la déconnexion
+    pub end_ip: Option<String>,
+    /// Motif de fermeture
+    pub close_reason: CloseReason,
+}
+
+/// Session ouverte sans signal de présence récent (à fermer, `timed_out`)
+#[derive(Debug, Clone, FromRow)]
+pub struct StaleSession {
+    pub session_uuid: String,
+    pub username: String,
+    pub hostname: Option<String>,
+    pub os_name: Option<String>,
+    pub os_version: Option<String>,
+    /// Dernier signal de présence : heure de la déconnexion insérée
+    pub last_heartbeat_at: String,
+}
+
+/// Session ouverte depuis plus longtemps que la plus courte durée maximale configurée
+#[derive(Debug, Clone, FromRow)]
+pub struct OverdueSession {
+    pub session_uuid: String,
+    pub username: String,
+    pub hostname: Option<String>,
+    pub os_name: Option<String>,
+    pub os_version: Option<String>,
+    pub started_at: String,
+}
+
+/// Session ouverte d'un autre utilisateur sur un poste à place unique (à fermer)
+#[derive(Debug, Clone, FromRow)]
+pub struct HostSession {
+    pub session_uuid: String,
+    pub username: String,
+    pub os_name: Option<String>,
+    pub os_version: Option<String>,
+}
+
+/// Événement inséré en base
+#[derive(Debug, Clone, Copy)]
+pub struct InsertedEvent {
+    /// ID de la ligne insérée (dans events_today ou events_history)
+    pub id: i64,
+    /// Événement antérieur à la dernière rotation, rangé dans events_history
+    pub archived: bool,
+}
+
+/// Changement d'appariement d'une session provoqué par un événement reçu en retard
+#[derive(Debug, Clone, Serialize)]
+pub struct PairingChange {
+    /// Session dont la déconnexion a changé
+    pub session_uuid: String,
+    /// Déconnexion précédente (timestamp)
+    pub previous_disconnect: String,
+    /// Nouvelle déconnexion (timestamp)
+    pub new_disconnect: String,
+    /// Cause : "late_disconnect" (remplace une déconnexion automatique) ou
+    /// "late_connection" (session scindée par une connexion tardive)
+    pub reason: String,
+}
+
+/// Événement déjà enregistré, retrouvé par son event_id client
+#[derive(Debug, Clone, FromRow)]
+pub struct StoredEvent {
+    pub id: i64,
+    pub username: String,
+    pub action: Action,
+    pub session_uuid: String,
+    /// Ligne d'origine dans events_history (et non events_today)
+    pub archived: bool,
+}
+
+/// Ligne complète de events_history, exportée en Parquet avant purge
+#[derive(Debug, Clone, FromRow)]
+pub struct ArchivedEvent {
+    pub id: i64,
+    pub username: String,
+    pub action: String,
+    pub timestamp: String,
+    pub hostname: Option<String>,
+    pub source_ip: Option<String>,
+    pub peer_ip: Option<String>,
+    pub server_timestamp: Option<String>,
+    pub os_name: Option<String>,
+    pub os_version: Option<String>,
+    pub kernel_version: Option<String>,
+    pub hardware_info: Option<String>,
+    pub session_uuid: Option<String>,
+    pub event_id: Option<String>,
+    pub created_at: Option<String>,
+    pub archived_at: Option<String>,
+    pub close_reason: Option<String>,
+    pub clock_skew_secs: Option<i64>,
+    pub client_timestamp: Option<String>,
+}
+
 /// Session en cours pour l'API GET /api/v1/sessions/current
 #[derive(Debug, Clone, Serialize, FromRow)]
 pub struct CurrentSession {
@@ -86,40 +293,298 @@ pub struct CurrentSession {
     
     /// Version du système d'exploitation (nullable en base)
     pub os_version: Option<String>,
+
+    /// Début du verrouillage de l'écran en cours (None si déverrouillé)
+    pub locked_at: Option<String>,
+
+    /// Durée cumulée des verrouillages terminés (secondes)
+    pub locked_seconds: i64,
 }
 
-/// Réponse de succès retournée au client
+/// Demande d'enrôlement d'une machine (POST /api/v1/enroll)
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnrollRequest {
+    /// Nom de la machine à enrôler
+    pub hostname: String,
+}
+
+/// Jeton délivré à une machine (retourné une seule fois, jamais stocké en clair)
 #[derive(Debug, Serialize)]
+pub struct EnrollResponse {
+    pub token_id: i64,
+    pub hostname: String,
+    pub token: String,
+}
+
+/// Jeton d'enrôlement pour l'API d'administration (sans le secret)
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct HostToken {
+    pub id: i64,
+    pub hostname: String,
+    pub created_at: String,
+    /// Date de révocation (None si le jeton est actif)
+    pub revoked_at: Option<String>,
+}
+
+/// Origine d'une rotation (colonne `rotations.triggered_by`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum RotationTrigger {
+    /// Heure configurée dans `[rotation]`
+    Scheduled,
+    /// Rotation manquée (serveur arrêté à l'heure prévue), rattrapée au démarrage
+    CatchUp,
+    /// POST /api/v1/admin/rotate
+    Manual,
+}
+
+/// Exécution de la rotation quotidienne (table rotations)
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Rotation {
+    pub id: i64,
+    /// Début de la rotation (UTC), reporté dans `archived_at` des lignes déplacées
+    pub rotated_at: String,
+    pub triggered_by: RotationTrigger,
+    /// "success" ou "failed"
+    pub status: String,
+    pub today_before: Option<i64>,
+    pub history_before: Option<i64>,
+    pub today_after: Option<i64>,
+    pub history_after: Option<i64>,
+    /// Lignes déplacées vers events_history
+    pub archived: Option<i64>,
+    /// Sessions encore ouvertes au moment de la rotation
+    pub open_sessions: Option<i64>,
+    pub duration_ms: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Bilan d'une règle de conservation appliquée (table purges)
+#[derive(Debug, Clone)]
+pub struct Purge {
+    /// Début de la purge (UTC, format CURRENT_TIMESTAMP)
+    pub purged_at: String,
+    /// "events_history", "sessions", "orphan_repairs" ou "host_clocks"
+    pub target: &'static str,
+    /// Action purgée (None hors events_history)
+    pub action: Option<Action>,
+    pub retention_days: Option<u32>,
+    /// Date limite (ISO 8601 UTC) : lignes antérieures supprimées
+    pub cutoff: Option<String>,
+    pub deleted: i64,
+    pub batches: i64,
+    /// Fichiers Parquet écrits avant suppression (None : archivage désactivé)
+    pub archive_files: Option<i64>,
+    pub duration_ms: i64,
+    /// Erreur ayant interrompu la purge (lots déjà supprimés conservés)
+    pub error: Option<String>,
+}
+
+/// Origine d'un réappariement de déconnexion orpheline (colonne `orphan_repairs.triggered_by`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum OrphanRepairTrigger {
+    /// Connexion enregistrée (éventuellement reçue après la déconnexion)
+    LateConnection,
+    /// Passe complète lors de la rotation quotidienne
+    Rotation,
+}
+
+/// Déconnexion orpheline et session à laquelle la rattacher
+#[derive(Debug, Clone, FromRow)]
+pub struct OrphanPairing {
+    pub orphan_id: i64,
+    /// Table de la déconnexion orpheline ("today" ou "history")
+    pub orphan_source: String,
+    pub orphan_uuid: String,
+    pub username: String,
+    pub hostname: Option<String>,
+    pub disconnect_timestamp: String,
+    pub source_ip: Option<String>,
+    pub session_uuid: String,
+    pub session_started_at: String,
+    /// Fin actuelle de la session (None : ouverte)
+    pub previous_ended_at: Option<String>,
+    pub previous_close_reason: Option<CloseReason>,
+    /// Déconnexion insérée par le serveur, remplacée (None : session ouverte)
+    pub server_disconnect_id: Option<i64>,
+    pub server_disconnect_source: Option<String>,
+}
+
+/// Réappariement d'une déconnexion orpheline (table orphan_repairs)
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct OrphanRepair {
+    pub id: i64,
+    /// Heure de la réparation (UTC)
+    pub repaired_at: String,
+    pub triggered_by: OrphanRepairTrigger,
+    pub username: String,
+    pub hostname: Option<String>,
+    pub disconnect_timestamp: String,
+    /// Identifiant `orphan_…` remplacé
+    pub orphan_uuid: String,
+    pub session_uuid: String,
+    pub session_started_at: String,
+    pub previous_ended_at: Option<String>,
+    pub previous_close_reason: Option<CloseReason>,
+}
+
+/// Déconnexions orphelines non réappariées d'une machine
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct OrphanHost {
+    pub hostname: Option<String>,
+    pub unrepaired: i64,
+    pub last_orphan_at: String,
+}
+
+/// Bilan des déconnexions orphelines (GET /api/v1/admin/orphans)
+#[derive(Debug, Clone, Serialize)]
+pub struct OrphanStats {
+    /// Déconnexions orphelines non réappariées (toutes machines)
+    pub unrepaired: i64,
+    /// Déconnexions réappariées depuis l'origine (table orphan_repairs)
+    pub repaired: i64,
+    pub hosts: Vec<OrphanHost>,
+}
+
+/// Écart d'horloge d'une machine (table host_clocks, GET /api/v1/admin/clocks)
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct HostClock {
+    pub hostname: String,
+    /// Dernier écart mesuré (secondes, positif = horloge en avance)
+    pub skew_secs: i64,
+    /// Heure de la dernière mesure (UTC)
+    pub last_seen_at: String,
+    pub samples: i64,
+    pub min_skew_secs: i64,
+    pub max_skew_secs: i64,
+    /// Mesures hors tolérance depuis la première mesure
+    pub out_of_tolerance: i64,
+    pub last_out_of_tolerance_at: Option<String>,
+}
+
+/// Réponse de succès retournée au client
+#[derive(Debug, Clone, Serialize)]
 pub struct SuccessResponse {
     pub status: String,
     pub message: String,
     pub event_id: i64,
     pub session_uuid: String,
-    pub action: String,
+    pub action: Action,
     pub username: String,
+    /// Événement antérieur à la dernière rotation, rangé dans events_history
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub archived: bool,
+    /// Sessions dont l'appariement a changé (événement reçu en retard)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pairing_changes: Vec<PairingChange>,
+}
+
+/// Résultat du traitement d'un élément d'un lot (POST /api/v1/events/batch)
+#[derive(Debug, Serialize)]
+pub struct BatchItemResult {
+    /// Position de l'événement dans le tableau reçu (à partir de 0)
+    pub index: usize,
+
+    /// "success" ou "error"
+    pub status: String,
+
+    /// Réponse identique à celle de POST /api/v1/events (si succès)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<SuccessResponse>,
+
+    /// Code d'erreur stable (si échec)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>,
+
+    /// Message d'erreur (si échec)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// Détails de l'erreur (si échec de validation)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+/// Réponse globale du traitement par lot
+#[derive(Debug, Serialize)]
+pub struct BatchResponse {
+    pub status: String,
+    /// Nombre d'événements enregistrés
+    pub accepted: usize,
+    /// Nombre d'événements rejetés
+    pub rejected: usize,
+    /// Résultat de chaque élément, dans l'ordre de la requête
+    pub results: Vec<BatchItemResult>,
+}
+
+/// Code d'erreur stable, destiné aux programmes clients
+///
+/// Sérialisé en `SCREAMING_SNAKE_CASE` (ex : `INVALID_ACTION`). Contrairement au
+/// message, ces valeurs ne changent pas d'une version à l'autre : le client s'en
+/// sert pour décider si un nouvel essai a un sens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// Corps JSON mal formé ou de structure inattendue
+    BadJson,
+    /// Champ obligatoire absent ou vide
+    MissingFields,
+    /// Action désactivée (absente de `security.valid_actions`)
+    InvalidAction,
+    /// Timestamp non conforme à ISO 8601
+    BadTimestamp,
+    /// Lot supérieur à `server.max_batch_size`
+    BatchTooLarge,
+    /// Signature, jeton ou clé absent, invalide ou expiré
+    Unauthorized,
+    /// Client refusé (User-Agent) ou fonctionnalité désactivée
+    Forbidden,
+    /// Hostname de l'événement différent de la machine du jeton
+    HostnameMismatch,
+    /// Machine déjà enrôlée
+    AlreadyEnrolled,
+    /// Ressource inexistante
+    NotFound,
+    /// Limite de débit dépassée (réessayer après `Retry-After`)
+    RateLimited,
+    /// Horloge de la machine hors tolérance (`[clock] on_skew = "reject"`)
+    ClockSkew,
+    /// Base de données occupée, verrouillée ou inaccessible (erreur transitoire)
+    DbUnavailable,
+    /// Erreur SQLite permanente (contrainte, requête invalide...)
+    DatabaseError,
 }
 
 /// Réponse d'erreur retournée au client
 #[derive(Debug, Serialize)]
 pub struct ErrorResponse {
+    /// Code stable (voir `ErrorCode`)
+    pub code: ErrorCode,
+    /// Message lisible (peut évoluer, ne pas l'interpréter)
     pub error: String,
+    /// Détails structurés (champ en cause, valeur reçue, valeurs attendues...)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub details: Option<serde_json::Value>,
 }
 
 impl ErrorResponse {
     /// Crée une réponse d'erreur simple
-    pub fn new(error: impl Into<String>) -> Self {
+    pub fn new(code: ErrorCode, error: impl Into<String>) -> Self {
         Self {
+            code,
             error: error.into(),
             details: None,
         }
     }
 
-    /// Crée une réponse d'erreur avec détails (prévu pour future gestion d'erreurs avancée)
-    #[allow(dead_code)]  // API complète pour évolutions futures
-    pub fn with_details(error: impl Into<String>, details: serde_json::Value) -> Self {
+    /// Crée une réponse d'erreur avec détails (erreurs de validation)
+    pub fn with_details(code: ErrorCode, error: impl Into<String>, details: serde_json::Value) -> Self {
         Self {
+            code,
             error: error.into(),
             details: Some(details),
         }
@@ -24,77 +24,257 @@
 //! - `SQL_INSERT_*` : Requêtes d'insertion (INSERT)
 //! - `SQL_UPDATE_*` : Requêtes de mise à jour (UPDATE)
 //! - `SQL_DELETE_*` : Requêtes de suppression (DELETE)
+//! - `SQL_COUNT_*` : Comptages (SELECT COUNT)
 
 // ============================================================================
 // REQUÊTES DE RECHERCHE (SELECT)
 // ============================================================================
 
-/// Recherche une session de connexion (action='C') ouverte aujourd'hui pour un user@host donné.
+/// Recherche une session ouverte dans les 24 heures précédentes pour un user@host donné.
 /// 
-/// **Objectif** : Éviter de créer plusieurs sessions le même jour pour le même utilisateur.
+/// **Objectif** : Éviter de laisser plusieurs sessions ouvertes pour le même utilisateur
+///                sur la même machine (nouvelle connexion sans déconnexion).
 /// 
 /// **Logique** :
-/// - Filtre par username, hostname, action='C'
-/// - Compare uniquement la date (DATE()) entre l'événement et le timestamp de référence
-/// - Exclut les sessions déjà fermées (vérifie qu'il n'existe pas d'action='D' associée)
-/// - Retourne la plus récente (ORDER BY timestamp DESC)
+/// - Lecture de la table sessions (index partiel idx_sessions_open : sessions ouvertes
+///   uniquement), qu'elles aient commencé avant ou après la dernière rotation
+/// - Fenêtre glissante de 24 heures et non date calendaire : une session de nuit
+///   (connexion à 22:00) est encore trouvée par une connexion à 06:00 le lendemain
+/// - Ignore les sessions ouvertes après le timestamp de référence (connexion reçue en retard :
+///   elle ne ferme pas une session ouverte après elle)
+/// - Retourne la plus récente (ORDER BY started_at DESC)
 /// 
 /// **Paramètres** :
 /// - `?1` : username (TEXT)
 /// - `?2` : hostname (TEXT)
-/// - `?3` : timestamp de référence (TEXT ISO 8601) - pour extraction de la date
+/// - `?3` : timestamp de référence (TEXT ISO 8601) - borne inférieure (moins 1 jour)
+/// - `?4` : timestamp de référence (TEXT ISO 8601) - borne supérieure
 /// 
 /// **Colonnes retournées** :
 /// - `session_uuid` : Identifiant unique de la session
 /// - `timestamp` : Date/heure de connexion (ISO 8601)
 /// 
-/// **Utilisé dans** : `database.rs::find_open_session_today()`
-pub const SQL_FIND_OPEN_SESSION_TODAY: &str = r#"
-    SELECT session_uuid, timestamp 
-    FROM events_today 
-    WHERE username = ? 
-      AND hostname = ? 
-      AND action = 'C'
-      AND DATE(timestamp) = DATE(?)
-      AND NOT EXISTS (
-          SELECT 1 FROM events_today e2 
-          WHERE e2.session_uuid = events_today.session_uuid 
-            AND e2.action = 'D'
-      )
-    ORDER BY timestamp DESC 
+/// **Utilisé dans** : `database.rs::find_recent_open_session()`
+pub const SQL_FIND_RECENT_OPEN_SESSION: &str = r#"
+    SELECT session_uuid, started_at AS timestamp
+    FROM sessions
+    WHERE username = ?
+      AND hostname = ?
+      AND ended_at IS NULL
+      AND julianday(started_at) > julianday(?) - 1
+      AND julianday(started_at) <= julianday(?)
+    ORDER BY started_at DESC
     LIMIT 1
 "#;
 
-/// Recherche la dernière session de connexion (action='C') encore ouverte pour un user@host.
+/// Recherche la dernière session encore ouverte pour un user@host.
 /// 
-/// **Objectif** : Associer une déconnexion (action='D') orpheline à la bonne session.
+/// **Objectif** : Associer une déconnexion (action='D') à la bonne session.
 /// 
 /// **Logique** :
-/// - Filtre par username, hostname, action='C'
-/// - Exclut les sessions déjà fermées (vérifie qu'il n'existe pas d'action='D' associée)
+/// - Lecture de la table sessions (index partiel idx_sessions_open)
+/// - Ignore les sessions ouvertes après la déconnexion (déconnexion reçue en retard)
 /// - Retourne uniquement le session_uuid de la plus récente
-/// - Pas de filtre de date : cherche dans toute la table events_today
+/// - Pas de filtre de date : la connexion d'une déconnexion tardive peut déjà être archivée
 /// 
 /// **Paramètres** :
 /// - `?1` : username (TEXT)
 /// - `?2` : hostname (TEXT)
+/// - `?3` : timestamp de la déconnexion (TEXT ISO 8601)
 /// 
 /// **Colonnes retournées** :
 /// - `session_uuid` : Identifiant unique de la dernière session ouverte
 /// 
 /// **Utilisé dans** : `database.rs::find_last_open_session()`
 pub const SQL_FIND_LAST_OPEN_SESSION: &str = r#"
-    SELECT session_uuid 
-    FROM events_today 
-    WHERE username = ? 
-      AND hostname = ? 
-      AND action = 'C'
+    SELECT session_uuid
+    FROM sessions
+    WHERE username = ?
+      AND hostname = ?
+      AND ended_at IS NULL
+      AND julianday(started_at) <= julianday(?)
+    ORDER BY started_at DESC
+    LIMIT 1
+"#;
+
+/// Recherche la session d'un user@host en cours à un instant donné mais déjà fermée après lui.
+/// 
+/// **Objectif** : Replacer un événement reçu en retard (file d'attente du client) :
+/// - une déconnexion tardive doit remplacer la déconnexion automatique insérée entre-temps
+/// - une connexion tardive reprend la déconnexion de la session qui l'englobe
+/// 
+/// **Logique** :
+/// - Dernière session (table sessions) commencée au plus tard à l'instant donné
+/// - Terminée après cet instant
+/// - Jointure sur la déconnexion (action='D') pour retrouver sa ligne ; `disconnect_source` :
+///   table de la déconnexion ('today' ou 'history', vue events_all)
+/// 
+/// **Paramètres** :
+/// - `?1` : username (TEXT)
+/// - `?2` : hostname (TEXT)
+/// - `?3` : instant de référence (TEXT ISO 8601) - borne du début de session
+/// - `?4` : instant de référence (TEXT ISO 8601) - borne de la fin de session
+/// 
+/// **Colonnes retournées** :
+/// - `session_uuid` : Session concernée
+/// - `disconnect_id`, `disconnect_source` : Ligne de la déconnexion
+/// - `disconnect_timestamp` : Heure de la déconnexion actuelle
+/// - `end_ip` : Adresse IP de la déconnexion
+/// - `close_reason` : Motif de fermeture ('explicit', 'auto_closed', 'timed_out', 'policy_closed')
+/// 
+/// **Utilisé dans** : `database.rs::find_session_closed_after()`
+pub const SQL_FIND_SESSION_CLOSED_AFTER: &str = r#"
+    SELECT 
+        s.session_uuid,
+        d.id AS disconnect_id,
+        d.source AS disconnect_source,
+        s.ended_at AS disconnect_timestamp,
+        s.end_ip,
+        s.close_reason
+    FROM sessions s
+    INNER JOIN events_all d
+        ON d.session_uuid = s.session_uuid
+       AND d.action = 'D'
+    WHERE s.username = ?
+      AND s.hostname = ?
+      AND s.ended_at IS NOT NULL
+      AND julianday(s.started_at) <= julianday(?)
+      AND julianday(s.ended_at) > julianday(?)
+    ORDER BY s.started_at DESC
+    LIMIT 1
+"#;
+
+/// Recherche la dernière session d'un user@host fermée par expiration avant un instant donné.
+/// 
+/// **Objectif** : Une déconnexion réelle reçue après l'expiration de sa session (signaux de
+///                présence interrompus par une coupure réseau, déconnexion rejouée depuis la
+///                file d'attente du client) remplace la déconnexion insérée à l'expiration.
+/// 
+/// **Logique** :
+/// - Session fermée avec le motif 'timed_out', au plus tard à l'instant donné
+/// - Aucune autre session du même user@host commencée entre son début et l'instant donné
+/// - Jointure sur la déconnexion insérée par le serveur (action='D', sans event_id)
+/// 
+/// **Paramètres** :
+/// - `?1` : username (TEXT)
+/// - `?2` : hostname (TEXT)
+/// - `?3` : instant de la déconnexion (TEXT ISO 8601) - borne de la fin de session
+/// - `?4` : instant de la déconnexion (TEXT ISO 8601) - borne des sessions suivantes
+/// 
+/// **Colonnes retournées** : Identiques à `SQL_FIND_SESSION_CLOSED_AFTER`
+/// 
+/// **Utilisé dans** : `database.rs::find_timed_out_session_before()`
+pub const SQL_FIND_TIMED_OUT_SESSION_BEFORE: &str = r#"
+    SELECT 
+        s.session_uuid,
+        d.id AS disconnect_id,
+        d.source AS disconnect_source,
+        s.ended_at AS disconnect_timestamp,
+        s.end_ip,
+        s.close_reason
+    FROM sessions s
+    INNER JOIN events_all d
+        ON d.session_uuid = s.session_uuid
+       AND d.action = 'D'
+       AND d.event_id IS NULL
+    WHERE s.username = ?
+      AND s.hostname = ?
+      AND s.close_reason = 'timed_out'
+      AND julianday(s.ended_at) <= julianday(?)
       AND NOT EXISTS (
-          SELECT 1 FROM events_today e2 
-          WHERE e2.session_uuid = events_today.session_uuid 
-            AND e2.action = 'D'
+          SELECT 1 FROM sessions n
+          WHERE n.username = s.username
+            AND n.hostname = s.hostname
+            AND julianday(n.started_at) > julianday(s.started_at)
+            AND julianday(n.started_at) <= julianday(?)
       )
-    ORDER BY timestamp DESC 
+    ORDER BY s.started_at DESC
+    LIMIT 1
+"#;
+
+/// Recherche la première connexion d'un user@host postérieure à un instant donné.
+/// 
+/// **Objectif** : Fermer une session reçue en retard qui a forcément pris fin avant
+///                la connexion suivante du même utilisateur sur la même machine.
+/// 
+/// **Logique** : Début de session le plus proche (index idx_sessions_user_host)
+/// 
+/// **Paramètres** :
+/// - `?1` : username (TEXT)
+/// - `?2` : hostname (TEXT)
+/// - `?3` : instant de référence (TEXT ISO 8601)
+/// 
+/// **Colonnes retournées** :
+/// - `started_at` : Heure de la connexion suivante
+/// 
+/// **Utilisé dans** : `database.rs::find_next_connection()`
+pub const SQL_FIND_NEXT_CONNECTION: &str = r#"
+    SELECT started_at
+    FROM sessions
+    WHERE username = ?
+      AND hostname = ?
+      AND julianday(started_at) > julianday(?)
+    ORDER BY started_at ASC
+    LIMIT 1
+"#;
+
+/// Retourne la date de la dernière rotation si un instant lui est antérieur.
+/// 
+/// **Objectif** : Ranger un événement reçu en retard dans events_history plutôt que
+///                dans events_today lorsque sa journée a déjà été archivée.
+/// 
+/// **Logique** :
+/// - Dernière rotation = plus grande valeur de archived_at (index idx_history_archived)
+///   ou de rotated_at des rotations réussies (une rotation sans événement à déplacer
+///   ne laisse pas de trace dans events_history)
+/// - Aucune ligne si l'instant est postérieur (ou s'il n'y a jamais eu de rotation)
+/// 
+/// **Paramètres** :
+/// - `?1` : timestamp de l'événement (TEXT ISO 8601)
+/// 
+/// **Colonnes retournées** :
+/// - `archived_at` : Date de la dernière rotation (reprise pour la ligne insérée)
+/// 
+/// **Utilisé dans** : `database.rs::rotation_after()`
+pub const SQL_FIND_ROTATION_AFTER: &str = r#"
+    SELECT archived_at
+    FROM (
+        SELECT MAX(archived_at) AS archived_at
+        FROM (
+            SELECT MAX(archived_at) AS archived_at FROM events_history
+            UNION ALL
+            SELECT MAX(rotated_at) FROM rotations WHERE status = 'success'
+        )
+    )
+    WHERE julianday(?) < julianday(archived_at)
+"#;
+
+/// Recherche un événement déjà enregistré à partir de l'identifiant généré par le client.
+/// 
+/// **Objectif** : Rendre l'envoi idempotent. Si le client renvoie un événement après un
+///                timeout alors que le serveur l'avait déjà validé, on retrouve l'original
+///                au lieu d'insérer un doublon (et de déclencher une fausse déconnexion auto).
+/// 
+/// **Logique** :
+/// - Cherche d'abord dans events_today, puis dans events_history (renvoi après rotation)
+/// - L'index unique idx_today_event_id garantit au plus un résultat dans events_today
+/// 
+/// **Paramètres** :
+/// - `?1` : event_id (TEXT UUID) - recherche dans events_today
+/// - `?2` : event_id (TEXT UUID) - recherche dans events_history
+/// 
+/// **Colonnes retournées** :
+/// - `id` : ID de la ligne d'origine
+/// - `username` : Nom d'utilisateur
+/// - `action` : Code action d'origine
+/// - `session_uuid` : Session attribuée à l'origine
+/// - `archived` : 1 si la ligne d'origine est dans events_history
+/// 
+/// **Utilisé dans** : `database.rs::find_event_by_event_id()`
+pub const SQL_FIND_EVENT_BY_EVENT_ID: &str = r#"
+    SELECT id, username, action, session_uuid, 0 AS archived FROM events_today WHERE event_id = ?
+    UNION ALL
+    SELECT id, username, action, session_uuid, 1 AS archived FROM events_history WHERE event_id = ?
     LIMIT 1
 "#;
 
@@ -116,26 +296,47 @@ pub const SQL_FIND_LAST_OPEN_SESSION: &str = r#"
 /// - Le timestamp est calculé comme : nouvelle_connexion - 1 seconde
 /// - Réutilise les infos système (os_name, os_version, etc.) de la nouvelle connexion
 /// - Associe la déconnexion à l'ancien session_uuid
+/// - Session expirée (aucun signal de présence) : déconnexion à l'heure du dernier
+///   signal, infos système de la session
 /// 
 /// **Paramètres** :
 /// - `?1` : username (TEXT)
 /// - `?2` : timestamp (TEXT ISO 8601) - calculé comme event.timestamp - 1 seconde
 /// - `?3` : hostname (TEXT, nullable)
-/// - `?4` : source_ip (TEXT) - IP du client
+/// - `?4` : source_ip (TEXT, nullable) - IP du client (NULL pour une session expirée)
 /// - `?5` : server_timestamp (TEXT ISO 8601) - timestamp serveur au moment de l'insertion
 /// - `?6` : os_name (TEXT, nullable)
 /// - `?7` : os_version (TEXT, nullable)
 /// - `?8` : kernel_version (TEXT, nullable)
 /// - `?9` : session_uuid (TEXT) - UUID de la session à fermer
+/// - `?10` : close_reason (TEXT) - motif de fermeture de la session ('auto_closed',
+///   'timed_out', 'policy_closed'), distingue la ligne d'une déconnexion réelle
 /// 
 /// **Note** : action='D' est hardcodé dans la requête (pas de paramètre)
 /// 
-/// **Utilisé dans** : `database.rs::insert_auto_disconnect()`
+/// **Utilisé dans** : `database.rs::insert_server_disconnect()` (déconnexion automatique,
+/// session expirée)
 pub const SQL_INSERT_AUTO_DISCONNECT: &str = r#"
     INSERT INTO events_today (
         username, action, timestamp, hostname, source_ip, server_timestamp,
-        os_name, os_version, kernel_version, session_uuid
-    ) VALUES (?, 'D', ?, ?, ?, ?, ?, ?, ?, ?)
+        os_name, os_version, kernel_version, session_uuid, close_reason
+    ) VALUES (?, 'D', ?, ?, ?, ?, ?, ?, ?, ?, ?)
+"#;
+
+/// Insère une déconnexion automatique directement dans events_history.
+/// 
+/// **Objectif** : Fermer une session dont la journée a déjà été archivée (événement reçu
+///                en retard).
+/// 
+/// **Paramètres** : Identiques à `SQL_INSERT_AUTO_DISCONNECT`, plus :
+/// - `?11` : archived_at (TEXT) - date de la rotation couvrant l'événement
+/// 
+/// **Utilisé dans** : `database.rs::insert_server_disconnect()`
+pub const SQL_INSERT_AUTO_DISCONNECT_HISTORY: &str = r#"
+    INSERT INTO events_history (
+        username, action, timestamp, hostname, source_ip, server_timestamp,
+        os_name, os_version, kernel_version, session_uuid, close_reason, created_at, archived_at
+    ) VALUES (?, 'D', ?, ?, ?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, ?)
 "#;
 
 /// Insère un nouvel événement (connexion, déconnexion ou inventaire matériel) dans events_today.
@@ -151,7 +352,7 @@ pub const SQL_INSERT_AUTO_DISCONNECT: &str = r#"
 /// 
 /// **Paramètres** :
 /// - `?1` : username (TEXT)
-/// - `?2` : action (TEXT) - 'C' = connexion, 'D' = déconnexion, 'M' = inventaire matériel
+/// - `?2` : action (TEXT) - code de `models::Action` ('C', 'D', 'M', 'L', 'U', 'B', 'S', 'H')
 /// - `?3` : timestamp (TEXT ISO 8601) - horodatage client
 /// - `?4` : hostname (TEXT, nullable)
 /// - `?5` : source_ip (TEXT) - adresse IP du client
@@ -161,6 +362,10 @@ pub const SQL_INSERT_AUTO_DISCONNECT: &str = r#"
 /// - `?9` : kernel_version (TEXT, nullable) - version du noyau
 /// - `?10` : hardware_info (TEXT JSON, nullable) - infos matérielles sérialisées
 /// - `?11` : session_uuid (TEXT) - identifiant de session généré
+/// - `?12` : event_id (TEXT UUID, nullable) - identifiant généré par le client
+/// - `?13` : peer_ip (TEXT) - adresse de la connexion TCP (proxy le cas échéant)
+/// - `?14` : clock_skew_secs (INTEGER, nullable) - écart d'horloge hors tolérance
+/// - `?15` : client_timestamp (TEXT ISO 8601, nullable) - timestamp d'origine si corrigé
 /// 
 /// **Retourne** : L'ID de la ligne insérée (last_insert_rowid)
 /// 
@@ -168,8 +373,931 @@ pub const SQL_INSERT_AUTO_DISCONNECT: &str = r#"
 pub const SQL_INSERT_EVENT: &str = r#"
     INSERT INTO events_today (
         username, action, timestamp, hostname, source_ip, server_timestamp,
-        os_name, os_version, kernel_version, hardware_info, session_uuid
+        os_name, os_version, kernel_version, hardware_info, session_uuid, event_id,
+        peer_ip, clock_skew_secs, client_timestamp
+    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+"#;
+
+/// Insère un événement reçu en retard directement dans events_history.
+/// 
+/// **Objectif** : Un événement antérieur à la dernière rotation appartient à une journée
+///                déjà archivée ; l'insérer dans events_today le ferait archiver une
+///                seconde fois, à la mauvaise date.
+/// 
+/// **Logique** :
+/// - archived_at reprend la date de la dernière rotation (et non l'heure d'insertion),
+///   pour que `SQL_FIND_ROTATION_AFTER` continue de désigner la vraie rotation
+/// 
+/// **Paramètres** : Identiques à `SQL_INSERT_EVENT`, plus :
+/// - `?16` : archived_at (TEXT) - date de la rotation couvrant l'événement
+/// 
+/// **Retourne** : L'ID de la ligne insérée dans events_history
+/// 
+/// **Utilisé dans** : `database.rs::insert_event()`
+pub const SQL_INSERT_EVENT_HISTORY: &str = r#"
+    INSERT INTO events_history (
+        username, action, timestamp, hostname, source_ip, server_timestamp,
+        os_name, os_version, kernel_version, hardware_info, session_uuid, event_id,
+        peer_ip, clock_skew_secs, client_timestamp, created_at, archived_at
+    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, ?)
+"#;
+
+// ============================================================================
+// REQUÊTES DE MISE À JOUR ET DE SUPPRESSION (réappariement des sessions)
+// ============================================================================
+
+/// Rattache un événement de events_today à une autre session.
+/// 
+/// **Objectif** : Une connexion reçue en retard reprend la déconnexion de la session
+///                qui l'englobait.
+/// 
+/// **Paramètres** :
+/// - `?1` : session_uuid (TEXT) - nouvelle session
+/// - `?2` : id de l'événement (INTEGER)
+/// 
+/// **Utilisé dans** : `database.rs::reassign_event()`
+pub const SQL_UPDATE_EVENT_SESSION_TODAY: &str = r#"
+    UPDATE events_today SET session_uuid = ? WHERE id = ?
+"#;
+
+/// Rattache un événement de events_history à une autre session.
+/// 
+/// **Paramètres** : Identiques à `SQL_UPDATE_EVENT_SESSION_TODAY`
+/// 
+/// **Utilisé dans** : `database.rs::reassign_event()`
+pub const SQL_UPDATE_EVENT_SESSION_HISTORY: &str = r#"
+    UPDATE events_history SET session_uuid = ? WHERE id = ?
+"#;
+
+/// Supprime une déconnexion automatique de events_today.
+/// 
+/// **Objectif** : La déconnexion réelle, reçue en retard, remplace celle que le serveur
+///                avait déduite de la connexion suivante.
+/// 
+/// **Logique** : Ne supprime que les déconnexions automatiques (action='D', sans event_id)
+/// 
+/// **Paramètres** :
+/// - `?1` : id de la déconnexion (INTEGER)
+/// 
+/// **Utilisé dans** : `database.rs::delete_auto_disconnect()`
+pub const SQL_DELETE_AUTO_DISCONNECT_TODAY: &str = r#"
+    DELETE FROM events_today WHERE id = ? AND action = 'D' AND event_id IS NULL
+"#;
+
+/// Supprime une déconnexion automatique de events_history.
+/// 
+/// **Paramètres** : Identiques à `SQL_DELETE_AUTO_DISCONNECT_TODAY`
+/// 
+/// **Utilisé dans** : `database.rs::delete_auto_disconnect()`
+pub const SQL_DELETE_AUTO_DISCONNECT_HISTORY: &str = r#"
+    DELETE FROM events_history WHERE id = ? AND action = 'D' AND event_id IS NULL
+"#;
+
+// ============================================================================
+// SESSIONS (table sessions)
+// ============================================================================
+
+/// Crée la ligne d'une nouvelle session.
+/// 
+/// **Objectif** : Tenir à jour une ligne par session, dans la même transaction que
+///                l'insertion de la connexion (action='C').
+/// 
+/// **Paramètres** :
+/// - `?1` : session_uuid (TEXT)
+/// - `?2` : username (TEXT)
+/// - `?3` : hostname (TEXT, nullable)
+/// - `?4` : started_at (TEXT ISO 8601) - timestamp de la connexion
+/// - `?5` : start_ip (TEXT) - IP du client
+/// - `?6` : os_name (TEXT, nullable)
+/// - `?7` : os_version (TEXT, nullable)
+/// - `?8` : updated_at (TEXT ISO 8601) - timestamp serveur
+/// 
+/// **Utilisé dans** : `database.rs::insert_session()`
+pub const SQL_INSERT_SESSION: &str = r#"
+    INSERT INTO sessions (
+        session_uuid, username, hostname, started_at, start_ip,
+        os_name, os_version, updated_at
+    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+"#;
+
+/// Ferme une session (ou déplace sa fin).
+/// 
+/// **Objectif** : Enregistrer la fin de session dans la même transaction que la
+///                déconnexion (réelle ou automatique).
+/// 
+/// **Logique** : Écrase une fin existante : un événement reçu en retard peut
+///               modifier l'appariement d'une session déjà fermée. Un verrouillage
+///               en cours (`locked_at`) prend fin avec la session : sa durée s'ajoute
+///               à `locked_seconds`.
+/// 
+/// **Paramètres** :
+/// - `?1` : ended_at (TEXT ISO 8601) - fin du verrouillage en cours
+/// - `?2` : ended_at (TEXT ISO 8601)
+/// - `?3` : end_ip (TEXT, nullable)
+/// - `?4` : close_reason (TEXT : 'explicit', 'auto_closed', 'timed_out' ou 'policy_closed')
+/// - `?5` : updated_at (TEXT ISO 8601) - timestamp serveur
+/// - `?6` : session_uuid (TEXT)
+/// 
+/// **Utilisé dans** : `database.rs::close_session()`
+pub const SQL_UPDATE_CLOSE_SESSION: &str = r#"
+    UPDATE sessions
+    SET locked_seconds = locked_seconds + COALESCE(
+            MAX(0, CAST(ROUND((julianday(?) - julianday(locked_at)) * 86400) AS INTEGER)), 0),
+        locked_at = NULL,
+        ended_at = ?, end_ip = ?, close_reason = ?, updated_at = ?
+    WHERE session_uuid = ?
+"#;
+
+/// Note le verrouillage de l'écran d'une session (action='L').
+/// 
+/// **Objectif** : Début d'une période d'inactivité, déduite du temps actif.
+/// 
+/// **Logique** : `locked_at IS NULL` : un verrouillage répété ne déplace pas le
+///               début de l'inactivité
+/// 
+/// **Paramètres** :
+/// - `?1` : locked_at (TEXT ISO 8601) - timestamp du verrouillage
+/// - `?2` : updated_at (TEXT ISO 8601) - timestamp serveur
+/// - `?3` : session_uuid (TEXT)
+/// 
+/// **Utilisé dans** : `database.rs::lock_session()`
+pub const SQL_UPDATE_LOCK_SESSION: &str = r#"
+    UPDATE sessions
+    SET locked_at = ?, updated_at = ?
+    WHERE session_uuid = ? AND locked_at IS NULL
+"#;
+
+/// Termine le verrouillage de l'écran d'une session (action='U').
+/// 
+/// **Objectif** : Cumuler la durée verrouillée de la session (temps actif =
+///                durée de la session - `locked_seconds`).
+/// 
+/// **Logique** :
+/// - Durée en secondes entre `locked_at` et le déverrouillage (nulle si négative :
+///   déverrouillage reçu dans le désordre)
+/// - Sans effet si la session n'est pas verrouillée
+/// 
+/// **Paramètres** :
+/// - `?1` : unlocked_at (TEXT ISO 8601) - timestamp du déverrouillage
+/// - `?2` : updated_at (TEXT ISO 8601) - timestamp serveur
+/// - `?3` : session_uuid (TEXT)
+/// 
+/// **Utilisé dans** : `database.rs::unlock_session()`
+pub const SQL_UPDATE_UNLOCK_SESSION: &str = r#"
+    UPDATE sessions
+    SET locked_seconds = locked_seconds
+            + MAX(0, CAST(ROUND((julianday(?) - julianday(locked_at)) * 86400) AS INTEGER)),
+        locked_at = NULL,
+        updated_at = ?
+    WHERE session_uuid = ? AND locked_at IS NOT NULL
+"#;
+
+/// Enregistre un signal de présence d'une session (action='H').
+/// 
+/// **Objectif** : Dater le dernier signe de vie de la machine, pour fermer la
+///                session s'il cesse (`SQL_FIND_STALE_SESSIONS`).
+/// 
+/// **Logique** : Conserve le plus récent des deux timestamps (signal reçu en retard,
+///               rejoué après un signal plus récent)
+/// 
+/// **Paramètres** :
+/// - `?1` : timestamp du signal (TEXT ISO 8601)
+/// - `?2` : timestamp du signal (TEXT ISO 8601)
+/// - `?3` : updated_at (TEXT ISO 8601) - timestamp serveur
+/// - `?4` : session_uuid (TEXT)
+/// 
+/// **Utilisé dans** : `database.rs::record_heartbeat()`
+pub const SQL_UPDATE_SESSION_HEARTBEAT: &str = r#"
+    UPDATE sessions
+    SET last_heartbeat_at = CASE
+            WHEN last_heartbeat_at IS NULL OR julianday(?) > julianday(last_heartbeat_at) THEN ?
+            ELSE last_heartbeat_at
+        END,
+        updated_at = ?
+    WHERE session_uuid = ?
+"#;
+
+/// Recherche les sessions ouvertes dont le dernier signal de présence est trop ancien.
+/// 
+/// **Objectif** : Fermer les sessions d'une machine qui a cessé de répondre (coupure de
+///                courant, plantage) sans attendre la connexion suivante de l'utilisateur.
+/// 
+/// **Logique** :
+/// - Sessions ouvertes ayant reçu au moins un signal (index partiel idx_sessions_heartbeat)
+/// - Dernier signal antérieur à la date limite (maintenant - délai configuré)
+/// 
+/// **Paramètres** :
+/// - `?1` : date limite (TEXT ISO 8601 UTC)
+/// 
+/// **Colonnes retournées** :
+/// - `session_uuid`, `username`, `hostname`, `os_name`, `os_version` : session à fermer
+/// - `last_heartbeat_at` : dernier signal (heure de la déconnexion insérée)
+/// 
+/// **Utilisé dans** : `database.rs::close_stale_sessions()`
+pub const SQL_FIND_STALE_SESSIONS: &str = r#"
+    SELECT session_uuid, username, hostname, os_name, os_version, last_heartbeat_at
+    FROM sessions
+    WHERE ended_at IS NULL
+      AND last_heartbeat_at IS NOT NULL
+      AND julianday(last_heartbeat_at) < julianday(?)
+    ORDER BY last_heartbeat_at ASC
+"#;
+
+/// Recherche les sessions ouvertes commencées avant une date limite.
+/// 
+/// **Objectif** : Fermer les sessions qui dépassent la durée maximale
+///                (`[sessions] max_session_hours`, globale ou par groupe de machines).
+/// 
+/// **Logique** :
+/// - Sessions ouvertes (index partiel idx_sessions_open_started)
+/// - Date limite = maintenant - plus courte durée maximale configurée : la durée
+///   propre à la machine de chaque session est vérifiée ensuite (`database.rs`)
+/// 
+/// **Paramètres** :
+/// - `?1` : date limite (TEXT ISO 8601 UTC)
+/// 
+/// **Colonnes retournées** :
+/// - `session_uuid`, `username`, `hostname`, `os_name`, `os_version` : session candidate
+/// - `started_at` : début de la session (fin = début + durée maximale)
+/// 
+/// **Utilisé dans** : `database.rs::close_overdue_sessions()`
+pub const SQL_FIND_OPEN_SESSIONS_STARTED_BEFORE: &str = r#"
+    SELECT session_uuid, username, hostname, os_name, os_version, started_at
+    FROM sessions
+    WHERE ended_at IS NULL
+      AND julianday(started_at) <= julianday(?)
+    ORDER BY started_at ASC
+"#;
+
+/// Recherche les sessions ouvertes des autres utilisateurs d'une machine.
+/// 
+/// **Objectif** : Fermer les sessions d'un poste à place unique
+///                (`SeatPolicy::SingleSeat`) lorsqu'un autre utilisateur s'y connecte.
+/// 
+/// **Logique** :
+/// - Sessions ouvertes de la machine (index partiel idx_sessions_open)
+/// - Autres utilisateurs uniquement (la session du même utilisateur suit la règle habituelle)
+/// - Ignore les sessions ouvertes après la connexion (connexion reçue en retard)
+/// 
+/// **Paramètres** :
+/// - `?1` : hostname (TEXT)
+/// - `?2` : username de la connexion (TEXT)
+/// - `?3` : timestamp de la connexion (TEXT ISO 8601)
+/// 
+/// **Colonnes retournées** :
+/// - `session_uuid`, `username`, `os_name`, `os_version` : session à fermer
+/// 
+/// **Utilisé dans** : `database.rs::close_other_host_sessions()`
+pub const SQL_FIND_OTHER_OPEN_SESSIONS_ON_HOST: &str = r#"
+    SELECT session_uuid, username, os_name, os_version
+    FROM sessions
+    WHERE hostname = ?
+      AND username <> ?
+      AND ended_at IS NULL
+      AND julianday(started_at) <= julianday(?)
+    ORDER BY started_at ASC
+"#;
+
+// ============================================================================
+// ROTATION QUOTIDIENNE (table rotations)
+// ============================================================================
+
+/// Compte les événements de events_today.
+/// 
+/// **Utilisé dans** : `database.rs::rotate()` (comptages avant/après)
+pub const SQL_COUNT_EVENTS_TODAY: &str = r#"
+    SELECT COUNT(*) FROM events_today
+"#;
+
+/// Compte les événements de events_history.
+/// 
+/// **Utilisé dans** : `database.rs::rotate()` (comptages avant/après)
+pub const SQL_COUNT_EVENTS_HISTORY: &str = r#"
+    SELECT COUNT(*) FROM events_history
+"#;
+
+/// Compte les sessions encore ouvertes.
+/// 
+/// **Objectif** : Journaliser les sessions reportées sur la nouvelle journée. Leur
+///                connexion reste dans events_today (voir `SQL_INSERT_ROTATE_TO_HISTORY`)
+///                et leur ligne de la table sessions reste ouverte : la déconnexion du
+///                lendemain les ferme.
+/// 
+/// **Utilisé dans** : `database.rs::rotate()`
+pub const SQL_COUNT_OPEN_SESSIONS: &str = r#"
+    SELECT COUNT(*) FROM sessions WHERE ended_at IS NULL
+"#;
+
+/// Copie les événements de events_today dans events_history, sauf les connexions
+/// des sessions encore ouvertes.
+/// 
+/// **Objectif** : Première étape de la rotation quotidienne (même transaction que
+///                `SQL_DELETE_EVENTS_TODAY`).
+/// 
+/// **Logique** :
+/// - Une session à cheval sur minuit (connexion à 22:00, déconnexion à 06:00) garde
+///   sa connexion dans events_today : la connexion et la déconnexion du lendemain
+///   restent dans la même table
+/// - Sessions ouvertes : table sessions, index partiel idx_sessions_open
+/// - Toutes les autres lignes (déconnexions, matériel, sessions fermées) sont archivées
+/// 
+/// **Paramètres** :
+/// - `?1` : archived_at (TEXT, format CURRENT_TIMESTAMP) - début de la rotation
+/// 
+/// **Utilisé dans** : `database.rs::rotate()`
+pub const SQL_INSERT_ROTATE_TO_HISTORY: &str = r#"
+    INSERT INTO events_history (
+        username, action, timestamp, hostname, source_ip, server_timestamp,
+        os_name, os_version, kernel_version, hardware_info, session_uuid, event_id,
+        peer_ip, created_at, archived_at, close_reason, clock_skew_secs, client_timestamp
+    )
+    SELECT 
+        username, action, timestamp, hostname, source_ip, server_timestamp,
+        os_name, os_version, kernel_version, hardware_info, session_uuid, event_id,
+        peer_ip, created_at, ?, close_reason, clock_skew_secs, client_timestamp
+    FROM events_today
+    WHERE NOT (action = 'C' AND session_uuid IN (SELECT session_uuid FROM sessions WHERE ended_at IS NULL))
+    ORDER BY id
+"#;
+
+/// Vide events_today après copie dans events_history.
+/// 
+/// **Logique** : Même filtre que `SQL_INSERT_ROTATE_TO_HISTORY` (les connexions des
+///               sessions encore ouvertes sont conservées)
+/// 
+/// **Utilisé dans** : `database.rs::rotate()`
+pub const SQL_DELETE_EVENTS_TODAY: &str = r#"
+    DELETE FROM events_today
+    WHERE NOT (action = 'C' AND session_uuid IN (SELECT session_uuid FROM sessions WHERE ended_at IS NULL))
+"#;
+
+/// Réinitialise l'auto-incrément de events_today.
+/// 
+/// **Logique** : Sans entrée dans sqlite_sequence, SQLite repart du plus grand id
+///               restant (connexions reportées) : pas de collision
+/// 
+/// **Utilisé dans** : `database.rs::rotate()`
+pub const SQL_DELETE_EVENTS_TODAY_SEQUENCE: &str = r#"
+    DELETE FROM sqlite_sequence WHERE name = 'events_today'
+"#;
+
+/// Journalise une exécution de la rotation.
+/// 
+/// **Paramètres** :
+/// - `?1` : rotated_at (TEXT, format CURRENT_TIMESTAMP)
+/// - `?2` : triggered_by (TEXT : 'scheduled', 'catch_up' ou 'manual')
+/// - `?3` : status (TEXT : 'success' ou 'failed')
+/// - `?4` à `?7` : today_before, history_before, today_after, history_after (INTEGER, nullable)
+/// - `?8` : archived (INTEGER, nullable) - lignes déplacées
+/// - `?9` : open_sessions (INTEGER, nullable)
+/// - `?10` : duration_ms (INTEGER)
+/// - `?11` : error (TEXT, nullable)
+/// 
+/// **Colonnes retournées** : la ligne insérée (RETURNING)
+/// 
+/// **Utilisé dans** : `database.rs::rotate()`
+pub const SQL_INSERT_ROTATION: &str = r#"
+    INSERT INTO rotations (
+        rotated_at, triggered_by, status,
+        today_before, history_before, today_after, history_after,
+        archived, open_sessions, duration_ms, error
     ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+    RETURNING id, rotated_at, triggered_by, status,
+              today_before, history_before, today_after, history_after,
+              archived, open_sessions, duration_ms, error
+"#;
+
+/// Indique si events_today contient des événements reçus avant un instant donné.
+/// 
+/// **Objectif** : Rattraper au démarrage une rotation manquée (serveur arrêté à
+///                l'heure prévue).
+/// 
+/// **Logique** :
+/// - created_at (CURRENT_TIMESTAMP, UTC) est comparé à l'heure de la dernière rotation
+///   prévue, dans le même format
+/// - Les connexions des sessions encore ouvertes, conservées par la rotation, sont ignorées
+/// 
+/// **Paramètres** :
+/// - `?1` : heure de la dernière rotation prévue (TEXT, format CURRENT_TIMESTAMP)
+/// 
+/// **Colonnes retournées** : 1 si une rotation est en retard, 0 sinon
+/// 
+/// **Utilisé dans** : `database.rs::rotation_overdue()`
+pub const SQL_FIND_ROTATION_OVERDUE: &str = r#"
+    SELECT EXISTS (
+        SELECT 1 FROM events_today
+        WHERE created_at < ?
+          AND NOT (action = 'C' AND session_uuid IN (SELECT session_uuid FROM sessions WHERE ended_at IS NULL))
+    )
+"#;
+
+// ============================================================================
+// CONSERVATION (purge de events_history, table purges)
+// ============================================================================
+
+/// Supprime un lot d'événements expirés de events_history pour un type d'action.
+/// 
+/// **Objectif** : Appliquer la durée de conservation `[database.retention]` sans
+///                bloquer la collecte (un lot = une transaction courte).
+/// 
+/// **Logique** :
+/// - Date de l'événement (timestamp) comparée via julianday (fuseaux horaires du client)
+/// - Borne sur l'id : seules les lignes déjà présentes lors de l'export Parquet sont
+///   supprimées (un événement tardif inséré entre-temps attend la purge suivante)
+/// - Plus anciens en premier (ORDER BY id) : le parcours s'arrête dès le lot complet
+/// - À répéter jusqu'à ce que moins de `?4` lignes soient supprimées
+/// 
+/// **Paramètres** :
+/// - `?1` : action (TEXT, code de `models::Action`)
+/// - `?2` : date limite (TEXT ISO 8601 UTC) - événements antérieurs supprimés
+/// - `?3` : id maximal (INTEGER) - `SQL_FIND_HISTORY_MAX_ID` avant export
+/// - `?4` : taille du lot (INTEGER)
+/// 
+/// **Utilisé dans** : `database.rs::purge_history_batch()`
+pub const SQL_DELETE_HISTORY_EXPIRED: &str = r#"
+    DELETE FROM events_history
+    WHERE id IN (
+        SELECT id
+        FROM events_history
+        WHERE action = ?
+          AND julianday(timestamp) < julianday(?)
+          AND id <= ?
+        ORDER BY id
+        LIMIT ?
+    )
+"#;
+
+/// Retourne le plus grand id de events_history.
+/// 
+/// **Objectif** : Borner la suppression aux lignes exportées (voir `SQL_DELETE_HISTORY_EXPIRED`).
+/// 
+/// **Colonnes retournées** : id maximal (0 si la table est vide)
+/// 
+/// **Utilisé dans** : `database.rs::history_max_id()`
+pub const SQL_FIND_HISTORY_MAX_ID: &str = r#"
+    SELECT COALESCE(MAX(id), 0) FROM events_history
+"#;
+
+/// Liste les dates (UTC) des événements expirés d'un type d'action.
+/// 
+/// **Objectif** : Découper l'export Parquet en partitions journalières (`date=AAAA-MM-JJ`).
+/// 
+/// **Logique** :
+/// - DATE() convertit le timestamp du client en date UTC (index idx_history_date)
+/// - Lignes déjà exportées (exported_at, purge précédente interrompue) exclues
+/// 
+/// **Paramètres** :
+/// - `?1` : action (TEXT)
+/// - `?2` : date limite (TEXT ISO 8601 UTC)
+/// - `?3` : id maximal (INTEGER)
+/// 
+/// **Colonnes retournées** : date (TEXT AAAA-MM-JJ), croissante
+/// 
+/// **Utilisé dans** : `database.rs::expired_history_dates()`
+pub const SQL_FIND_EXPIRED_HISTORY_DATES: &str = r#"
+    SELECT DISTINCT DATE(timestamp) AS date
+    FROM events_history
+    WHERE action = ?
+      AND julianday(timestamp) < julianday(?)
+      AND id <= ?
+      AND exported_at IS NULL
+    ORDER BY date
+"#;
+
+/// Retourne les événements expirés d'un type d'action pour une date donnée.
+/// 
+/// **Objectif** : Contenu d'une partition de l'archive Parquet.
+/// 
+/// **Logique** : lignes non encore exportées uniquement (exported_at NULL)
+/// 
+/// **Paramètres** :
+/// - `?1` : action (TEXT)
+/// - `?2` : date limite (TEXT ISO 8601 UTC)
+/// - `?3` : id maximal (INTEGER)
+/// - `?4` : date de la partition (TEXT AAAA-MM-JJ)
+/// 
+/// **Colonnes retournées** : toutes les colonnes de events_history, par id croissant
+/// 
+/// **Utilisé dans** : `database.rs::expired_history_on()`
+pub const SQL_FIND_EXPIRED_HISTORY_ON_DATE: &str = r#"
+    SELECT 
+        id, username, action, timestamp, hostname, source_ip, peer_ip, server_timestamp,
+        os_name, os_version, kernel_version, hardware_info, session_uuid, event_id,
+        created_at, archived_at, close_reason, clock_skew_secs, client_timestamp
+    FROM events_history
+    WHERE action = ?
+      AND julianday(timestamp) < julianday(?)
+      AND id <= ?
+      AND DATE(timestamp) = ?
+      AND exported_at IS NULL
+    ORDER BY id
+"#;
+
+/// Marque comme exportées les lignes d'une partition écrite en Parquet.
+/// 
+/// **Objectif** : Ne pas réexporter ces lignes (doublons dans les archives) si leur
+///                suppression échoue : la purge suivante les supprime sans les exporter.
+/// 
+/// **Logique** : mêmes critères que `SQL_FIND_EXPIRED_HISTORY_ON_DATE` ; les lignes
+/// d'id au plus `?4` ne sont modifiées que par la purge
+/// 
+/// **Paramètres** :
+/// - `?1` : exported_at (TEXT, format CURRENT_TIMESTAMP)
+/// - `?2` : action (TEXT)
+/// - `?3` : date limite (TEXT ISO 8601 UTC)
+/// - `?4` : id maximal (INTEGER)
+/// - `?5` : date de la partition (TEXT AAAA-MM-JJ)
+/// 
+/// **Utilisé dans** : `database.rs::mark_history_exported()`
+pub const SQL_MARK_HISTORY_EXPORTED: &str = r#"
+    UPDATE events_history
+    SET exported_at = ?
+    WHERE action = ?
+      AND julianday(timestamp) < julianday(?)
+      AND id <= ?
+      AND DATE(timestamp) = ?
+      AND exported_at IS NULL
+"#;
+
+/// Restaure une ligne d'archive Parquet dans events_history (base de travail).
+/// 
+/// **Objectif** : Sous-commande `restore-archive` (investigations sur des données purgées).
+/// 
+/// **Logique** : id d'origine conservé ; OR IGNORE rend la restauration rejouable
+/// 
+/// **Paramètres** : les 19 colonnes de events_history, dans l'ordre de la table
+/// 
+/// **Utilisé dans** : `database.rs::insert_archived_events()`
+pub const SQL_INSERT_ARCHIVED_EVENT: &str = r#"
+    INSERT OR IGNORE INTO events_history (
+        id, username, action, timestamp, hostname, source_ip, peer_ip, server_timestamp,
+        os_name, os_version, kernel_version, hardware_info, session_uuid, event_id,
+        created_at, archived_at, close_reason, clock_skew_secs, client_timestamp
+    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+"#;
+
+/// Supprime un lot de sessions terminées dont plus aucun événement ne subsiste.
+/// 
+/// **Objectif** : L'historique des connexions est aussi conservé dans la table sessions ;
+///                une session disparaît avec ses derniers événements.
+/// 
+/// **Logique** :
+/// - Sessions terminées uniquement (une session ouverte n'est jamais purgée)
+/// - Aucun événement (connexion, déconnexion) dans events_all (index idx_*_session)
+/// 
+/// **Paramètres** :
+/// - `?1` : taille du lot (INTEGER)
+/// 
+/// **Utilisé dans** : `database.rs::purge_sessions_batch()`
+pub const SQL_DELETE_SESSIONS_EXPIRED: &str = r#"
+    DELETE FROM sessions
+    WHERE session_uuid IN (
+        SELECT s.session_uuid
+        FROM sessions s
+        WHERE s.ended_at IS NOT NULL
+          AND NOT EXISTS (SELECT 1 FROM events_all e WHERE e.session_uuid = s.session_uuid)
+        LIMIT ?
+    )
+"#;
+
+/// Supprime un lot de réappariements de déconnexions orphelines antérieurs à une date.
+/// 
+/// **Objectif** : Appliquer `[database.retention] orphan_repairs_days` (noms
+///                d'utilisateur et de machine journalisés à chaque réparation).
+/// 
+/// **Logique** : plus anciens en premier (index idx_orphan_repairs_date), à répéter
+/// jusqu'à ce que moins de `?2` lignes soient supprimées
+/// 
+/// **Paramètres** :
+/// - `?1` : date limite (TEXT ISO 8601 UTC) - réparations antérieures supprimées
+/// - `?2` : taille du lot (INTEGER)
+/// 
+/// **Utilisé dans** : `database.rs::purge_orphan_repairs_batch()`
+pub const SQL_DELETE_ORPHAN_REPAIRS_EXPIRED: &str = r#"
+    DELETE FROM orphan_repairs
+    WHERE id IN (
+        SELECT id
+        FROM orphan_repairs
+        WHERE julianday(repaired_at) < julianday(?)
+        ORDER BY id
+        LIMIT ?
+    )
+"#;
+
+/// Supprime un lot de machines dont l'horloge n'a plus été mesurée depuis une date.
+/// 
+/// **Objectif** : Appliquer `[database.retention] host_clocks_days` (machine retirée
+///                ou renommée : sa ligne host_clocks n'est plus mise à jour).
+/// 
+/// **Logique** : une machine à nouveau mesurée après la purge repart d'une nouvelle
+/// ligne (bilan remis à zéro)
+/// 
+/// **Paramètres** :
+/// - `?1` : date limite (TEXT ISO 8601 UTC) - dernière mesure antérieure : supprimée
+/// - `?2` : taille du lot (INTEGER)
+/// 
+/// **Utilisé dans** : `database.rs::purge_host_clocks_batch()`
+pub const SQL_DELETE_HOST_CLOCKS_EXPIRED: &str = r#"
+    DELETE FROM host_clocks
+    WHERE hostname IN (
+        SELECT hostname
+        FROM host_clocks
+        WHERE julianday(last_seen_at) < julianday(?)
+        LIMIT ?
+    )
+"#;
+
+/// Journalise le bilan d'une règle de conservation.
+/// 
+/// **Paramètres** :
+/// - `?1` : purged_at (TEXT, format CURRENT_TIMESTAMP)
+/// - `?2` : target (TEXT : 'events_history', 'sessions', 'orphan_repairs' ou 'host_clocks')
+/// - `?3` : action (TEXT, nullable)
+/// - `?4` : retention_days (INTEGER, nullable)
+/// - `?5` : cutoff (TEXT ISO 8601, nullable)
+/// - `?6` : deleted (INTEGER)
+/// - `?7` : batches (INTEGER)
+/// - `?8` : archive_files (INTEGER, nullable) - fichiers Parquet écrits
+/// - `?9` : duration_ms (INTEGER)
+/// - `?10` : status (TEXT : 'success' ou 'failed')
+/// - `?11` : error (TEXT, nullable)
+/// 
+/// **Utilisé dans** : `database.rs::insert_purge()`
+pub const SQL_INSERT_PURGE: &str = r#"
+    INSERT INTO purges (
+        purged_at, target, action, retention_days, cutoff,
+        deleted, batches, archive_files, duration_ms, status, error
+    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+"#;
+
+// ============================================================================
+// RÉCONCILIATION DES DÉCONNEXIONS ORPHELINES (table orphan_repairs)
+// ============================================================================
+
+/// Recherche les déconnexions orphelines qui peuvent être rattachées à une session.
+/// 
+/// **Objectif** : Réapparier une déconnexion arrivée sans session ouverte (connexion
+///                perdue puis rejouée, ou reçue après elle) avec sa connexion.
+/// 
+/// **Logique** :
+/// - Déconnexions (action='D') dont le session_uuid commence par 'orphan_'
+///   (index partiels idx_today_orphans / idx_history_orphans, vue events_all)
+/// - Session la plus proche : dernière session du même user@host commencée au plus
+///   tard à l'heure de la déconnexion, dans les 24 heures qui la précèdent (aucune
+///   autre connexion entre les deux)
+/// - Session retenue si encore ouverte, expirée ('timed_out'), ou fermée par le serveur
+///   ('auto_closed', 'policy_closed') après l'heure de la déconnexion
+/// - Jointure facultative sur la déconnexion insérée par le serveur (sans event_id),
+///   remplacée par la déconnexion orpheline
+/// - Périmètre : un user@host (après une connexion) ou toutes les machines (rotation)
+/// - Fenêtre : à la rotation, seules les déconnexions des `orphan_window_hours` dernières
+///   heures sont examinées (index idx_*_user_host_action)
+/// 
+/// **Paramètres** :
+/// - `?1` : username (TEXT, NULL = toutes les machines)
+/// - `?2` : username (TEXT)
+/// - `?3` : hostname (TEXT)
+/// - `?4` : date limite (TEXT ISO 8601 UTC, NULL = sans limite)
+/// - `?5` : date limite (TEXT)
+/// 
+/// **Colonnes retournées** :
+/// - `orphan_id`, `orphan_source` : Ligne de la déconnexion orpheline ('today' ou 'history')
+/// - `orphan_uuid`, `username`, `hostname`, `disconnect_timestamp`, `source_ip` : Déconnexion
+/// - `session_uuid`, `session_started_at` : Session retrouvée
+/// - `previous_ended_at`, `previous_close_reason` : Fin actuelle de la session (NULL : ouverte)
+/// - `server_disconnect_id`, `server_disconnect_source` : Déconnexion insérée par le serveur (NULL : aucune)
+/// 
+/// **Utilisé dans** : `database.rs::reconcile_orphans()`
+pub const SQL_FIND_ORPHAN_PAIRINGS: &str = r#"
+    SELECT
+        o.id AS orphan_id,
+        o.source AS orphan_source,
+        o.session_uuid AS orphan_uuid,
+        o.username,
+        o.hostname,
+        o.timestamp AS disconnect_timestamp,
+        o.source_ip,
+        s.session_uuid,
+        s.started_at AS session_started_at,
+        s.ended_at AS previous_ended_at,
+        s.close_reason AS previous_close_reason,
+        d.id AS server_disconnect_id,
+        d.source AS server_disconnect_source
+    FROM events_all o
+    INNER JOIN sessions s
+        ON s.session_uuid = (
+            SELECT n.session_uuid
+            FROM sessions n
+            WHERE n.username = o.username
+              AND n.hostname = o.hostname
+              AND julianday(n.started_at) <= julianday(o.timestamp)
+              AND julianday(n.started_at) > julianday(o.timestamp) - 1
+            ORDER BY n.started_at DESC
+            LIMIT 1
+        )
+    LEFT JOIN events_all d
+        ON d.session_uuid = s.session_uuid
+       AND d.action = 'D'
+       AND d.event_id IS NULL
+    WHERE o.action = 'D'
+      AND o.session_uuid LIKE 'orphan\_%' ESCAPE '\'
+      AND (? IS NULL OR (o.username = ? AND o.hostname = ?))
+      AND (? IS NULL OR julianday(o.timestamp) >= julianday(?))
+      AND (
+          s.ended_at IS NULL
+          OR s.close_reason = 'timed_out'
+          OR (s.close_reason IN ('auto_closed', 'policy_closed')
+              AND julianday(s.ended_at) >= julianday(o.timestamp))
+      )
+    ORDER BY o.timestamp ASC
+"#;
+
+/// Journalise le réappariement d'une déconnexion orpheline.
+/// 
+/// **Paramètres** :
+/// - `?1` : repaired_at (TEXT, format CURRENT_TIMESTAMP UTC)
+/// - `?2` : triggered_by (TEXT : 'late_connection' ou 'rotation')
+/// - `?3` à `?6` : username, hostname, disconnect_timestamp, orphan_uuid
+/// - `?7` à `?10` : session_uuid, session_started_at, previous_ended_at, previous_close_reason
+/// 
+/// **Colonnes retournées** : la ligne insérée (RETURNING)
+/// 
+/// **Utilisé dans** : `database.rs::reconcile_orphans()`
+pub const SQL_INSERT_ORPHAN_REPAIR: &str = r#"
+    INSERT INTO orphan_repairs (
+        repaired_at, triggered_by,
+        username, hostname, disconnect_timestamp, orphan_uuid,
+        session_uuid, session_started_at, previous_ended_at, previous_close_reason
+    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+    RETURNING id, repaired_at, triggered_by,
+              username, hostname, disconnect_timestamp, orphan_uuid,
+              session_uuid, session_started_at, previous_ended_at, previous_close_reason
+"#;
+
+/// Compte les déconnexions orphelines non réappariées, par machine.
+/// 
+/// **Objectif** : Suivre le taux d'orphelines (GET /api/v1/admin/orphans) et repérer
+///                les machines concernées (décalage d'horloge, réseau instable).
+/// 
+/// **Logique** : Déconnexions 'orphan_' de events_today et events_history (vue events_all)
+/// 
+/// **Paramètres** : Aucun
+/// 
+/// **Colonnes retournées** :
+/// - `hostname` : Machine
+/// - `unrepaired` : Nombre de déconnexions orphelines
+/// - `last_orphan_at` : Heure de la plus récente
+/// 
+/// **Utilisé dans** : `database.rs::orphan_stats()`
+pub const SQL_COUNT_ORPHANS_BY_HOST: &str = r#"
+    SELECT hostname, COUNT(*) AS unrepaired, MAX(timestamp) AS last_orphan_at
+    FROM events_all
+    WHERE action = 'D'
+      AND session_uuid LIKE 'orphan\_%' ESCAPE '\'
+    GROUP BY hostname
+    ORDER BY unrepaired DESC, hostname ASC
+"#;
+
+/// Compte les déconnexions orphelines réappariées (table orphan_repairs).
+/// 
+/// **Utilisé dans** : `database.rs::orphan_stats()`
+pub const SQL_COUNT_ORPHAN_REPAIRS: &str = r#"
+    SELECT COUNT(*) FROM orphan_repairs
+"#;
+
+// ============================================================================
+// ÉCART D'HORLOGE DES MACHINES (table host_clocks)
+// ============================================================================
+
+/// Enregistre une mesure de l'écart d'horloge d'une machine.
+/// 
+/// **Objectif** : Suivre l'horloge de chaque machine (NTP en panne, pile CMOS usée) et
+///                fournir l'écart à appliquer aux événements rejoués (`[clock] on_skew`).
+/// 
+/// **Logique** :
+/// - Mesure sur un événement envoyé en direct : timestamp client - heure de réception
+/// - Création de la ligne à la première mesure, sinon mise à jour (dernière mesure,
+///   bornes, nombre de mesures et de mesures hors tolérance)
+/// 
+/// **Paramètres** :
+/// - `?1` : hostname (TEXT)
+/// - `?2` : écart mesuré (INTEGER, secondes)
+/// - `?3` : heure de réception de l'événement (TEXT ISO 8601, UTC)
+/// - `?4` : hors tolérance (INTEGER 0/1)
+/// 
+/// **Utilisé dans** : `database.rs::record_clock_skew()`
+pub const SQL_UPSERT_HOST_CLOCK: &str = r#"
+    INSERT INTO host_clocks (
+        hostname, skew_secs, last_seen_at, samples, min_skew_secs, max_skew_secs,
+        out_of_tolerance, last_out_of_tolerance_at
+    ) VALUES (?1, ?2, ?3, 1, ?2, ?2, ?4, CASE WHEN ?4 THEN ?3 END)
+    ON CONFLICT(hostname) DO UPDATE SET
+        skew_secs = excluded.skew_secs,
+        last_seen_at = excluded.last_seen_at,
+        samples = samples + 1,
+        min_skew_secs = MIN(min_skew_secs, excluded.skew_secs),
+        max_skew_secs = MAX(max_skew_secs, excluded.skew_secs),
+        out_of_tolerance = out_of_tolerance + excluded.out_of_tolerance,
+        last_out_of_tolerance_at = COALESCE(excluded.last_out_of_tolerance_at, last_out_of_tolerance_at)
+"#;
+
+/// Retourne le dernier écart d'horloge mesuré d'une machine.
+/// 
+/// **Objectif** : Marquer, refuser ou corriger un événement rejoué (POST
+///                /api/v1/events/batch), dont l'écart ne peut pas être mesuré.
+/// 
+/// **Paramètres** :
+/// - `?1` : hostname (TEXT)
+/// 
+/// **Colonnes retournées** : skew_secs (INTEGER), aucune ligne si jamais mesuré
+/// 
+/// **Utilisé dans** : `database.rs::host_clock_skew()`
+pub const SQL_FIND_HOST_CLOCK_SKEW: &str = r#"
+    SELECT skew_secs FROM host_clocks WHERE hostname = ?
+"#;
+
+/// Liste l'écart d'horloge des machines, les plus décalées en premier.
+/// 
+/// **Utilisé dans** : `database.rs::list_host_clocks()` (GET /api/v1/admin/clocks)
+pub const SQL_LIST_HOST_CLOCKS: &str = r#"
+    SELECT hostname, skew_secs, last_seen_at, samples, min_skew_secs, max_skew_secs,
+           out_of_tolerance, last_out_of_tolerance_at
+    FROM host_clocks
+    ORDER BY ABS(skew_secs) DESC, hostname ASC
+"#;
+
+// ============================================================================
+// JETONS D'ENRÔLEMENT (table host_tokens)
+// ============================================================================
+
+/// Recherche la machine associée à un jeton valide.
+/// 
+/// **Objectif** : Authentifier une requête de collecte (en-tête `Authorization: Bearer`).
+/// 
+/// **Logique** :
+/// - Comparaison sur l'empreinte SHA-256 du jeton (le jeton en clair n'est pas stocké)
+/// - Les jetons révoqués sont ignorés
+/// 
+/// **Paramètres** :
+/// - `?1` : token_hash (TEXT hex SHA-256)
+/// 
+/// **Colonnes retournées** :
+/// - `hostname` : Machine à laquelle le jeton a été délivré
+/// 
+/// **Utilisé dans** : `database.rs::find_token_hostname()`
+pub const SQL_FIND_TOKEN_HOSTNAME: &str = r#"
+    SELECT hostname
+    FROM host_tokens
+    WHERE token_hash = ?
+      AND revoked_at IS NULL
+"#;
+
+/// Enregistre un nouveau jeton d'enrôlement.
+/// 
+/// **Logique** : L'index unique partiel idx_tokens_active_host fait échouer l'insertion
+///               si la machine possède déjà un jeton non révoqué.
+/// 
+/// **Paramètres** :
+/// - `?1` : hostname (TEXT)
+/// - `?2` : token_hash (TEXT hex SHA-256)
+/// - `?3` : created_at (TEXT ISO 8601)
+/// 
+/// **Retourne** : L'ID du jeton (last_insert_rowid)
+/// 
+/// **Utilisé dans** : `database.rs::insert_host_token()`
+pub const SQL_INSERT_HOST_TOKEN: &str = r#"
+    INSERT INTO host_tokens (hostname, token_hash, created_at)
+    VALUES (?, ?, ?)
+"#;
+
+/// Liste tous les jetons (actifs et révoqués), sans leur empreinte.
+/// 
+/// **Paramètres** : Aucun
+/// 
+/// **Colonnes retournées** :
+/// - `id`, `hostname`, `created_at`, `revoked_at` (NULL si actif)
+/// 
+/// **Utilisé dans** : `database.rs::list_host_tokens()`
+pub const SQL_LIST_HOST_TOKENS: &str = r#"
+    SELECT id, hostname, created_at, revoked_at
+    FROM host_tokens
+    ORDER BY hostname ASC, created_at DESC
+"#;
+
+/// Révoque un jeton (les révocations sont définitives).
+/// 
+/// **Logique** : Ne modifie pas un jeton déjà révoqué (conserve la date d'origine)
+/// 
+/// **Paramètres** :
+/// - `?1` : revoked_at (TEXT ISO 8601)
+/// - `?2` : id du jeton (INTEGER)
+/// 
+/// **Utilisé dans** : `database.rs::revoke_host_token()`
+pub const SQL_UPDATE_REVOKE_HOST_TOKEN: &str = r#"
+    UPDATE host_tokens
+    SET revoked_at = ?
+    WHERE id = ?
+      AND revoked_at IS NULL
 "#;
 
 // ============================================================================
@@ -180,55 +1308,53 @@ pub const SQL_INSERT_EVENT: &str = r#"
 //        Elles sont documentées ici pour référence et usage futur potentiel.
 //        Elles sont actuellement mentionnées dans README.md pour analyse manuelle.
 
-#[allow(dead_code)]  // Usage futur ou requêtes manuelles SQL
-/// Liste toutes les sessions actuellement ouvertes (connexion sans déconnexion associée).
+/// Liste toutes les sessions actuellement ouvertes.
 /// 
 /// **Objectif** : Monitoring en temps réel - identifier les utilisateurs actuellement connectés.
 /// 
 /// **Logique** :
-/// - Recherche toutes les connexions (action='C') dans events_today
-/// - Exclut celles qui ont une déconnexion (action='D') associée via session_uuid
-/// - Trie par hostname puis timestamp (plus anciennes en premier par machine)
+/// - Lecture de la table sessions, sessions sans fin (index partiel idx_sessions_open)
+/// - Trie par hostname puis début de session (plus anciennes en premier par machine)
 /// 
 /// **Paramètres** : Aucun
 /// 
 /// **Colonnes retournées** :
 /// - `username` : Nom d'utilisateur
 /// - `hostname` : Nom de la machine
-/// - `connected_at` : Date/heure de connexion (alias de timestamp)
+/// - `connected_at` : Date/heure de connexion (alias de started_at)
 /// - `session_uuid` : Identifiant de session
-/// - `source_ip` : Adresse IP source
+/// - `source_ip` : Adresse IP source de la connexion
 /// - `os_name` : Nom du système d'exploitation
 /// - `os_version` : Version du système d'exploitation
+/// - `locked_at` : Début du verrouillage en cours (NULL si l'écran est déverrouillé)
+/// - `locked_seconds` : Durée cumulée des verrouillages terminés (secondes)
 /// 
 /// **Utilisé dans** : `handlers.rs::get_current_sessions()` (endpoint GET /api/v1/sessions/current)
 pub const SQL_LIST_OPEN_SESSIONS: &str = r#"
     SELECT 
         username,
         hostname,
-        timestamp AS connected_at,
+        started_at AS connected_at,
         session_uuid,
-        source_ip,
+        start_ip AS source_ip,
         os_name,
-        os_version
-    FROM events_today
-    WHERE action = 'C'
-      AND NOT EXISTS (
-          SELECT 1 FROM events_today e2
-          WHERE e2.session_uuid = events_today.session_uuid
-            AND e2.action = 'D'
-      )
-    ORDER BY hostname ASC, timestamp ASC
+        os_version,
+        locked_at,
+        locked_seconds
+    FROM sessions
+    WHERE ended_at IS NULL
+    ORDER BY hostname ASC, started_at ASC
 "#;
 
 #[allow(dead_code)]  // Usage futur ou requêtes manuelles SQL
-/// Calcule la durée des sessions terminées (avec connexion ET déconnexion).
+/// Calcule la durée et le temps actif des sessions terminées.
 /// 
 /// **Objectif** : Analyse des temps de session - statistiques d'utilisation.
 /// 
 /// **Logique** :
-/// - Joint les connexions (c.action='C') avec leurs déconnexions (d.action='D')
-/// - Calcule la durée en minutes : (julianday(disconnect) - julianday(connect)) * 1440
+/// - Lecture de la table sessions, sessions terminées uniquement
+/// - Durée en minutes : (julianday(ended_at) - julianday(started_at)) * 1440
+/// - Temps actif : durée moins le temps écran verrouillé (`locked_seconds`, actions 'L'/'U')
 /// - Limite aux 50 sessions les plus récentes par défaut
 /// 
 /// **Paramètres** : Aucun (mais LIMIT peut être ajusté)
@@ -239,23 +1365,22 @@ pub const SQL_LIST_OPEN_SESSIONS: &str = r#"
 /// - `connected_at` : Timestamp de connexion
 /// - `disconnected_at` : Timestamp de déconnexion
 /// - `duration_minutes` : Durée de la session en minutes (arrondi)
+/// - `active_minutes` : Temps actif (écran déverrouillé) en minutes (arrondi)
 /// - `session_uuid` : Identifiant de session
 /// 
 /// **Usage** : Requête manuelle ou future API d'analyse
 pub const SQL_SESSION_DURATIONS: &str = r#"
     SELECT 
-        c.username,
-        c.hostname,
-        c.timestamp AS connected_at,
-        d.timestamp AS disconnected_at,
-        ROUND((julianday(d.timestamp) - julianday(c.timestamp)) * 1440, 2) AS duration_minutes,
-        c.session_uuid
-    FROM events_today c
-    INNER JOIN events_today d 
-        ON c.session_uuid = d.session_uuid 
-       AND d.action = 'D'
-    WHERE c.action = 'C'
-    ORDER BY c.timestamp DESC
+        username,
+        hostname,
+        started_at AS connected_at,
+        ended_at AS disconnected_at,
+        ROUND((julianday(ended_at) - julianday(started_at)) * 1440, 2) AS duration_minutes,
+        ROUND((julianday(ended_at) - julianday(started_at)) * 1440 - locked_seconds / 60.0, 2) AS active_minutes,
+        session_uuid
+    FROM sessions
+    WHERE ended_at IS NOT NULL
+    ORDER BY started_at DESC
     LIMIT 50
 "#;
 
@@ -0,0 +1,199 @@
+//! # Module de limitation de débit
+//!
+//! Seaux à jetons (token buckets) en mémoire, indexés par adresse IP source et
+//! par `username@hostname`. Protège la base contre un client qui boucle (GPO mal
+//! configurée relançant `logon.exe`) : chaque connexion superflue déclencherait
+//! sinon une déconnexion automatique.
+//!
+//! Chaque clé dispose d'une réserve de `burst` jetons, rechargée au rythme de
+//! `per_minute` jetons par minute. Une requête consomme un jeton ; sans jeton
+//! disponible, elle est refusée avec le délai d'attente avant le prochain.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::config::LimitsConfig;
+
+/// Au-delà de ce nombre de clés suivies, les seaux pleins (clés inactives) sont purgés
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Limiteurs de l'ingestion d'événements
+pub struct RateLimits {
+    pub per_ip: TokenBuckets,
+    pub per_host: TokenBuckets,
+}
+
+impl RateLimits {
+    /// Construit les limiteurs à partir de la section `[limits]`
+    pub fn new(config: &LimitsConfig) -> Self {
+        Self {
+            per_ip: TokenBuckets::new(config.per_ip_burst, config.per_ip_per_minute),
+            per_host: TokenBuckets::new(config.per_host_burst, config.per_host_per_minute),
+        }
+    }
+}
+
+/// Ensemble de seaux à jetons indexés par clé
+pub struct TokenBuckets {
+    capacity: f64,
+    refill_per_sec: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    rejected: AtomicU64,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBuckets {
+    /// Crée un limiteur (`per_minute = 0` désactive la limite)
+    pub fn new(burst: u32, per_minute: u32) -> Self {
+        Self {
+            capacity: f64::from(burst.max(1)),
+            refill_per_sec: f64::from(per_minute) / 60.0,
+            buckets: Mutex::new(HashMap::new()),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// Indique si la limite est active
+    pub fn is_enabled(&self) -> bool {
+        self.refill_per_sec > 0.0
+    }
+
+    /// Consomme un jeton pour `key`
+    ///
+    /// # Retourne
+    /// - `Ok(())` si la requête est autorisée
+    /// - `Err(secondes)` : délai (arrondi au supérieur) avant le prochain jeton
+    pub fn check(&self, key: &str) -> Result<(), u64> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        if buckets.len() > PRUNE_THRESHOLD {
+            self.prune(&mut buckets, now);
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: self.capacity,
+            updated_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            let wait = (1.0 - bucket.tokens) / self.refill_per_sec;
+            Err(wait.ceil().max(1.0) as u64)
+        }
+    }
+
+    /// Nombre total de requêtes refusées depuis le démarrage
+    pub fn rejected_count(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Supprime les seaux redevenus pleins (aucune information à conserver)
+    fn prune(&self, buckets: &mut HashMap<String, Bucket>, now: Instant) {
+        buckets.retain(|_, bucket| {
+            let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+            bucket.tokens + elapsed * self.refill_per_sec < self.capacity
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    /// Recule l'horodatage du seau de `key`, comme si `elapsed` s'était écoulé
+    fn age(limiter: &TokenBuckets, key: &str, elapsed: Duration) {
+        let mut buckets = limiter.buckets.lock().unwrap();
+        let bucket = buckets.get_mut(key).expect("seau existant");
+        bucket.updated_at -= elapsed;
+    }
+
+    #[test]
+    fn burst_then_rejects_with_retry_after() {
+        let limiter = TokenBuckets::new(3, 6);
+        for _ in 0..3 {
+            assert_eq!(limiter.check("10.0.0.1"), Ok(()));
+        }
+        // 6 jetons par minute : un jeton toutes les 10 secondes
+        assert_eq!(limiter.check("10.0.0.1"), Err(10));
+        assert_eq!(limiter.rejected_count(), 1);
+    }
+
+    #[test]
+    fn keys_are_independent() {
+        let limiter = TokenBuckets::new(1, 60);
+        assert_eq!(limiter.check("alice@PC-01"), Ok(()));
+        assert!(limiter.check("alice@PC-01").is_err());
+        assert_eq!(limiter.check("bob@PC-01"), Ok(()));
+    }
+
+    #[test]
+    fn disabled_limit_accepts_everything() {
+        let limiter = TokenBuckets::new(1, 0);
+        assert!(!limiter.is_enabled());
+        for _ in 0..100 {
+            assert_eq!(limiter.check("10.0.0.1"), Ok(()));
+        }
+        assert_eq!(limiter.rejected_count(), 0);
+    }
+
+    #[test]
+    fn zero_burst_still_allows_one_request() {
+        let limiter = TokenBuckets::new(0, 60);
+        assert_eq!(limiter.check("10.0.0.1"), Ok(()));
+        assert!(limiter.check("10.0.0.1").is_err());
+    }
+
+    #[test]
+    fn tokens_refill_over_time_up_to_burst() {
+        let limiter = TokenBuckets::new(2, 60);
+        assert_eq!(limiter.check("10.0.0.1"), Ok(()));
+        assert_eq!(limiter.check("10.0.0.1"), Ok(()));
+        assert!(limiter.check("10.0.0.1").is_err());
+
+        // Une inactivité prolongée ne rend que `burst` jetons
+        age(&limiter, "10.0.0.1", Duration::from_secs(10));
+        assert_eq!(limiter.check("10.0.0.1"), Ok(()));
+        assert_eq!(limiter.check("10.0.0.1"), Ok(()));
+        assert!(limiter.check("10.0.0.1").is_err());
+    }
+
+    #[test]
+    fn retry_after_is_rounded_up_to_at_least_one_second() {
+        let limiter = TokenBuckets::new(1, 120);
+        assert_eq!(limiter.check("10.0.0.1"), Ok(()));
+        assert_eq!(limiter.check("10.0.0.1"), Err(1));
+    }
+
+    #[test]
+    fn prune_drops_only_full_buckets() {
+        let limiter = TokenBuckets::new(2, 60);
+        assert_eq!(limiter.check("idle"), Ok(()));
+        assert_eq!(limiter.check("busy"), Ok(()));
+        age(&limiter, "idle", Duration::from_secs(10));
+
+        let mut buckets = limiter.buckets.lock().unwrap();
+        limiter.prune(&mut buckets, Instant::now());
+        assert!(!buckets.contains_key("idle"));
+        assert!(buckets.contains_key("busy"));
+    }
+}
@@ -0,0 +1,238 @@
+//! # Conservation des données
+//!
+//! Applique les durées de conservation de `[database.retention]` : les
+//! événements de events_history plus anciens que la durée fixée pour leur type
+//! d'action sont supprimés, puis les sessions terminées qui n'ont plus aucun
+//! événement. Le journal orphan_repairs et les machines de host_clocks qui ne
+//! sont plus mesurées sont purgés de la même manière (`orphan_repairs_days`,
+//! `host_clocks_days`).
+//!
+//! Si `archive_dir` est configuré, les lignes expirées sont d'abord exportées
+//! en Parquet (module `archive`). La suppression se fait ensuite par lots (une
+//! transaction courte par lot, avec une pause entre deux lots) pour ne pas
+//! bloquer la collecte. Le bilan de chaque règle est journalisé dans la table
+//! purges.
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+
+use crate::archive::{self, ArchiveError};
+use crate::config::RetentionConfig;
+use crate::database::{Database, SQLITE_TIMESTAMP_FORMAT};
+use crate::models::Purge;
+
+/// Pause entre deux lots : laisse passer les écritures de la collecte
+const BATCH_PAUSE: Duration = Duration::from_millis(100);
+
+/// Lance la tâche de purge périodique (sauf si aucune règle n'est configurée)
+pub fn spawn_purger(db: Database, config: RetentionConfig) {
+    if !config.has_rules() {
+        tracing::info!("Aucune règle de conservation : données conservées indéfiniment");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let interval = Duration::from_secs(config.interval_hours * 3600);
+        loop {
+            run(&db, &config).await;
+            tracing::info!("Prochaine purge de conservation dans {} h", config.interval_hours);
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+/// Applique toutes les règles de conservation puis purge les sessions
+async fn run(db: &Database, config: &RetentionConfig) {
+    let now = Utc::now();
+    let purged_at = now.format(SQLITE_TIMESTAMP_FORMAT).to_string();
+    let stamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let archive_files = config.archive_dir.as_ref().map(|_| 0);
+
+    let cutoff = |days: u32| {
+        (now - chrono::Duration::days(i64::from(days)))
+            .format("%Y-%m-%dT%H:%M:%SZ")
+            .to_string()
+    };
+
+    for (action, days) in &config.days {
+        let mut purge = Purge {
+            purged_at: purged_at.clone(),
+            target: "events_history",
+            action: Some(*action),
+            retention_days: Some(*days),
+            cutoff: Some(cutoff(*days)),
+            deleted: 0,
+            batches: 0,
+            archive_files,
+            duration_ms: 0,
+            error: None,
+        };
+        let started = Instant::now();
+        if let Err(e) = purge_history(db, config, &stamp, &mut purge).await {
+            purge.error = Some(e.to_string());
+        }
+        finish(db, purge, started).await;
+    }
+
+    let mut purge = Purge {
+        purged_at: purged_at.clone(),
+        target: "sessions",
+        action: None,
+        retention_days: None,
+        cutoff: None,
+        deleted: 0,
+        batches: 0,
+        archive_files: None,
+        duration_ms: 0,
+        error: None,
+    };
+    let started = Instant::now();
+    let batch_size = config.batch_size;
+    if let Err(e) = delete_in_batches(&mut purge, batch_size, || db.purge_sessions_batch(batch_size)).await {
+        purge.error = Some(e.to_string());
+    }
+    finish(db, purge, started).await;
+
+    if let Some(days) = config.orphan_repairs_days {
+        let mut purge = Purge {
+            purged_at: purged_at.clone(),
+            target: "orphan_repairs",
+            action: None,
+            retention_days: Some(days),
+            cutoff: Some(cutoff(days)),
+            deleted: 0,
+            batches: 0,
+            archive_files: None,
+            duration_ms: 0,
+            error: None,
+        };
+        let started = Instant::now();
+        let cutoff = purge.cutoff.clone().unwrap_or_default();
+        if let Err(e) =
+            delete_in_batches(&mut purge, batch_size, || db.purge_orphan_repairs_batch(&cutoff, batch_size)).await
+        {
+            purge.error = Some(e.to_string());
+        }
+        finish(db, purge, started).await;
+    }
+
+    if let Some(days) = config.host_clocks_days {
+        let mut purge = Purge {
+            purged_at,
+            target: "host_clocks",
+            action: None,
+            retention_days: Some(days),
+            cutoff: Some(cutoff(days)),
+            deleted: 0,
+            batches: 0,
+            archive_files: None,
+            duration_ms: 0,
+            error: None,
+        };
+        let started = Instant::now();
+        let cutoff = purge.cutoff.clone().unwrap_or_default();
+        if let Err(e) =
+            delete_in_batches(&mut purge, batch_size, || db.purge_host_clocks_batch(&cutoff, batch_size)).await
+        {
+            purge.error = Some(e.to_string());
+        }
+        finish(db, purge, started).await;
+    }
+}
+
+/// Archive (si `archive_dir` est configuré) puis supprime les événements expirés d'une action
+///
+/// Seules les lignes présentes au début de l'export sont supprimées : un
+/// échec d'archivage interrompt la règle avant toute suppression. Les lignes
+/// archivées sont marquées (`exported_at`) : si la suppression échoue, la
+/// purge suivante les supprime sans les exporter une seconde fois.
+async fn purge_history(
+    db: &Database,
+    config: &RetentionConfig,
+    stamp: &str,
+    purge: &mut Purge,
+) -> Result<(), ArchiveError> {
+    let (Some(action), Some(cutoff)) = (purge.action, purge.cutoff.clone()) else {
+        return Ok(());
+    };
+    let max_id = db.history_max_id().await?;
+
+    if let Some(archive_dir) = &config.archive_dir {
+        for date in db.expired_history_dates(action, &cutoff, max_id).await? {
+            let events = db.expired_history_on(action, &cutoff, max_id, &date).await?;
+            let (dir, partition, stamp) = (PathBuf::from(archive_dir), date.clone(), stamp.to_string());
+            let path = tokio::task::spawn_blocking(move || {
+                archive::write_partition(&dir, &partition, action, &stamp, &events)
+            })
+            .await
+            .map_err(|e| ArchiveError::Io(std::io::Error::other(e)))??;
+            db.mark_history_exported(&purge.purged_at, action, &cutoff, max_id, &date).await?;
+            tracing::debug!("Archive écrite : {}", path.display());
+            purge.archive_files = purge.archive_files.map(|files| files + 1);
+        }
+    }
+
+    let batch_size = config.batch_size;
+    delete_in_batches(purge, batch_size, || db.purge_history_batch(action, &cutoff, max_id, batch_size)).await?;
+    Ok(())
+}
+
+/// Répète `delete_batch` jusqu'à un lot incomplet, avec une pause entre deux lots
+async fn delete_in_batches<F, Fut>(purge: &mut Purge, batch_size: u32, delete_batch: F) -> Result<(), sqlx::Error>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<u64, sqlx::Error>>,
+{
+    loop {
+        let deleted = delete_batch().await?;
+        if deleted > 0 {
+            purge.deleted += deleted as i64;
+            purge.batches += 1;
+        }
+        if deleted < u64::from(batch_size) {
+            return Ok(());
+        }
+        tokio::time::sleep(BATCH_PAUSE).await;
+    }
+}
+
+/// Journalise le bilan d'une règle (logs et table purges)
+async fn finish(db: &Database, mut purge: Purge, started: Instant) {
+    purge.duration_ms = started.elapsed().as_millis() as i64;
+
+    match (&purge.error, &purge.action) {
+        (Some(e), _) => tracing::error!(
+            "❌ Échec de la purge {} ({} ligne(s) supprimée(s) avant l'erreur): {}",
+            purge.target,
+            purge.deleted,
+            e
+        ),
+        (None, Some(action)) => tracing::info!(
+            "✓ Purge events_history action {} : {} ligne(s) antérieure(s) au {} supprimée(s), {} fichier(s) d'archive ({} ms)",
+            action,
+            purge.deleted,
+            purge.cutoff.as_deref().unwrap_or_default(),
+            purge.archive_files.unwrap_or(0),
+            purge.duration_ms
+        ),
+        (None, None) if purge.target == "sessions" => tracing::info!(
+            "✓ Purge sessions : {} session(s) sans événement supprimée(s) ({} ms)",
+            purge.deleted,
+            purge.duration_ms
+        ),
+        (None, None) => tracing::info!(
+            "✓ Purge {} : {} ligne(s) antérieure(s) au {} supprimée(s) ({} ms)",
+            purge.target,
+            purge.deleted,
+            purge.cutoff.as_deref().unwrap_or_default(),
+            purge.duration_ms
+        ),
+    }
+
+    if let Err(e) = db.insert_purge(&purge).await {
+        tracing::error!("Impossible de journaliser la purge {}: {}", purge.target, e);
+    }
+}
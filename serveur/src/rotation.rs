@@ -0,0 +1,102 @@
+//! # Rotation quotidienne planifiée
+//!
+//! Déplace chaque jour events_today vers events_history, à l'heure `time` du
+//! fuseau `timezone` (section `[rotation]`). Remplace le script
+//! `rotate_daily.sh` (cron + sqlite3) : la rotation passe par le même pool que
+//! la collecte et est journalisée dans la table rotations.
+//!
+//! Au démarrage, une rotation manquée (serveur arrêté à l'heure prévue) est
+//! rattrapée immédiatement.
+
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+
+use crate::config::RotationConfig;
+use crate::database::Database;
+use crate::models::{Rotation, RotationTrigger};
+
+/// Lance la tâche de rotation planifiée (sauf si `enabled = false`)
+pub fn spawn_scheduler(db: Database, config: RotationConfig) {
+    if !config.enabled {
+        tracing::info!("Rotation planifiée désactivée (POST /api/v1/admin/rotate uniquement)");
+        return;
+    }
+
+    tokio::spawn(async move {
+        // Rattrapage : événements reçus avant la dernière rotation prévue
+        let previous = previous_run(&config, Utc::now());
+        match db.rotation_overdue(previous).await {
+            Ok(true) => {
+                tracing::warn!(
+                    "Rotation du {} manquée : rattrapage",
+                    previous.with_timezone(&config.timezone)
+                );
+                let _ = run(&db, &config, RotationTrigger::CatchUp).await;
+            }
+            Ok(false) => {}
+            Err(e) => tracing::error!("Vérification de la rotation manquée impossible: {}", e),
+        }
+
+        let mut next = next_run(&config, Utc::now());
+        loop {
+            tracing::info!("Prochaine rotation : {}", next.with_timezone(&config.timezone));
+            let wait = (next - Utc::now()).to_std().unwrap_or_default();
+            tokio::time::sleep(wait).await;
+
+            let _ = run(&db, &config, RotationTrigger::Scheduled).await;
+
+            // Depuis l'échéance passée : un réveil en avance ne relance pas la même rotation
+            next = next_run(&config, next.max(Utc::now()));
+        }
+    });
+}
+
+/// Exécute une rotation et journalise son résultat
+pub async fn run(db: &Database, config: &RotationConfig, trigger: RotationTrigger) -> Result<Rotation, sqlx::Error> {
+    let result = db.rotate(trigger, config.orphan_window_hours).await;
+    match &result {
+        Ok(rotation) => tracing::info!(
+            "✓ Rotation {:?} : {} événement(s) archivé(s), {} session(s) ouverte(s) reportée(s) ({} ms)",
+            trigger,
+            rotation.archived.unwrap_or(0),
+            rotation.open_sessions.unwrap_or(0),
+            rotation.duration_ms
+        ),
+        Err(e) => tracing::error!("❌ Échec de la rotation {:?}: {}", trigger, e),
+    }
+    result
+}
+
+/// Instant (UTC) de la rotation prévue le jour `date` du fuseau configuré
+fn scheduled_on(config: &RotationConfig, date: NaiveDate) -> DateTime<Utc> {
+    let local = date.and_time(config.time);
+    config
+        .timezone
+        .from_local_datetime(&local)
+        .earliest()
+        // Heure inexistante (passage à l'heure d'été) : une heure plus tard
+        .or_else(|| config.timezone.from_local_datetime(&(local + Duration::hours(1))).earliest())
+        .map(|at| at.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&local))
+}
+
+/// Première rotation prévue strictement après `now`
+fn next_run(config: &RotationConfig, now: DateTime<Utc>) -> DateTime<Utc> {
+    let today = now.with_timezone(&config.timezone).date_naive();
+    let at = scheduled_on(config, today);
+    if at > now {
+        at
+    } else {
+        scheduled_on(config, today + Duration::days(1))
+    }
+}
+
+/// Dernière rotation prévue au plus tard à `now`
+fn previous_run(config: &RotationConfig, now: DateTime<Utc>) -> DateTime<Utc> {
+    let today = now.with_timezone(&config.timezone).date_naive();
+    let at = scheduled_on(config, today);
+    if at <= now {
+        at
+    } else {
+        scheduled_on(config, today - Duration::days(1))
+    }
+}
@@ -0,0 +1,151 @@
+//! # Module TLS (HTTPS natif)
+//!
+//! Construit la configuration rustls à partir de la section `[server.tls]` et
+//! la recharge à chaud lorsque les fichiers PEM changent sur le disque
+//! (renouvellement de certificat sans redémarrage du serveur).
+//!
+//! Si `client_ca_path` est renseigné, le serveur exige un certificat client
+//! signé par cette autorité (mTLS) : seuls les postes provisionnés peuvent
+//! établir une connexion.
+
+use axum_server::tls_rustls::RustlsConfig;
+use rustls::crypto::ring;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::config::TlsConfig;
+
+/// Charge la configuration TLS initiale
+///
+/// # Erreurs
+/// Retourne une erreur si un fichier est illisible ou invalide
+pub fn load(config: &TlsConfig) -> Result<RustlsConfig, TlsError> {
+    Ok(RustlsConfig::from_config(build_server_config(config)?))
+}
+
+/// Lance la tâche de rechargement à chaud des certificats
+///
+/// Vérifie périodiquement la date de modification des fichiers PEM. En cas de
+/// changement, reconstruit la configuration ; si les nouveaux fichiers sont
+/// invalides (copie en cours, clé ne correspondant pas...), l'ancienne
+/// configuration reste active et une erreur est journalisée.
+pub fn spawn_reload_task(rustls_config: RustlsConfig, config: TlsConfig) {
+    if config.reload_interval_secs == 0 {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(config.reload_interval_secs));
+        interval.tick().await; // Le premier tick est immédiat
+        let mut last_modified = files_modified_at(&config);
+
+        loop {
+            interval.tick().await;
+
+            let modified = files_modified_at(&config);
+            if modified == last_modified {
+                continue;
+            }
+
+            match build_server_config(&config) {
+                Ok(server_config) => {
+                    rustls_config.reload_from_config(server_config);
+                    last_modified = modified;
+                    tracing::info!("🔐 Certificats TLS rechargés ({})", config.cert_path);
+                }
+                Err(e) => {
+                    tracing::error!("Rechargement TLS impossible, configuration précédente conservée: {}", e);
+                }
+            }
+        }
+    });
+}
+
+/// Construit la configuration rustls (certificat, clé, vérification client optionnelle)
+fn build_server_config(config: &TlsConfig) -> Result<Arc<ServerConfig>, TlsError> {
+    let provider = Arc::new(ring::default_provider());
+
+    let certs = load_certs(&config.cert_path)?;
+    let key = load_private_key(&config.key_path)?;
+
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| TlsError::Rustls(e.to_string()))?;
+
+    let builder = match &config.client_ca_path {
+        Some(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(ca_path)? {
+                roots
+                    .add(cert)
+                    .map_err(|e| TlsError::Rustls(format!("{}: {}", ca_path, e)))?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .map_err(|e| TlsError::Rustls(e.to_string()))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut server_config = builder
+        .with_single_cert(certs, key)
+        .map_err(|e| TlsError::Rustls(e.to_string()))?;
+
+    // ALPN : obligatoire avec RustlsConfig::from_config
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(Arc::new(server_config))
+}
+
+/// Lit tous les certificats d'un fichier PEM
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let file = File::open(path).map_err(|e| TlsError::FileRead(path.to_string(), e.to_string()))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| TlsError::FileRead(path.to_string(), e.to_string()))?;
+
+    if certs.is_empty() {
+        return Err(TlsError::NoCertificate(path.to_string()));
+    }
+    Ok(certs)
+}
+
+/// Lit la première clé privée d'un fichier PEM
+fn load_private_key(path: &str) -> Result<PrivateKeyDer<'static>, TlsError> {
+    let file = File::open(path).map_err(|e| TlsError::FileRead(path.to_string(), e.to_string()))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .map_err(|e| TlsError::FileRead(path.to_string(), e.to_string()))?
+        .ok_or_else(|| TlsError::NoPrivateKey(path.to_string()))
+}
+
+/// Dates de modification des fichiers surveillés (None si illisible)
+fn files_modified_at(config: &TlsConfig) -> Vec<Option<SystemTime>> {
+    [Some(&config.cert_path), Some(&config.key_path), config.client_ca_path.as_ref()]
+        .into_iter()
+        .flatten()
+        .map(|path| Path::new(path).metadata().and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+/// Erreurs de chargement TLS
+#[derive(Debug, thiserror::Error)]
+pub enum TlsError {
+    #[error("Lecture de {0} impossible: {1}")]
+    FileRead(String, String),
+
+    #[error("Aucun certificat PEM dans {0}")]
+    NoCertificate(String),
+
+    #[error("Aucune clé privée PEM dans {0}")]
+    NoPrivateKey(String),
+
+    #[error("Configuration rustls invalide: {0}")]
+    Rustls(String),
+}